@@ -0,0 +1,441 @@
+//! Byte channels between TCP sockets and the application. Each port has a
+//! socket that accepts one connection at a time: the bytes it receives are
+//! forwarded to the application, and the bytes the application sends are
+//! written back, until the remote closes the connection and the socket listens
+//! again. It only needs a smoltcp `SocketSet`, so it's tested on smoltcp's
+//! loopback device rather than the USB ethernet device.
+
+use smoltcp::{
+    iface::{ SocketHandle, SocketSet },
+    socket::tcp,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SendError {
+    Full,
+    Closed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReceiveError {
+    Empty,
+    Closed,
+}
+
+/// The application's end that the bytes received on the socket go to
+pub trait ByteSender {
+    fn try_send(&mut self, byte: u8) -> Result<(), SendError>;
+}
+
+/// The application's end that the bytes to send on the socket come from
+pub trait ByteReceiver {
+    fn try_recv(&mut self) -> Result<u8, ReceiveError>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecvChannelState {
+    Listening,
+    Receiving,
+    Closing,
+}
+
+/// Forwards bytes from the socket to the application, N at a time
+pub struct RecvChannel<S, const N: usize> {
+    port: u16,
+    handle: SocketHandle,
+    sender: S,
+    state: RecvChannelState,
+}
+
+impl <S: ByteSender, const N: usize> RecvChannel<S, N> {
+    /// Starts the socket listening on the port
+    pub fn new(port: u16, handle: SocketHandle, sender: S, sockets: &mut SocketSet<'_>) -> Self {
+        sockets.get_mut::<tcp::Socket>(handle).listen(port).ok();
+        RecvChannel { port, handle, sender, state: RecvChannelState::Listening }
+    }
+
+    pub fn state(&self) -> RecvChannelState {
+        self.state
+    }
+
+    /// Forwards as many received bytes as the application has room for. The
+    /// rest stay in the socket for the next call. Returns true if any were
+    /// forwarded, so there's an ack to send.
+    pub fn try_recv(&mut self, sockets: &mut SocketSet<'_>) -> bool {
+        let socket: &mut tcp::Socket = sockets.get_mut(self.handle);
+        if !self.may_recv(socket) {
+            return false;
+        }
+
+        let mut buf = [0u8; N];
+        // peek at the bytes, because we don't know how many we can forward
+        let received = match socket.peek_slice(&mut buf[..]) {
+            Ok(received) => received,
+            Err(_err) => {
+                log!(warn, "Error peeking socket input on {}: {}", self.port, _err);
+                return false;
+            },
+        };
+
+        let mut consumed: usize = 0;
+        for byte in &buf[..received] {
+            match self.sender.try_send(*byte) {
+                Ok(()) => consumed += 1,
+                Err(SendError::Full) => break,
+                Err(SendError::Closed) => {
+                    // Nothing will read the bytes, so don't accept any more
+                    log!(error, "application closed on {}, aborting connection", self.port);
+                    socket.abort();
+                    self.state = RecvChannelState::Closing;
+                    return false;
+                },
+            }
+        }
+
+        // Read however many bytes we could send to the channel
+        socket.recv_slice(&mut buf[..consumed]).ok();
+        if consumed < received {
+            log!(debug, "sender is full. received {}, consumed {} for {}", received, consumed, self.port);
+        } else {
+            log!(trace, "consumed {} bytes on {}", consumed, self.port);
+        }
+        consumed > 0
+    }
+
+    fn may_recv(&mut self, socket: &mut tcp::Socket<'_>) -> bool {
+        // If the remote closes the socket, we close the socket too, and return to the
+        // listenning state. It may not be necessary to track the state of the channel
+        // separately, but it's simpler (the socket state is complicated), and it makes
+        // logging the transitions possible. may_recv() stays true after the remote
+        // closes until everything it sent has been received.
+        let (state, may_recv) = match (self.state, socket.may_recv()) {
+            (RecvChannelState::Listening, true) => {
+                log!(info, "accepted connection, state: {} on {}", socket.state(), self.port);
+                (RecvChannelState::Receiving, true)
+            },
+            (RecvChannelState::Receiving, false) => {
+                log!(info, "remote closed socket, state: {}, closing", socket.state());
+                socket.close();
+                (RecvChannelState::Closing, false)
+            },
+            (RecvChannelState::Closing, _) => {
+                match socket.is_active() {
+                    true => (RecvChannelState::Closing, false),
+                    false => {
+                        log!(info, "socket closed, state {}, listenning on {}", socket.state(), self.port);
+                        socket.listen(self.port).ok();
+                        (RecvChannelState::Listening, false)
+                    }
+                }
+            },
+            (state, receive) => (state, receive)
+        };
+
+        self.state = state;
+        may_recv
+    }
+}
+
+/// Writes the bytes from the application to the socket
+pub struct SendChannel<R> {
+    handle: SocketHandle,
+    receiver: R,
+}
+
+impl <R: ByteReceiver> SendChannel<R> {
+    pub fn new(handle: SocketHandle, receiver: R) -> Self {
+        SendChannel { handle, receiver }
+    }
+
+    /// Writes as much as the socket has room for, or discards it all when
+    /// there's no connection to send it on. Returns true if anything was
+    /// written, or an error if the application's end has closed.
+    pub fn try_send(&mut self, sockets: &mut SocketSet<'_>) -> Result<bool, ReceiveError> {
+        let socket: &mut tcp::Socket = sockets.get_mut(self.handle);
+
+        if socket.may_send() {
+            let mut count: usize = 0;
+            while socket.can_send() {
+                match self.receiver.try_recv() {
+                    Ok(data) => {
+                        socket.send_slice(&[data]).ok();
+                        count += 1;
+                    },
+                    Err(ReceiveError::Empty) => break,
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(count != 0)
+        } else {
+            loop {
+                match self.receiver.try_recv() {
+                    Ok(_) => { },
+                    Err(ReceiveError::Empty) => return Ok(false),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ cell::RefCell, collections::VecDeque, rc::Rc, vec, vec::Vec };
+
+    use smoltcp::{
+        iface::{ Config, Interface },
+        phy::{ Loopback, Medium },
+        time::{ Duration, Instant },
+        wire::{ HardwareAddress, IpAddress, IpCidr },
+    };
+
+    use super::*;
+
+    const PORT: u16 = 1338;
+    const SERVER: (IpAddress, u16) = (IpAddress::v4(127, 0, 0, 1), PORT);
+
+    // One end of an application channel with room for `capacity` bytes
+    #[derive(Clone)]
+    struct Queue {
+        bytes: Rc<RefCell<VecDeque<u8>>>,
+        capacity: usize,
+        closed: Rc<RefCell<bool>>,
+    }
+
+    impl Queue {
+        fn new(capacity: usize) -> Self {
+            Queue { bytes: Default::default(), capacity, closed: Default::default() }
+        }
+
+        fn drain(&self) -> Vec<u8> {
+            self.bytes.borrow_mut().drain(..).collect()
+        }
+
+        fn push(&self, bytes: &[u8]) {
+            self.bytes.borrow_mut().extend(bytes);
+        }
+
+        fn close(&self) {
+            *self.closed.borrow_mut() = true;
+        }
+    }
+
+    impl ByteSender for Queue {
+        fn try_send(&mut self, byte: u8) -> Result<(), SendError> {
+            let mut bytes = self.bytes.borrow_mut();
+            match (*self.closed.borrow(), bytes.len() < self.capacity) {
+                (true, _) => Err(SendError::Closed),
+                (false, false) => Err(SendError::Full),
+                (false, true) => {
+                    bytes.push_back(byte);
+                    Ok(())
+                },
+            }
+        }
+    }
+
+    impl ByteReceiver for Queue {
+        fn try_recv(&mut self) -> Result<u8, ReceiveError> {
+            match (self.bytes.borrow_mut().pop_front(), *self.closed.borrow()) {
+                (Some(byte), _) => Ok(byte),
+                (None, true) => Err(ReceiveError::Closed),
+                (None, false) => Err(ReceiveError::Empty),
+            }
+        }
+    }
+
+    fn socket<'a>() -> tcp::Socket<'a> {
+        tcp::Socket::new(tcp::SocketBuffer::new(vec![0; 64]), tcp::SocketBuffer::new(vec![0; 64]))
+    }
+
+    // A server channel and clients on the loopback device
+    struct Loop {
+        device: Loopback,
+        interface: Interface,
+        sockets: SocketSet<'static>,
+        now: Instant,
+        recv: RecvChannel<Queue, 16>,
+        send: SendChannel<Queue>,
+        // What the application received, and what it's sending
+        received: Queue,
+        sending: Queue,
+    }
+
+    impl Loop {
+        fn new(capacity: usize) -> Self {
+            let mut device = Loopback::new(Medium::Ip);
+            let now = Instant::from_millis(0);
+            let mut interface = Interface::new(Config::new(HardwareAddress::Ip), &mut device, now);
+            interface.update_ip_addrs(|addrs| addrs.push(IpCidr::new(SERVER.0, 8)).unwrap());
+            let mut sockets = SocketSet::new(vec![]);
+            let server = sockets.add(socket());
+            let (received, sending) = (Queue::new(capacity), Queue::new(capacity));
+            let recv = RecvChannel::new(PORT, server, received.clone(), &mut sockets);
+            let send = SendChannel::new(server, sending.clone());
+            Loop { device, interface, sockets, now, recv, send, received, sending }
+        }
+
+        fn poll(&mut self) {
+            for _ in 0..20 {
+                self.now += Duration::from_millis(10);
+                self.interface.poll(self.now, &mut self.device, &mut self.sockets);
+                self.recv.try_recv(&mut self.sockets);
+                self.send.try_send(&mut self.sockets).unwrap();
+            }
+        }
+
+        fn connect(&mut self, local_port: u16) -> SocketHandle {
+            let client = self.sockets.add(socket());
+            let context = self.interface.context();
+            self.sockets.get_mut::<tcp::Socket>(client).connect(context, SERVER, local_port).unwrap();
+            self.poll();
+            client
+        }
+
+        fn client(&mut self, client: SocketHandle) -> &mut tcp::Socket<'static> {
+            self.sockets.get_mut(client)
+        }
+
+        fn client_send(&mut self, client: SocketHandle, bytes: &[u8]) {
+            assert_eq!(self.client(client).send_slice(bytes), Ok(bytes.len()));
+            self.poll();
+        }
+
+        fn client_recv(&mut self, client: SocketHandle) -> Vec<u8> {
+            let mut buf = [0u8; 64];
+            let count = self.client(client).recv_slice(&mut buf).unwrap_or(0);
+            buf[..count].to_vec()
+        }
+    }
+
+    #[test]
+    fn transfers_both_ways() {
+        let mut net = Loop::new(16);
+        let client = net.connect(49152);
+        assert_eq!(net.recv.state(), RecvChannelState::Receiving);
+
+        net.client_send(client, b"$$\n");
+        assert_eq!(net.received.drain(), b"$$\n");
+
+        net.sending.push(b"ok\n");
+        net.poll();
+        assert_eq!(net.client_recv(client), b"ok\n");
+    }
+
+    #[test]
+    fn full_application_channel() {
+        let mut net = Loop::new(4);
+        let client = net.connect(49152);
+        net.client_send(client, b"0123456789");
+
+        // the rest wait in the socket until there's room
+        let mut received = net.received.drain();
+        assert_eq!(received, b"0123");
+        while received.len() < 10 {
+            net.poll();
+            let more = net.received.drain();
+            assert!(!more.is_empty());
+            received.extend(more);
+        }
+        assert_eq!(received, b"0123456789");
+    }
+
+    #[test]
+    fn disconnect_mid_transfer() {
+        let mut net = Loop::new(4);
+        let client = net.connect(49152);
+        net.client_send(client, b"0123456789");
+        net.client(client).abort();
+        net.poll();
+
+        // what arrived before the reset is still delivered, then it listens again
+        let mut received = net.received.drain();
+        for _ in 0..10 {
+            net.poll();
+            received.extend(net.received.drain());
+        }
+        assert_eq!(received, b"0123456789");
+        assert_eq!(net.recv.state(), RecvChannelState::Listening);
+
+        let client = net.connect(49153);
+        assert_eq!(net.recv.state(), RecvChannelState::Receiving);
+        net.client_send(client, b"new");
+        assert_eq!(net.received.drain(), b"new");
+    }
+
+    #[test]
+    fn reconnect() {
+        let mut net = Loop::new(16);
+        let client = net.connect(49152);
+        net.client(client).close();
+        net.poll();
+        assert_eq!(net.recv.state(), RecvChannelState::Listening);
+
+        let client = net.connect(49153);
+        net.client_send(client, b"again");
+        assert_eq!(net.received.drain(), b"again");
+        net.sending.push(b"ok");
+        net.poll();
+        assert_eq!(net.client_recv(client), b"ok");
+    }
+
+    #[test]
+    fn remote_half_close() {
+        let mut net = Loop::new(16);
+        let client = net.connect(49152);
+        // the FIN follows the data
+        net.client(client).send_slice(b"bye").unwrap();
+        net.client(client).close();
+        net.poll();
+
+        assert_eq!(net.received.drain(), b"bye");
+        assert_eq!(net.recv.state(), RecvChannelState::Listening);
+        assert!(!net.client(client).may_recv());
+    }
+
+    #[test]
+    fn half_close_with_full_channel() {
+        let mut net = Loop::new(2);
+        let client = net.connect(49152);
+        net.client(client).send_slice(b"abcdef").unwrap();
+        net.client(client).close();
+        net.poll();
+
+        // everything sent before the FIN is still delivered
+        let mut received = net.received.drain();
+        while net.recv.state() == RecvChannelState::Receiving {
+            net.poll();
+            received.extend(net.received.drain());
+        }
+        assert_eq!(received, b"abcdef");
+    }
+
+    #[test]
+    fn discards_without_connection() {
+        let mut net = Loop::new(16);
+        net.sending.push(b"lost");
+        assert_eq!(net.send.try_send(&mut net.sockets), Ok(false));
+        assert!(net.sending.drain().is_empty());
+    }
+
+    #[test]
+    fn application_closed() {
+        let mut net = Loop::new(16);
+        let client = net.connect(49152);
+        net.received.close();
+        net.client_send(client, b"nobody");
+        assert_eq!(net.recv.state(), RecvChannelState::Listening);
+        assert!(!net.client(client).is_open());
+    }
+
+    #[test]
+    fn sender_closed() {
+        let mut net = Loop::new(16);
+        net.sending.close();
+        assert_eq!(net.send.try_send(&mut net.sockets), Err(ReceiveError::Closed));
+    }
+}
@@ -0,0 +1,248 @@
+//! A DHCP server for the point-to-point USB link. The device takes one address
+//! of a small subnet, a /30 by default, and offers the host another, so
+//! plugging the board into a host running a DHCP client just works, without a
+//! relay or routing set up on the host.
+//!
+//! There's only ever one host on the other end of the link, so every client
+//! is offered the same address, and nothing is remembered between requests.
+//! The offer has no router or DNS servers: the host keeps its own default
+//! route, and only reaches the device's subnet over the link.
+
+use smoltcp::wire::{
+    DhcpMessageType, DhcpPacket, DhcpRepr, Ipv4Address, Ipv4Cidr,
+};
+
+pub const LEASE_SECS: u32 = 24 * 60 * 60;
+
+/// The device's address when serving DHCP, unless another is configured
+pub const ADDRESS: Ipv4Cidr = Ipv4Cidr::new(Ipv4Address::new(192, 168, 7, 1), 30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Server {
+    address: Ipv4Cidr,
+    lease: Ipv4Address,
+}
+
+impl Server {
+    /// address: the device's address and the subnet it serves. None if the
+    /// subnet has no other address to offer.
+    pub fn new(address: Ipv4Cidr) -> Option<Self> {
+        let network = address.network().address().to_bits();
+        let broadcast = address.broadcast()?.to_bits();
+        let own = address.address().to_bits();
+        let lease = (network + 1..broadcast).find(|candidate| *candidate != own)?;
+        Some(Server { address, lease: Ipv4Address::from_bits(lease) })
+    }
+
+    pub fn address(&self) -> Ipv4Cidr {
+        self.address
+    }
+
+    /// The address offered to the host
+    pub fn lease(&self) -> Ipv4Address {
+        self.lease
+    }
+
+    /// Answers a packet received on the server port, writing the reply into
+    /// buffer. Returns where on the client port to send it and its length, or
+    /// None when there's nothing to reply.
+    pub fn process(&self, request: &[u8], buffer: &mut [u8]) -> Option<(Ipv4Address, usize)> {
+        let packet = DhcpPacket::new_checked(request).ok()?;
+        let request = DhcpRepr::parse(&packet).ok()?;
+
+        let message_type = match request.message_type {
+            DhcpMessageType::Discover => DhcpMessageType::Offer,
+            DhcpMessageType::Request => {
+                // A client in INIT-REBOOT or SELECTING asks for an address, and
+                // one that's renewing puts its address in ciaddr
+                let requested = request.requested_ip.unwrap_or(request.client_ip);
+                let ours = request.server_identifier
+                    .is_none_or(|server| server == self.address.address());
+                match (ours, requested == self.lease) {
+                    // Selected another server's offer
+                    (false, _) => return None,
+                    (true, true) => DhcpMessageType::Ack,
+                    (true, false) => DhcpMessageType::Nak,
+                }
+            },
+            _ => return None,
+        };
+        let (your_ip, subnet_mask, lease_duration) = match message_type {
+            DhcpMessageType::Nak => (Ipv4Address::UNSPECIFIED, None, None),
+            _ => (self.lease, Some(self.address.netmask()), Some(LEASE_SECS)),
+        };
+
+        let reply = DhcpRepr {
+            message_type,
+            transaction_id: request.transaction_id,
+            secs: 0,
+            client_hardware_address: request.client_hardware_address,
+            client_ip: Ipv4Address::UNSPECIFIED,
+            your_ip,
+            server_ip: Ipv4Address::UNSPECIFIED,
+            router: None,
+            subnet_mask,
+            relay_agent_ip: request.relay_agent_ip,
+            broadcast: request.broadcast,
+            requested_ip: None,
+            client_identifier: None,
+            server_identifier: Some(self.address.address()),
+            parameter_request_list: None,
+            dns_servers: None,
+            max_size: None,
+            lease_duration,
+            renew_duration: None,
+            rebind_duration: None,
+            additional_options: &[],
+        };
+        let len = reply.buffer_len();
+        let mut packet = DhcpPacket::new_unchecked(buffer.get_mut(..len)?);
+        reply.emit(&mut packet).ok()?;
+
+        // Until it's acknowledged, the client can't receive unicast to an
+        // address it doesn't have
+        let destination = match (message_type, request.client_ip) {
+            (DhcpMessageType::Ack, client) if client == self.lease => client,
+            _ => Ipv4Address::BROADCAST,
+        };
+        Some((destination, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::{ vec, vec::Vec };
+    use smoltcp::wire::EthernetAddress;
+
+    const CLIENT: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 1]);
+
+    fn request(message_type: DhcpMessageType) -> DhcpRepr<'static> {
+        DhcpRepr {
+            message_type,
+            transaction_id: 0x1234_5678,
+            secs: 0,
+            client_hardware_address: CLIENT,
+            client_ip: Ipv4Address::UNSPECIFIED,
+            your_ip: Ipv4Address::UNSPECIFIED,
+            server_ip: Ipv4Address::UNSPECIFIED,
+            router: None,
+            subnet_mask: None,
+            relay_agent_ip: Ipv4Address::UNSPECIFIED,
+            broadcast: false,
+            requested_ip: None,
+            client_identifier: Some(CLIENT),
+            server_identifier: None,
+            parameter_request_list: Some(&[1, 3, 6]),
+            dns_servers: None,
+            max_size: Some(1500),
+            lease_duration: None,
+            renew_duration: None,
+            rebind_duration: None,
+            additional_options: &[],
+        }
+    }
+
+    // Returns where the reply went, and the reply's bytes
+    fn exchange(request: DhcpRepr) -> Option<(Ipv4Address, Vec<u8>)> {
+        let mut bytes = vec![0u8; request.buffer_len()];
+        request.emit(&mut DhcpPacket::new_unchecked(&mut bytes[..])).unwrap();
+        let mut buffer = [0u8; 576];
+        let (destination, len) = server().process(&bytes, &mut buffer)?;
+        Some((destination, buffer[..len].to_vec()))
+    }
+
+    fn server() -> Server {
+        Server::new(ADDRESS).unwrap()
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(server().lease(), Ipv4Address::new(192, 168, 7, 2));
+        let server = Server::new(Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 2), 30)).unwrap();
+        assert_eq!(server.lease(), Ipv4Address::new(10, 0, 0, 1));
+        let server = Server::new(Ipv4Cidr::new(Ipv4Address::new(10, 1, 0, 1), 24)).unwrap();
+        assert_eq!(server.lease(), Ipv4Address::new(10, 1, 0, 2));
+        assert_eq!(Server::new(Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 1), 31)), None);
+        assert_eq!(Server::new(Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 1), 32)), None);
+    }
+
+    #[test]
+    fn offer() {
+        let (destination, reply) = exchange(request(DhcpMessageType::Discover)).unwrap();
+        let packet = DhcpPacket::new_checked(&reply[..]).unwrap();
+        let offer = DhcpRepr::parse(&packet).unwrap();
+        assert_eq!(destination, Ipv4Address::BROADCAST);
+        assert_eq!(offer.message_type, DhcpMessageType::Offer);
+        assert_eq!(offer.transaction_id, 0x1234_5678);
+        assert_eq!(offer.client_hardware_address, CLIENT);
+        assert_eq!(offer.your_ip, Ipv4Address::new(192, 168, 7, 2));
+        assert_eq!(offer.subnet_mask, Some(Ipv4Address::new(255, 255, 255, 252)));
+        assert_eq!(offer.server_identifier, Some(Ipv4Address::new(192, 168, 7, 1)));
+        assert_eq!(offer.lease_duration, Some(LEASE_SECS));
+        assert_eq!(offer.router, None);
+    }
+
+    #[test]
+    fn acknowledge() {
+        let request = DhcpRepr {
+            requested_ip: Some(Ipv4Address::new(192, 168, 7, 2)),
+            server_identifier: Some(Ipv4Address::new(192, 168, 7, 1)),
+            ..request(DhcpMessageType::Request)
+        };
+        let (destination, reply) = exchange(request).unwrap();
+        let packet = DhcpPacket::new_checked(&reply[..]).unwrap();
+        let ack = DhcpRepr::parse(&packet).unwrap();
+        assert_eq!(destination, Ipv4Address::BROADCAST);
+        assert_eq!(ack.message_type, DhcpMessageType::Ack);
+        assert_eq!(ack.your_ip, Ipv4Address::new(192, 168, 7, 2));
+    }
+
+    #[test]
+    fn renew() {
+        let request = DhcpRepr {
+            client_ip: Ipv4Address::new(192, 168, 7, 2),
+            ..request(DhcpMessageType::Request)
+        };
+        let (destination, reply) = exchange(request).unwrap();
+        let packet = DhcpPacket::new_checked(&reply[..]).unwrap();
+        let ack = DhcpRepr::parse(&packet).unwrap();
+        assert_eq!(destination, Ipv4Address::new(192, 168, 7, 2));
+        assert_eq!(ack.message_type, DhcpMessageType::Ack);
+    }
+
+    #[test]
+    fn wrong_address() {
+        // Remembered from another network
+        let request = DhcpRepr {
+            requested_ip: Some(Ipv4Address::new(10, 0, 0, 7)),
+            ..request(DhcpMessageType::Request)
+        };
+        let (destination, reply) = exchange(request).unwrap();
+        let packet = DhcpPacket::new_checked(&reply[..]).unwrap();
+        let nak = DhcpRepr::parse(&packet).unwrap();
+        assert_eq!(destination, Ipv4Address::BROADCAST);
+        assert_eq!(nak.message_type, DhcpMessageType::Nak);
+        assert_eq!(nak.your_ip, Ipv4Address::UNSPECIFIED);
+    }
+
+    #[test]
+    fn another_server() {
+        let request = DhcpRepr {
+            requested_ip: Some(Ipv4Address::new(192, 168, 7, 2)),
+            server_identifier: Some(Ipv4Address::new(192, 168, 7, 99)),
+            ..request(DhcpMessageType::Request)
+        };
+        assert!(exchange(request).is_none());
+    }
+
+    #[test]
+    fn ignored() {
+        for message_type in [DhcpMessageType::Release, DhcpMessageType::Decline, DhcpMessageType::Offer] {
+            assert!(exchange(request(message_type)).is_none());
+        }
+        assert!(server().process(&[0u8; 10], &mut [0u8; 576]).is_none());
+    }
+}
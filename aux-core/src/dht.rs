@@ -0,0 +1,495 @@
+//! Decoding of the 5 byte packet sent by DHT11, DHT22/AM2302 and compatible
+//! sensors. The bytes are in the order they're sent: humidity high and low,
+//! temperature high and low, then the checksum.
+//!
+//! The packet is timed from the intervals between falling edges on the data
+//! line. The decoder waits for an interval that corresponds to a 'response' of
+//! ~160uS, then transitions into the Data state. In the data state, there's a
+//! fixed low of 50uS, followed by a high of 26-28uS for a 0, or 70uS for a 1,
+//! so it looks for an interval of ~77uS or ~120uS respectively.
+
+use crate::statistics::StatsAccumulator;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Sensor {
+    /// Integer and tenths bytes, with the sign in the top bit of the
+    /// temperature tenths on sensors that measure below zero.
+    Dht11,
+    /// 16 bit values in tenths, with the sign in the top bit of the
+    /// temperature. Also AM2302, DHT21/AM2301 and DHT33.
+    Dht22,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Reading {
+    pub temperature_celsius: f32,
+    pub humidity_percent: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChecksumError {
+    pub computed: u8,
+    pub received: u8,
+}
+
+const SIGN: u8 = 0x80;
+
+impl Sensor {
+    /// How long the host holds the line low to start a reading
+    pub const fn start_millis(self) -> u32 {
+        match self {
+            Sensor::Dht11 => 20,
+            Sensor::Dht22 => 2,
+        }
+    }
+
+    /// The shortest time between readings
+    pub const fn interval_millis(self) -> u32 {
+        match self {
+            Sensor::Dht11 => 1000,
+            Sensor::Dht22 => 2000,
+        }
+    }
+
+    pub fn decode(self, data: [u8; 5]) -> Result<Reading, ChecksumError> {
+        let computed = data[0]
+            .wrapping_add(data[1])
+            .wrapping_add(data[2])
+            .wrapping_add(data[3]);
+        if computed != data[4] {
+            return Err(ChecksumError { computed, received: data[4] });
+        }
+
+        let reading = match self {
+            Sensor::Dht11 => {
+                let temperature = data[2] as f32 + (data[3] & !SIGN) as f32 / 10.0;
+                Reading {
+                    humidity_percent: data[0] as f32 + data[1] as f32 / 10.0,
+                    temperature_celsius: signed(data[3], temperature),
+                }
+            },
+            Sensor::Dht22 => {
+                let temperature = u16::from_be_bytes([data[2] & !SIGN, data[3]]) as f32 / 10.0;
+                Reading {
+                    humidity_percent: u16::from_be_bytes([data[0], data[1]]) as f32 / 10.0,
+                    temperature_celsius: signed(data[2], temperature),
+                }
+            },
+        };
+        Ok(reading)
+    }
+}
+
+fn signed(byte: u8, magnitude: f32) -> f32 {
+    if byte & SIGN != 0 { -magnitude } else { magnitude }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet(u8, [u8;5]);
+
+impl Packet {
+    const FIRST: u8 = 40; // MSB first: network data order
+    const LAST: u8 = 0;
+
+    pub const fn new() -> Self {
+        Packet(Self::FIRST, [0;5])
+    }
+
+    fn append(&mut self, value: bool) {
+        self.0 -= 1;
+        let byte = (self.0>>3) as usize;
+        self.1[byte] = self.1[byte]<<1 | match value { false => 0, true => 1 };
+    }
+
+    fn complete(&self) -> bool {
+        self.0 == Self::LAST
+    }
+
+    /// How many bits have been received
+    pub fn bits(&self) -> u32 {
+        (Self::FIRST - self.0) as u32
+    }
+
+    /// The bytes are assembled last first, so reverse them into the order
+    /// they were sent
+    pub fn data(self) -> [u8; 5] {
+        let mut data = self.1;
+        data.reverse();
+        data
+    }
+}
+
+impl Default for Packet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputState {
+    Standby,
+    Data(Packet),
+    Error
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReadError {
+    Checksum,
+    Timeout,
+    /// The state, and the interval in uS that didn't fit it
+    Timing(InputState, u32),
+    Busy,
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Statistics {
+    pub interrupt: StatsAccumulator<u32,f32>,
+    pub response: StatsAccumulator<u32,f32>,
+    pub zero: StatsAccumulator<u32,f32>,
+    pub one: StatsAccumulator<u32,f32>,
+}
+
+impl Statistics {
+    pub const fn new() -> Self {
+        Statistics {
+            interrupt: StatsAccumulator::new(),
+            zero: StatsAccumulator::new(),
+            one: StatsAccumulator::new(),
+            response: StatsAccumulator::new(),
+        }
+    }
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct DurationRange {
+    min: u32,
+    max: u32,
+    med: u32,
+}
+
+impl DurationRange {
+    const fn micros(min: u32, max: u32) -> Self {
+        DurationRange { min, max, med: (min+max)/2 }
+    }
+
+    fn contains(self, value: u32) -> bool {
+        value > self.min && value < self.max
+    }
+}
+
+// Shorter than the start signal of any sensor, but longer than any part of the response
+const INITIATE: u32 = 1000;
+const RESPONSE: DurationRange = DurationRange::micros(150, 180);
+const DATA: DurationRange = DurationRange::micros(50, 150);
+
+/// Turns the intervals between falling edges into packets
+pub struct Decoder {
+    state: InputState,
+    pub statistics: Statistics,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Decoder { state: InputState::Standby, statistics: Statistics::new() }
+    }
+
+    /// Forgets any packet in progress
+    pub fn reset(&mut self) {
+        self.state = InputState::Standby;
+    }
+
+    /// Returns the packet when it's complete, or the error when the timing goes wrong.
+    /// interval: uS since the previous falling edge
+    pub fn interval(&mut self, interval: u32) -> Option<Result<Packet, ReadError>> {
+        let initial = self.state;
+        let (state, result) = self.updated(interval, initial);
+        self.state = state;
+        result
+    }
+
+    fn updated(&mut self, interval: u32, initial: InputState) -> (InputState, Option<Result<Packet, ReadError>>) {
+        use InputState::*;
+
+        match initial {
+            // Either this is initiate, or the interval between reads
+            Error | Standby if interval > INITIATE => (Standby, None),
+            Standby if RESPONSE.contains(interval) => {
+                self.statistics.response.add(interval as f32);
+                (Data(Packet::new()), None)
+            },
+            Data(mut packet)  if DATA.contains(interval) => {
+                let value = interval > DATA.med;
+                if value { &mut self.statistics.one } else { &mut self.statistics.zero }.add(interval as f32);
+                packet.append(value);
+                if packet.complete() {
+                    (Standby, Some(Ok(packet)))
+                } else {
+                    (Data(packet), None)
+                }
+            },
+            Standby | Data(_) => (Error, Some(Err(ReadError::Timing(initial, interval)))),
+            Error => (Error, None),
+        }
+    }
+}
+
+/// A free running counter of uS that edges are timestamped with. The decoder's
+/// limits are in uS, so unlike `frequency::Interval` the clock's rate is fixed,
+/// and only the width of the counter varies.
+pub trait Ticks: Copy {
+    const ZERO: Self;
+
+    /// uS since `earlier`, allowing for the counter wrapping. Intervals too
+    /// long for a u32 are only ever the gap between reads, so saturate.
+    fn since(self, earlier: Self) -> u32;
+}
+
+/// Wraps every ~72 minutes
+impl Ticks for u32 {
+    const ZERO: Self = 0;
+
+    fn since(self, earlier: Self) -> u32 {
+        self.wrapping_sub(earlier)
+    }
+}
+
+impl Ticks for u64 {
+    const ZERO: Self = 0;
+
+    fn since(self, earlier: Self) -> u32 {
+        self.wrapping_sub(earlier).try_into().unwrap_or(u32::MAX)
+    }
+}
+
+/// Decodes falling edges timestamped by a counter of uS
+pub struct Edges<T: Ticks> {
+    timestamp: T,
+    pub decoder: Decoder,
+}
+
+impl <T: Ticks> Default for Edges<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: Ticks> Edges<T> {
+    pub const fn new() -> Self {
+        Edges { timestamp: T::ZERO, decoder: Decoder::new() }
+    }
+
+    pub fn falling_edge(&mut self, ticks: T) -> Option<Result<Packet, ReadError>> {
+        let interval = ticks.since(self.timestamp);
+        self.timestamp = ticks;
+        self.decoder.interval(interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+    use super::*;
+
+    fn assert_reading(reading: Reading, temperature_celsius: f32, humidity_percent: f32) {
+        assert!((reading.temperature_celsius - temperature_celsius).abs() < 0.01,
+            "temperature {} != {}", reading.temperature_celsius, temperature_celsius);
+        assert!((reading.humidity_percent - humidity_percent).abs() < 0.01,
+            "humidity {} != {}", reading.humidity_percent, humidity_percent);
+    }
+
+    #[test]
+    fn dht11() {
+        let reading = Sensor::Dht11.decode([0x2d, 0x00, 0x17, 0x04, 0x48]).unwrap();
+        assert_reading(reading, 23.4, 45.0);
+    }
+
+    #[test]
+    fn dht11_below_zero() {
+        let reading = Sensor::Dht11.decode([0x1e, 0x00, 0x02, 0x85, 0xa5]).unwrap();
+        assert_reading(reading, -2.5, 30.0);
+    }
+
+    #[test]
+    fn dht22() {
+        // the example from the AM2302 datasheet
+        let reading = Sensor::Dht22.decode([0x02, 0x8c, 0x01, 0x5f, 0xee]).unwrap();
+        assert_reading(reading, 35.1, 65.2);
+    }
+
+    #[test]
+    fn dht22_below_zero() {
+        let reading = Sensor::Dht22.decode([0x01, 0xc3, 0x80, 0x65, 0xa9]).unwrap();
+        assert_reading(reading, -10.1, 45.1);
+    }
+
+    #[test]
+    fn dht22_above_25_5() {
+        // more than a byte of tenths
+        let reading = Sensor::Dht22.decode([0x03, 0xe8, 0x01, 0x00, 0xec]).unwrap();
+        assert_reading(reading, 25.6, 100.0);
+    }
+
+    #[test]
+    fn checksum_wraps() {
+        let reading = Sensor::Dht22.decode([0x03, 0xe8, 0x01, 0xf4, 0xe0]).unwrap();
+        assert_reading(reading, 50.0, 100.0);
+    }
+
+    #[test]
+    fn checksum_error() {
+        assert_eq!(
+            Sensor::Dht22.decode([0x02, 0x8c, 0x01, 0x5f, 0xef]),
+            Err(ChecksumError { computed: 0xee, received: 0xef }));
+    }
+
+    // The intervals between falling edges for a packet, from the host's start signal
+    fn intervals(data: [u8; 5]) -> impl Iterator<Item = u32> {
+        let bits = data.into_iter()
+            .flat_map(|byte| (0..8).rev().map(move |bit| byte & (1 << bit) != 0))
+            .map(|bit| if bit { 120 } else { 77 });
+        [20_000, 160].into_iter().chain(bits)
+    }
+
+    fn decode(decoder: &mut Decoder, intervals: impl Iterator<Item = u32>) -> Vec<Result<Packet, ReadError>> {
+        intervals.filter_map(|interval| decoder.interval(interval)).collect()
+    }
+
+    const PACKET: [u8; 5] = [0x02, 0x8c, 0x01, 0x5f, 0xee];
+
+    #[test]
+    fn edge_sequence() {
+        let mut decoder = Decoder::new();
+        let results = decode(&mut decoder, intervals(PACKET));
+        assert_eq!(results.len(), 1);
+        let packet = results[0].unwrap();
+        assert_eq!(packet.bits(), 40);
+        assert_eq!(packet.data(), PACKET);
+        assert_reading(Sensor::Dht22.decode(packet.data()).unwrap(), 35.1, 65.2);
+
+        let ones = PACKET.iter().map(|byte| byte.count_ones()).sum::<u32>();
+        assert_eq!(decoder.statistics.response.count(), 1);
+        assert_eq!(decoder.statistics.one.count(), ones);
+        assert_eq!(decoder.statistics.zero.count(), 40 - ones);
+        assert_eq!(decoder.statistics.one.mean(), 120.0);
+    }
+
+    #[test]
+    fn back_to_back() {
+        let mut decoder = Decoder::new();
+        let results = decode(&mut decoder, intervals(PACKET).chain(intervals([0x2d, 0x00, 0x17, 0x04, 0x48])));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].unwrap().data(), [0x2d, 0x00, 0x17, 0x04, 0x48]);
+    }
+
+    #[test]
+    fn no_response() {
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.interval(20_000), None);
+        assert_eq!(decoder.interval(100), Some(Err(ReadError::Timing(InputState::Standby, 100))));
+    }
+
+    #[test]
+    fn timing_error_mid_packet() {
+        let mut decoder = Decoder::new();
+        let mut results = decode(&mut decoder, intervals(PACKET).take(12));
+        results.extend(decode(&mut decoder, [300, 77, 120].into_iter()));
+        match results.as_slice() {
+            [Err(ReadError::Timing(InputState::Data(packet), 300))] => assert_eq!(packet.bits(), 10),
+            results => panic!("{:?}", results),
+        }
+    }
+
+    #[test]
+    fn recovers_after_error() {
+        let mut decoder = Decoder::new();
+        decode(&mut decoder, [20_000, 160, 77, 300, 120, 77].into_iter());
+        let results = decode(&mut decoder, intervals(PACKET));
+        assert_eq!(results, [Ok(Packet(0, {
+            let mut bytes = PACKET;
+            bytes.reverse();
+            bytes
+        }))]);
+    }
+
+    #[test]
+    fn reset() {
+        let mut decoder = Decoder::new();
+        decode(&mut decoder, intervals(PACKET).take(20));
+        decoder.reset();
+        // without the initiate interval, the response comes straight after the reset
+        let results = decode(&mut decoder, intervals(PACKET).skip(1));
+        assert_eq!(results[0].unwrap().data(), PACKET);
+    }
+
+    // Timestamps as the edges would be seen from a free running counter
+    fn ticks(start: u32, intervals: impl Iterator<Item = u32>) -> impl Iterator<Item = u32> {
+        intervals.scan(start, |ticks, interval| {
+            *ticks = ticks.wrapping_add(interval);
+            Some(*ticks)
+        })
+    }
+
+    #[test]
+    fn falling_edges() {
+        let mut edges = Edges::new();
+        let results: Vec<_> = ticks(5_000_000, intervals(PACKET))
+            .filter_map(|ticks| edges.falling_edge(ticks))
+            .collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].unwrap().data(), PACKET);
+    }
+
+    #[test]
+    fn counter_wraps_mid_packet() {
+        let mut edges = Edges::new();
+        let results: Vec<_> = ticks(u32::MAX - 20_000 - 160 - 1000, intervals(PACKET))
+            .filter_map(|ticks| edges.falling_edge(ticks))
+            .collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].unwrap().data(), PACKET);
+    }
+
+    #[test]
+    fn wide_counter() {
+        let mut edges = Edges::new();
+        let start = u32::MAX as u64 + 5_000_000;
+        let results: Vec<_> = ticks(0, intervals(PACKET))
+            .filter_map(|ticks| edges.falling_edge(start + ticks as u64))
+            .collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].unwrap().data(), PACKET);
+        // Far longer than a u32 of uS is still the gap between reads
+        assert_eq!(edges.falling_edge(start * 4), None);
+        assert_eq!(edges.falling_edge(start * 4 + 160), None);
+        assert_eq!(edges.decoder.statistics.response.count(), 2);
+    }
+
+    #[test]
+    fn counter_wraps_exactly() {
+        let mut edges = Edges::new();
+        edges.falling_edge(u32::MAX - 20_100);
+        edges.falling_edge(u32::MAX - 100);
+        // u32::MAX to 0 is one tick
+        assert_eq!(edges.falling_edge(59), None);
+        assert_eq!(edges.decoder.statistics.response.max(), 160.0);
+    }
+}
@@ -0,0 +1,29 @@
+//! Discovery for networks mDNS doesn't reach, such as a board on a routed
+//! subnet: a host sends the query to UDP port 1339, usually as a broadcast,
+//! and each board replies to the sender with a `DeviceAnnouncement` protobuf
+//! (see aux.proto) saying who it is and where its services are.
+
+/// The port boards listen for queries on, next to the GRBL and RPC ports
+pub const PORT: u16 = 1339;
+
+/// The query. Anything longer that starts with it is a query too, so later
+/// versions can add to it.
+pub const QUERY: &[u8] = b"ant-aux discover";
+
+pub fn is_query(packet: &[u8]) -> bool {
+    packet.starts_with(QUERY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries() {
+        assert!(is_query(QUERY));
+        assert!(is_query(b"ant-aux discover v2"));
+        assert!(!is_query(b"ant-aux"));
+        assert!(!is_query(b""));
+        assert!(!is_query(b"\x00\x00\x01\x00"));
+    }
+}
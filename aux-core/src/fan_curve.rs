@@ -0,0 +1,241 @@
+//! The fan curve: duty from temperature and humidity by piecewise-linear
+//! interpolation, with hysteresis on falling temperatures, and full duty once
+//! too many sensor reads in a row have failed.
+
+use heapless::Vec;
+
+/// Points on each of a curve's inputs
+pub const MAX_POINTS: usize = 8;
+/// Failed reads before the failsafe, when the curve doesn't say
+pub const DEFAULT_FAILSAFE_ERRORS: u32 = 5;
+pub const FAILSAFE_DUTY: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Point {
+    pub input: f32,
+    pub duty: f32,
+}
+
+/// A reading from a temperature sensor
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Climate {
+    pub temperature_celsius: f32,
+    pub humidity_percent: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Curve {
+    pub temperature_celsius: Vec<Point, MAX_POINTS>,
+    pub humidity_percent: Vec<Point, MAX_POINTS>,
+    /// The duty only falls once the temperature is this far below the point
+    /// it was raised at
+    pub hysteresis_celsius: f32,
+    /// 0 for DEFAULT_FAILSAFE_ERRORS
+    pub failsafe_errors: u32,
+}
+
+impl Curve {
+    /// Points must be finite, in increasing order of input, with duties from
+    /// 0 to 1, and the hysteresis finite and not negative.
+    pub fn valid(&self) -> bool {
+        valid(&self.temperature_celsius)
+            && valid(&self.humidity_percent)
+            && self.hysteresis_celsius.is_finite()
+            && self.hysteresis_celsius >= 0.0
+    }
+}
+
+fn valid(points: &[Point]) -> bool {
+    points.iter().all(|point| point.input.is_finite() && (0.0..=1.0).contains(&point.duty))
+        && points.windows(2).all(|pair| pair[0].input < pair[1].input)
+}
+
+/// Piecewise-linear interpolation, held constant beyond the end points. An
+/// empty curve contributes nothing.
+pub fn interpolate(points: &[Point], input: f32) -> f32 {
+    let first = match points.first() {
+        Some(first) => first,
+        None => return 0.0,
+    };
+
+    if input <= first.input {
+        return first.duty;
+    }
+
+    for pair in points.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if input <= b.input {
+            return a.duty + (b.duty - a.duty) * (input - a.input) / (b.input - a.input);
+        }
+    }
+
+    points[points.len() - 1].duty
+}
+
+/// Follows a curve as readings arrive, counting the failed ones
+#[derive(Clone, Debug)]
+pub struct Control {
+    curve: Curve,
+    failures: u32,
+}
+
+impl Control {
+    /// None when the curve isn't valid
+    pub fn new(curve: Curve) -> Option<Self> {
+        curve.valid().then_some(Control { curve, failures: 0 })
+    }
+
+    pub fn curve(&self) -> &Curve {
+        &self.curve
+    }
+
+    pub fn failsafe(&self) -> bool {
+        let limit = match self.curve.failsafe_errors {
+            0 => DEFAULT_FAILSAFE_ERRORS,
+            limit => limit,
+        };
+        self.failures >= limit
+    }
+
+    /// The duty to run at after a reading, or a failed read, given the duty
+    /// the fan is running at now
+    pub fn climate(&mut self, climate: Option<Climate>, current: f32) -> f32 {
+        let climate = match climate {
+            Some(climate) => climate,
+            None => {
+                self.failures = self.failures.saturating_add(1);
+                return if self.failsafe() { FAILSAFE_DUTY } else { current };
+            },
+        };
+        self.failures = 0;
+
+        let curve = &self.curve;
+        let temperature = climate.temperature_celsius;
+        // Falling temperatures are looked up hysteresis warmer, so the fan
+        // doesn't hunt around a point on the curve.
+        let rising = interpolate(&curve.temperature_celsius, temperature);
+        let falling = interpolate(&curve.temperature_celsius, temperature + curve.hysteresis_celsius);
+        let duty = if rising > current {
+            rising
+        } else if falling < current {
+            falling
+        } else {
+            current
+        };
+        duty.max(interpolate(&curve.humidity_percent, climate.humidity_percent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(points: &[(f32, f32)]) -> Vec<Point, MAX_POINTS> {
+        points.iter().map(|&(input, duty)| Point { input, duty }).collect()
+    }
+
+    fn curve() -> Curve {
+        Curve {
+            temperature_celsius: points(&[(20.0, 0.25), (30.0, 0.75), (40.0, 1.0)]),
+            humidity_percent: points(&[(60.0, 0.0), (80.0, 0.5)]),
+            hysteresis_celsius: 2.5,
+            failsafe_errors: 0,
+        }
+    }
+
+    fn climate(temperature_celsius: f32, humidity_percent: f32) -> Option<Climate> {
+        Some(Climate { temperature_celsius, humidity_percent })
+    }
+
+    #[test]
+    fn interpolation() {
+        let curve = curve();
+        let temperature = &curve.temperature_celsius;
+        assert_eq!(interpolate(temperature, 25.0), 0.5);
+        assert_eq!(interpolate(temperature, 30.0), 0.75);
+        assert_eq!(interpolate(temperature, 35.0), 0.875);
+        assert_eq!(interpolate(&[], 35.0), 0.0);
+    }
+
+    #[test]
+    fn end_points() {
+        let curve = curve();
+        let temperature = &curve.temperature_celsius;
+        assert_eq!(interpolate(temperature, 20.0), 0.25);
+        assert_eq!(interpolate(temperature, -10.0), 0.25);
+        assert_eq!(interpolate(temperature, 40.0), 1.0);
+        assert_eq!(interpolate(temperature, 90.0), 1.0);
+        assert_eq!(interpolate(&points(&[(30.0, 0.5)]), 10.0), 0.5);
+        assert_eq!(interpolate(&points(&[(30.0, 0.5)]), 50.0), 0.5);
+    }
+
+    #[test]
+    fn validation() {
+        assert!(curve().valid());
+        assert!(Curve::default().valid());
+        let invalid = [
+            points(&[(30.0, 0.5), (20.0, 0.6)]),
+            points(&[(20.0, 0.5), (20.0, 0.6)]),
+            points(&[(20.0, 5.0)]),
+            points(&[(20.0, -1.0)]),
+            points(&[(20.0, f32::NAN)]),
+            points(&[(f32::NAN, 0.5)]),
+            points(&[(20.0, 0.5), (f32::INFINITY, 1.0)]),
+        ];
+        for points in invalid {
+            assert!(!Curve { temperature_celsius: points.clone(), ..curve() }.valid());
+            assert!(!Curve { humidity_percent: points, ..curve() }.valid());
+        }
+        assert!(!Curve { hysteresis_celsius: f32::NAN, ..curve() }.valid());
+        assert!(!Curve { hysteresis_celsius: -1.0, ..curve() }.valid());
+        assert!(Control::new(Curve { hysteresis_celsius: -1.0, ..curve() }).is_none());
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut control = Control::new(curve()).unwrap();
+        let duty = control.climate(climate(30.0, 0.0), 0.0);
+        assert_eq!(duty, 0.75);
+        // Falling less than the hysteresis holds the duty
+        assert_eq!(control.climate(climate(28.5, 0.0), duty), 0.75);
+        // Further, it falls to the duty hysteresis warmer
+        assert_eq!(control.climate(climate(25.0, 0.0), duty), 0.625);
+        // Rising follows the curve straight away
+        assert_eq!(control.climate(climate(35.0, 0.0), 0.625), 0.875);
+    }
+
+    #[test]
+    fn humidity() {
+        let mut control = Control::new(curve()).unwrap();
+        assert_eq!(control.climate(climate(20.0, 80.0), 0.0), 0.5);
+        assert_eq!(control.climate(climate(35.0, 80.0), 0.5), 0.875);
+    }
+
+    #[test]
+    fn failsafe() {
+        let mut control = Control::new(Curve { failsafe_errors: 3, ..curve() }).unwrap();
+        assert_eq!(control.climate(climate(20.0, 0.0), 0.0), 0.25);
+        assert_eq!(control.climate(None, 0.25), 0.25);
+        assert_eq!(control.climate(None, 0.25), 0.25);
+        assert!(!control.failsafe());
+        assert_eq!(control.climate(None, 0.25), FAILSAFE_DUTY);
+        assert!(control.failsafe());
+        assert_eq!(control.climate(None, FAILSAFE_DUTY), FAILSAFE_DUTY);
+        // A good reading leaves the failsafe, falling as any duty does
+        assert_eq!(control.climate(climate(20.0, 0.0), FAILSAFE_DUTY), 0.375);
+        assert!(!control.failsafe());
+    }
+
+    #[test]
+    fn default_failsafe() {
+        let mut control = Control::new(curve()).unwrap();
+        for _ in 1..DEFAULT_FAILSAFE_ERRORS {
+            control.climate(None, 0.25);
+        }
+        assert!(!control.failsafe());
+        control.climate(None, 0.25);
+        assert!(control.failsafe());
+    }
+}
@@ -0,0 +1,172 @@
+//! Smoothing of the interval between edges of a periodic signal, such as a fan
+//! tachometer, generic over the clock's instant and duration types.
+
+use core::ops::{ Add, Div, Mul, Sub };
+
+pub trait Proportion<P> : Add<P, Output = P> + Copy + Clone {}
+pub trait Value<V, P>:
+    Add<V, Output=V> +
+    Div<P, Output = V> +
+    Mul<P, Output = V> +
+    Ord {}
+
+impl <P: Add<P, Output = P> + Copy> Proportion<P> for P {}
+impl <V, P> Value<V, P> for V
+where V: Add<V, Output = V> + Div<P, Output = V> + Mul<P, Output = V> + Ord {}
+
+/// Weights the previous interval against the latest
+#[derive(Clone, Copy)]
+pub struct Ratio<R: Proportion<R>> (pub R, pub R);
+
+impl <R: Proportion<R>> Ratio<R> {
+    pub fn apply<V: Value<V, R>>(self, a: V, b: V) -> V {
+        let (ar, br) = (self.0, self.1);
+        (a*ar + b*br)/(ar + br)
+    }
+}
+
+/// The exclusive range of plausible intervals
+#[derive(Clone, Copy)]
+pub struct Bounds<V> (pub V, pub V);
+
+impl <V: Ord> Bounds<V> {
+    pub fn apply(self, x: V) -> Option<V> {
+        if x > self.0 && x < self.1 {
+            Some(x)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Interval<INSTANT, DURATION, R: Proportion<R>> {
+    filter: Ratio<R>,
+    bounds: Bounds<DURATION>,
+    previous: Option<INSTANT>,
+    interval: Option<DURATION>,
+}
+
+impl <INSTANT, DURATION, R> Interval<INSTANT, DURATION, R>
+where
+    INSTANT: Sub<INSTANT, Output = DURATION> + Copy,
+    DURATION: Value<DURATION, R> + Copy,
+    R: Proportion<R> {
+    pub fn new(filter: Ratio<R>, bounds: Bounds<DURATION>) -> Self {
+        Interval { filter, bounds, previous: None, interval: None }
+    }
+
+    /// Returns the smoothed interval, or None when the edges are too close
+    /// together or too far apart to be the signal
+    pub fn edge(&mut self, now: INSTANT) -> Option<DURATION> {
+        self.interval = match (self.previous, self.interval) {
+            (Some(instant), Some(duration)) => {
+                let next: DURATION = now - instant;
+                self.bounds.apply(self.filter.apply(duration, next))
+            },
+            (None, Some(duration)) => self.bounds.apply(duration),
+            (Some(instant), None) => self.bounds.apply(now - instant),
+            (None,None) => None
+        };
+        self.previous = Some(now);
+        self.interval
+    }
+}
+
+/// The last interval read, until none has come for longer than the timeout.
+/// Reading a signal takes its value, so the one reader keeps this for
+/// whoever asks next.
+pub struct Held<INSTANT, DURATION> {
+    timeout: DURATION,
+    last: Option<(INSTANT, DURATION)>,
+}
+
+impl <INSTANT, DURATION> Held<INSTANT, DURATION>
+where
+    INSTANT: Sub<INSTANT, Output = DURATION> + Copy,
+    DURATION: Ord + Copy {
+    pub fn new(timeout: DURATION) -> Self {
+        Held { timeout, last: None }
+    }
+
+    pub fn update(&mut self, now: INSTANT, interval: Option<DURATION>) {
+        if let Some(interval) = interval {
+            self.last = Some((now, interval));
+        }
+    }
+
+    pub fn get(&self, now: INSTANT) -> Option<DURATION> {
+        self.last
+            .filter(|(at, _)| now - *at < self.timeout)
+            .map(|(_, interval)| interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval() -> Interval<u32, u32, u32> {
+        Interval::new(Ratio(9, 1), Bounds(10, 1_000_000))
+    }
+
+    #[test]
+    fn ratio() {
+        assert_eq!(Ratio(9, 1).apply(1000u32, 2000), 1100);
+        assert_eq!(Ratio(1, 1).apply(1000u32, 2000), 1500);
+    }
+
+    #[test]
+    fn bounds() {
+        let bounds = Bounds(10, 100);
+        assert_eq!(bounds.apply(50), Some(50));
+        assert_eq!(bounds.apply(10), None);
+        assert_eq!(bounds.apply(100), None);
+        assert_eq!(bounds.apply(5), None);
+    }
+
+    #[test]
+    fn first_edge() {
+        assert_eq!(interval().edge(1000), None);
+    }
+
+    #[test]
+    fn smoothed() {
+        let mut interval = interval();
+        interval.edge(0);
+        assert_eq!(interval.edge(10_000), Some(10_000));
+        // a single late edge only moves the interval a tenth of the way
+        assert_eq!(interval.edge(30_000), Some(11_000));
+        assert_eq!(interval.edge(41_000), Some(11_000));
+    }
+
+    #[test]
+    fn out_of_bounds_restarts() {
+        let mut interval = interval();
+        interval.edge(0);
+        interval.edge(10_000);
+        // the fan stopped for a while
+        assert_eq!(interval.edge(20_000_000), None);
+        assert_eq!(interval.edge(20_012_000), Some(12_000));
+    }
+
+    #[test]
+    fn noise() {
+        let mut interval = interval();
+        interval.edge(0);
+        assert_eq!(interval.edge(5), None);
+    }
+
+    #[test]
+    fn held() {
+        let mut held: Held<u32, u32> = Held::new(1_000_000);
+        assert_eq!(held.get(0), None);
+        held.update(10_000, Some(40_000));
+        // Read again before the next interval arrives
+        held.update(60_000, None);
+        assert_eq!(held.get(60_000), Some(40_000));
+        held.update(100_000, Some(42_000));
+        assert_eq!(held.get(1_099_999), Some(42_000));
+        // The pulses stopped
+        assert_eq!(held.get(1_100_000), None);
+    }
+}
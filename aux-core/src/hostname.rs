@@ -0,0 +1,59 @@
+//! The device's host name, sent to DHCP servers and answered for by mDNS.
+//! Each board gets its own by default, so two on the same network don't
+//! collide: the prefix and the end of the MAC address, which is hashed from
+//! the chip's unique ID.
+
+use core::fmt::Write;
+use heapless::String;
+
+pub const PREFIX: &str = "ant-auxiliary";
+/// The longest name that can be set, which is the longest text setting
+pub const MAX_LEN: usize = 32;
+
+pub type HostName = String<MAX_LEN>;
+
+/// The default name for a MAC address, like `ant-auxiliary-4b7c9a`
+pub fn default(mac_address: [u8; 6]) -> HostName {
+    let mut name = HostName::new();
+    write!(name, "{}-{:02x}{:02x}{:02x}", PREFIX, mac_address[3], mac_address[4], mac_address[5]).unwrap();
+    name
+}
+
+/// Whether a name can be a host name: a single DNS label (RFC 1123) of
+/// letters, digits and hyphens, that doesn't start or end with a hyphen.
+/// There's no domain: DHCP servers and mDNS add their own.
+pub fn valid(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_LEN
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        let name = default([0x02, 0x11, 0x22, 0x4b, 0x7c, 0x9a]);
+        assert_eq!(name.as_str(), "ant-auxiliary-4b7c9a");
+        assert!(valid(&name));
+        assert_ne!(name, default([0x02, 0x11, 0x22, 0x4b, 0x7c, 0x9b]));
+    }
+
+    #[test]
+    fn validation() {
+        assert!(valid("bench"));
+        assert!(valid("ant-auxiliary-2"));
+        assert!(valid("A1"));
+        assert!(!valid(""));
+        assert!(!valid("-bench"));
+        assert!(!valid("bench-"));
+        assert!(!valid("bench.local"));
+        assert!(!valid("bench one"));
+        assert!(!valid("bänch"));
+        assert!(!valid("a23456789012345678901234567890123"));
+        assert!(valid("a2345678901234567890123456789012"));
+    }
+}
@@ -0,0 +1,209 @@
+//! Choosing the device's IPv4 address: a static address when one is
+//! configured, the address of its own DHCP server when it serves the link, or
+//! otherwise DHCP, falling back to an RFC 3927 link-local address when no
+//! lease arrives in time, so the device can always be reached over the USB
+//! link.
+//!
+//! The link-local address is derived from a seed rather than picked at random,
+//! so the device comes back on the same address. There's no ARP probing for
+//! conflicts: on the USB link the only other host is the one the device is
+//! plugged into, which probes for its own address.
+
+use crate::dhcp_server;
+use smoltcp::{
+    time::{ Duration, Instant },
+    wire::{ Ipv4Address, Ipv4Cidr },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Static {
+    pub address: Ipv4Cidr,
+    pub gateway: Option<Ipv4Address>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    Static(Static),
+    Dhcp,
+    /// Serve DHCP on the link, taking this address (see dhcp_server)
+    Server(Ipv4Cidr),
+}
+
+impl Mode {
+    /// The mode the stored settings select, or None for the firmware's default.
+    /// Serving DHCP uses the stored address, or dhcp_server::ADDRESS. Otherwise
+    /// an unspecified address (0.0.0.0) selects the DHCP client, so a stored
+    /// setting can override a compiled-in static address.
+    pub fn from_settings(address: Option<Ipv4Cidr>, gateway: Option<Ipv4Address>, serve: Option<bool>) -> Option<Self> {
+        match (serve, address) {
+            (Some(true), Some(address)) if !address.address().is_unspecified() => Some(Mode::Server(address)),
+            (Some(true), _) => Some(Mode::Server(dhcp_server::ADDRESS)),
+            (_, Some(address)) if address.address().is_unspecified() => Some(Mode::Dhcp),
+            (_, Some(address)) => Some(Mode::Static(Static { address, gateway })),
+            (Some(false), None) => Some(Mode::Dhcp),
+            (None, None) => None,
+        }
+    }
+}
+
+// 169.254.1.0 to 169.254.254.255: RFC 3927 reserves the first and last 256
+const FIRST: u32 = 0xa9fe_0100;
+const COUNT: u32 = 254 * 256;
+pub const LINK_LOCAL_PREFIX: u8 = 16;
+
+/// The link-local address for a seed, such as part of the MAC address
+pub fn link_local(seed: u32) -> Ipv4Cidr {
+    Ipv4Cidr::new(Ipv4Address::from(FIRST + seed % COUNT), LINK_LOCAL_PREFIX)
+}
+
+/// Follows DHCP, and says when to fall back to the link-local address
+pub struct Fallback {
+    seed: u32,
+    timeout: Duration,
+    leased: bool,
+    // When the wait for a lease started
+    since: Option<Instant>,
+    link_local: Option<Ipv4Cidr>,
+}
+
+impl Fallback {
+    pub fn new(seed: u32, timeout: Duration) -> Self {
+        Fallback { seed, timeout, leased: false, since: None, link_local: None }
+    }
+
+    /// DHCP acquired a lease, which replaces the link-local address
+    pub fn leased(&mut self) {
+        self.leased = true;
+        self.since = None;
+        self.link_local = None;
+    }
+
+    /// DHCP lost its lease, so the wait starts again
+    pub fn lost(&mut self) {
+        self.leased = false;
+        self.since = None;
+        self.link_local = None;
+    }
+
+    /// The link went down, so the wait starts again when it's back up. An
+    /// address already fallen back to is kept, as clients may be using it.
+    pub fn disconnected(&mut self) {
+        self.since = None;
+    }
+
+    /// The address in use, if the device has fallen back
+    pub fn link_local(&self) -> Option<Ipv4Cidr> {
+        self.link_local
+    }
+
+    /// Call while the link is up. The wait starts at the first call, and
+    /// this returns the link-local address once, when it's over.
+    pub fn poll(&mut self, now: Instant) -> Option<Ipv4Cidr> {
+        if self.leased || self.link_local.is_some() {
+            return None;
+        }
+        let since = *self.since.get_or_insert(now);
+        if now - since >= self.timeout {
+            self.link_local = Some(link_local(self.seed));
+            self.link_local
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn at(secs: u64) -> Instant {
+        Instant::from_secs(secs as i64)
+    }
+
+    #[test]
+    fn link_local_range() {
+        let first = Ipv4Address::new(169, 254, 1, 0);
+        let last = Ipv4Address::new(169, 254, 254, 255);
+        for seed in [0, 1, COUNT - 1, COUNT, u32::MAX, 0x1234_5678] {
+            let cidr = link_local(seed);
+            assert!(cidr.address() >= first && cidr.address() <= last, "{}", cidr);
+            assert_eq!(cidr.prefix_len(), 16);
+        }
+        assert_eq!(link_local(0).address(), first);
+        assert_eq!(link_local(COUNT - 1).address(), last);
+        assert_eq!(link_local(7), link_local(7));
+    }
+
+    #[test]
+    fn mode() {
+        let address = Ipv4Cidr::new(Ipv4Address::new(192, 168, 7, 2), 24);
+        let gateway = Some(Ipv4Address::new(192, 168, 7, 1));
+        let unspecified = Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0);
+        assert_eq!(Mode::from_settings(None, gateway, None), None);
+        assert_eq!(Mode::from_settings(Some(address), gateway, None), Some(Mode::Static(Static { address, gateway })));
+        assert_eq!(Mode::from_settings(Some(address), gateway, Some(false)), Some(Mode::Static(Static { address, gateway })));
+        assert_eq!(Mode::from_settings(Some(unspecified), None, None), Some(Mode::Dhcp));
+        assert_eq!(Mode::from_settings(None, None, Some(false)), Some(Mode::Dhcp));
+        assert_eq!(Mode::from_settings(Some(address), None, Some(true)), Some(Mode::Server(address)));
+        assert_eq!(Mode::from_settings(None, None, Some(true)), Some(Mode::Server(dhcp_server::ADDRESS)));
+        assert_eq!(Mode::from_settings(Some(unspecified), None, Some(true)), Some(Mode::Server(dhcp_server::ADDRESS)));
+    }
+
+    #[test]
+    fn falls_back_after_timeout() {
+        let mut fallback = Fallback::new(42, TIMEOUT);
+        assert_eq!(fallback.poll(at(5)), None);
+        assert_eq!(fallback.poll(at(14)), None);
+        assert_eq!(fallback.poll(at(15)), Some(link_local(42)));
+        assert_eq!(fallback.link_local(), Some(link_local(42)));
+        // only once
+        assert_eq!(fallback.poll(at(16)), None);
+    }
+
+    #[test]
+    fn lease_in_time() {
+        let mut fallback = Fallback::new(42, TIMEOUT);
+        assert_eq!(fallback.poll(at(0)), None);
+        fallback.leased();
+        assert_eq!(fallback.poll(at(60)), None);
+        assert_eq!(fallback.link_local(), None);
+    }
+
+    #[test]
+    fn lease_replaces_link_local() {
+        let mut fallback = Fallback::new(42, TIMEOUT);
+        fallback.poll(at(0));
+        assert!(fallback.poll(at(10)).is_some());
+        fallback.leased();
+        assert_eq!(fallback.link_local(), None);
+    }
+
+    #[test]
+    fn disconnected() {
+        let mut fallback = Fallback::new(42, TIMEOUT);
+        assert_eq!(fallback.poll(at(0)), None);
+        fallback.disconnected();
+        // the wait starts again when the link is back
+        assert_eq!(fallback.poll(at(100)), None);
+        assert_eq!(fallback.poll(at(109)), None);
+        assert_eq!(fallback.poll(at(110)), Some(link_local(42)));
+        fallback.disconnected();
+        assert_eq!(fallback.link_local(), Some(link_local(42)));
+        assert_eq!(fallback.poll(at(200)), None);
+    }
+
+    #[test]
+    fn lost_lease() {
+        let mut fallback = Fallback::new(42, TIMEOUT);
+        fallback.leased();
+        fallback.lost();
+        // the wait starts again from the next poll
+        assert_eq!(fallback.poll(at(100)), None);
+        assert_eq!(fallback.poll(at(109)), None);
+        assert_eq!(fallback.poll(at(110)), Some(link_local(42)));
+    }
+}
@@ -0,0 +1,377 @@
+//! IPv6 addressing: a link-local address from the MAC address, which the host
+//! can reach over the USB link with no DHCP or routing set up at all, and
+//! stateless autoconfiguration (SLAAC, RFC 4862) of a global address and
+//! default route from router advertisements, when the host or the network
+//! behind it has a router.
+//!
+//! smoltcp answers neighbor solicitations but ignores router advertisements,
+//! so they're read from a raw ICMPv6 socket. As with the IPv4 link-local
+//! address, there's no duplicate address detection: the interface ID comes
+//! from the MAC address, which is unique to the board. Only one prefix is
+//! taken from each advertisement, and lifetimes are applied as advertised,
+//! without RFC 4862's two hour minimum.
+
+use smoltcp::{
+    phy::ChecksumCapabilities,
+    time::{ Duration, Instant },
+    wire::{
+        EthernetAddress, Icmpv6Packet, Icmpv6Repr, IpProtocol, Ipv6Address, Ipv6Cidr,
+        Ipv6Packet, Ipv6Repr, NdiscPrefixInfoFlags, NdiscRepr, IPV6_LINK_LOCAL_ALL_ROUTERS,
+    },
+};
+
+/// The prefix length SLAAC needs, leaving 64 bits for the interface ID
+pub const PREFIX_LEN: u8 = 64;
+// Neighbor discovery packets have to arrive with the hop limit they're sent
+// with, so they can't have come from off the link
+const HOP_LIMIT: u8 = 255;
+// RFC 4861's MAX_RTR_SOLICITATIONS and RTR_SOLICITATION_INTERVAL
+const SOLICITATIONS: u8 = 3;
+const SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+// An advertised lifetime of all ones never expires
+const INFINITE: Duration = Duration::from_secs(0xffff_ffff);
+
+/// The modified EUI-64 interface ID (RFC 4291) for a MAC address
+fn interface_id(mac_address: EthernetAddress) -> [u8; 8] {
+    let mac = mac_address.0;
+    [mac[0] ^ 0x02, mac[1], mac[2], 0xff, 0xfe, mac[3], mac[4], mac[5]]
+}
+
+/// The address in a /64 prefix for a MAC address
+pub fn address(prefix: Ipv6Address, mac_address: EthernetAddress) -> Ipv6Address {
+    let mut octets = prefix.octets();
+    octets[8..].copy_from_slice(&interface_id(mac_address));
+    Ipv6Address::from(octets)
+}
+
+/// The link-local address for a MAC address, fe80::/64
+pub fn link_local(mac_address: EthernetAddress) -> Ipv6Cidr {
+    let prefix = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0);
+    Ipv6Cidr::new(address(prefix, mac_address), PREFIX_LEN)
+}
+
+// When a lifetime from now ends, or None if it never does
+fn expiry(now: Instant, lifetime: Duration) -> Option<Instant> {
+    (lifetime != INFINITE).then(|| now + lifetime)
+}
+
+fn expired(expiry: Option<Instant>, now: Instant) -> bool {
+    expiry.is_some_and(|expiry| now >= expiry)
+}
+
+/// Follows router advertisements, and solicits them
+pub struct Slaac {
+    mac_address: EthernetAddress,
+    // Each with when it expires
+    address: Option<(Ipv6Cidr, Option<Instant>)>,
+    router: Option<(Ipv6Address, Option<Instant>)>,
+    advertised: bool,
+    solicitations: u8,
+    next_solicitation: Option<Instant>,
+}
+
+impl Slaac {
+    pub fn new(mac_address: EthernetAddress) -> Self {
+        Slaac {
+            mac_address,
+            address: None,
+            router: None,
+            advertised: false,
+            solicitations: 0,
+            next_solicitation: None,
+        }
+    }
+
+    /// The address from the advertised prefix
+    pub fn address(&self) -> Option<Ipv6Cidr> {
+        self.address.map(|(address, _)| address)
+    }
+
+    /// The router advertising itself as the default route
+    pub fn router(&self) -> Option<Ipv6Address> {
+        self.router.map(|(router, _)| router)
+    }
+
+    /// Takes an IPv6 packet from a raw ICMPv6 socket, and returns whether the
+    /// address or router changed. Anything but a valid router advertisement
+    /// is ignored.
+    pub fn process(&mut self, now: Instant, packet: &[u8]) -> bool {
+        let Some((source, advert)) = Self::router_advert(packet) else { return false };
+        let NdiscRepr::RouterAdvert { router_lifetime, prefix_info, .. } = advert else { return false };
+        self.advertised = true;
+        let before = (self.address(), self.router());
+
+        if router_lifetime == Duration::ZERO {
+            // This router is no longer a default router
+            if self.router() == Some(source) {
+                self.router = None;
+            }
+        } else {
+            self.router = Some((source, expiry(now, router_lifetime)));
+        }
+
+        let prefix = prefix_info.filter(|prefix| {
+            prefix.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+                && prefix.prefix_len == PREFIX_LEN
+                && !prefix.prefix.is_unicast_link_local()
+                && prefix.preferred_lifetime <= prefix.valid_lifetime
+        });
+        if let Some(prefix) = prefix {
+            let cidr = Ipv6Cidr::new(address(prefix.prefix, self.mac_address), PREFIX_LEN);
+            if prefix.valid_lifetime == Duration::ZERO {
+                if self.address() == Some(cidr) {
+                    self.address = None;
+                }
+            } else {
+                self.address = Some((cidr, expiry(now, prefix.valid_lifetime)));
+            }
+        }
+        (self.address(), self.router()) != before
+    }
+
+    // The source and contents of a router advertisement that passes RFC 4861's
+    // validity checks
+    fn router_advert(packet: &[u8]) -> Option<(Ipv6Address, NdiscRepr<'_>)> {
+        let packet = Ipv6Packet::new_checked(packet).ok()?;
+        let ip = Ipv6Repr::parse(&packet).ok()?;
+        if ip.next_header != IpProtocol::Icmpv6 || ip.hop_limit != HOP_LIMIT || !ip.src_addr.is_unicast_link_local() {
+            return None;
+        }
+        let icmp = Icmpv6Packet::new_checked(packet.payload()).ok()?;
+        match Icmpv6Repr::parse(&ip.src_addr, &ip.dst_addr, &icmp, &ChecksumCapabilities::default()).ok()? {
+            Icmpv6Repr::Ndisc(advert @ NdiscRepr::RouterAdvert { .. }) => Some((ip.src_addr, advert)),
+            _ => None,
+        }
+    }
+
+    /// Forgets the address and router when their lifetimes end, and returns
+    /// whether either did
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        if self.address.is_some_and(|(_, expiry)| expired(expiry, now)) {
+            self.address = None;
+            changed = true;
+        }
+        if self.router.is_some_and(|(_, expiry)| expired(expiry, now)) {
+            self.router = None;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Writes a router solicitation from source, when one is due: up to three,
+    /// four seconds apart, until a router advertises. Returns its length.
+    pub fn solicit(&mut self, now: Instant, source: Ipv6Address, buffer: &mut [u8]) -> Option<usize> {
+        if self.advertised || self.solicitations >= SOLICITATIONS
+            || self.next_solicitation.is_some_and(|next| now < next) {
+            return None;
+        }
+        let icmp = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit {
+            // The source link-layer address isn't allowed from an unspecified address
+            lladdr: (!source.is_unspecified()).then(|| self.mac_address.into()),
+        });
+        let ip = Ipv6Repr {
+            src_addr: source,
+            dst_addr: IPV6_LINK_LOCAL_ALL_ROUTERS,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp.buffer_len(),
+            hop_limit: HOP_LIMIT,
+        };
+        let len = ip.buffer_len() + icmp.buffer_len();
+        let mut packet = Ipv6Packet::new_unchecked(buffer.get_mut(..len)?);
+        ip.emit(&mut packet);
+        icmp.emit(&source, &IPV6_LINK_LOCAL_ALL_ROUTERS,
+            &mut Icmpv6Packet::new_unchecked(packet.payload_mut()), &ChecksumCapabilities::default());
+
+        self.solicitations += 1;
+        self.next_solicitation = Some(now + SOLICITATION_INTERVAL);
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::{ vec, vec::Vec };
+    use smoltcp::wire::{ NdiscPrefixInformation, NdiscRouterFlags, IPV6_LINK_LOCAL_ALL_NODES };
+
+    const MAC: EthernetAddress = EthernetAddress([0x02, 0x11, 0x22, 0x33, 0x44, 0x55]);
+    const ROUTER: Ipv6Address = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const PREFIX: Ipv6Address = Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0);
+
+    fn at(secs: u64) -> Instant {
+        Instant::from_secs(secs as i64)
+    }
+
+    fn prefix(valid_secs: u64, preferred_secs: u64) -> NdiscPrefixInformation {
+        NdiscPrefixInformation {
+            prefix_len: PREFIX_LEN,
+            flags: NdiscPrefixInfoFlags::ON_LINK | NdiscPrefixInfoFlags::ADDRCONF,
+            valid_lifetime: Duration::from_secs(valid_secs),
+            preferred_lifetime: Duration::from_secs(preferred_secs),
+            prefix: PREFIX,
+        }
+    }
+
+    fn advert(router_secs: u64, prefix_info: Option<NdiscPrefixInformation>) -> Vec<u8> {
+        packet(ROUTER, HOP_LIMIT, NdiscRepr::RouterAdvert {
+            hop_limit: 64,
+            flags: NdiscRouterFlags::empty(),
+            router_lifetime: Duration::from_secs(router_secs),
+            reachable_time: Duration::ZERO,
+            retrans_time: Duration::ZERO,
+            lladdr: None,
+            mtu: None,
+            prefix_info,
+        })
+    }
+
+    fn packet(source: Ipv6Address, hop_limit: u8, ndisc: NdiscRepr) -> Vec<u8> {
+        let icmp = Icmpv6Repr::Ndisc(ndisc);
+        let ip = Ipv6Repr {
+            src_addr: source,
+            dst_addr: IPV6_LINK_LOCAL_ALL_NODES,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp.buffer_len(),
+            hop_limit,
+        };
+        let mut bytes = vec![0u8; ip.buffer_len() + icmp.buffer_len()];
+        let mut packet = Ipv6Packet::new_unchecked(&mut bytes[..]);
+        ip.emit(&mut packet);
+        icmp.emit(&source, &IPV6_LINK_LOCAL_ALL_NODES,
+            &mut Icmpv6Packet::new_unchecked(packet.payload_mut()), &ChecksumCapabilities::default());
+        bytes
+    }
+
+    // The neighbor discovery message in a packet
+    fn ndisc(packet: &[u8]) -> NdiscRepr<'_> {
+        let packet = Ipv6Packet::new_checked(packet).unwrap();
+        let ip = Ipv6Repr::parse(&packet).unwrap();
+        let icmp = Icmpv6Packet::new_checked(packet.payload()).unwrap();
+        match Icmpv6Repr::parse(&ip.src_addr, &ip.dst_addr, &icmp, &ChecksumCapabilities::default()).unwrap() {
+            Icmpv6Repr::Ndisc(ndisc) => ndisc,
+            _ => unreachable!(),
+        }
+    }
+
+    fn global() -> Ipv6Cidr {
+        Ipv6Cidr::new(Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0x0011, 0x22ff, 0xfe33, 0x4455), PREFIX_LEN)
+    }
+
+    #[test]
+    fn eui64() {
+        let expected = Ipv6Address::new(0xfe80, 0, 0, 0, 0x0011, 0x22ff, 0xfe33, 0x4455);
+        assert_eq!(link_local(MAC), Ipv6Cidr::new(expected, 64));
+        // The universal/local bit is inverted, so a globally unique MAC sets it
+        let universal = EthernetAddress([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_eq!(address(PREFIX, universal), Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0x0211, 0x22ff, 0xfe33, 0x4455));
+    }
+
+    #[test]
+    fn configures_from_advert() {
+        let mut slaac = Slaac::new(MAC);
+        assert!(slaac.process(at(0), &advert(1800, Some(prefix(3600, 1800)))));
+        assert_eq!(slaac.address(), Some(global()));
+        assert_eq!(slaac.router(), Some(ROUTER));
+        // The same again changes nothing
+        assert!(!slaac.process(at(10), &advert(1800, Some(prefix(3600, 1800)))));
+    }
+
+    #[test]
+    fn lifetimes() {
+        let mut slaac = Slaac::new(MAC);
+        slaac.process(at(0), &advert(1800, Some(prefix(3600, 1800))));
+        assert!(!slaac.poll(at(1799)));
+        assert!(slaac.poll(at(1800)));
+        assert_eq!(slaac.router(), None);
+        assert_eq!(slaac.address(), Some(global()));
+        assert!(slaac.poll(at(3600)));
+        assert_eq!(slaac.address(), None);
+
+        // A refreshed lifetime counts from the latest advert
+        slaac.process(at(4000), &advert(0, Some(prefix(100, 100))));
+        slaac.process(at(4050), &advert(0, Some(prefix(100, 100))));
+        assert!(!slaac.poll(at(4100)));
+        assert!(slaac.poll(at(4150)));
+
+        let infinite = prefix(0xffff_ffff, 0xffff_ffff);
+        slaac.process(at(0), &advert(0, Some(infinite)));
+        assert!(!slaac.poll(at(1 << 40)));
+        assert_eq!(slaac.address(), Some(global()));
+    }
+
+    #[test]
+    fn withdrawn() {
+        let mut slaac = Slaac::new(MAC);
+        slaac.process(at(0), &advert(1800, Some(prefix(3600, 1800))));
+        assert!(slaac.process(at(10), &advert(0, Some(prefix(0, 0)))));
+        assert_eq!(slaac.address(), None);
+        assert_eq!(slaac.router(), None);
+    }
+
+    #[test]
+    fn router_without_prefix() {
+        let mut slaac = Slaac::new(MAC);
+        assert!(slaac.process(at(0), &advert(1800, None)));
+        assert_eq!(slaac.router(), Some(ROUTER));
+        assert_eq!(slaac.address(), None);
+    }
+
+    #[test]
+    fn ignored_prefixes() {
+        let mut slaac = Slaac::new(MAC);
+        let on_link_only = NdiscPrefixInformation { flags: NdiscPrefixInfoFlags::ON_LINK, ..prefix(3600, 1800) };
+        let too_long = NdiscPrefixInformation { prefix_len: 80, ..prefix(3600, 1800) };
+        let link_local = NdiscPrefixInformation { prefix: Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), ..prefix(3600, 1800) };
+        for prefix in [on_link_only, too_long, link_local, prefix(1800, 3600)] {
+            slaac.process(at(0), &advert(0, Some(prefix)));
+            assert_eq!(slaac.address(), None);
+        }
+    }
+
+    #[test]
+    fn invalid_adverts() {
+        let mut slaac = Slaac::new(MAC);
+        let valid = advert(1800, Some(prefix(3600, 1800)));
+        // Forwarded from off the link
+        let forwarded = packet(ROUTER, 64, ndisc(&valid));
+        // Not from a link-local address
+        let global_source = packet(Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 1), HOP_LIMIT, ndisc(&valid));
+        let mut corrupt = valid.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        let solicitation = packet(ROUTER, HOP_LIMIT, NdiscRepr::RouterSolicit { lladdr: None });
+        for packet in [forwarded, global_source, corrupt, solicitation, vec![0u8; 10]] {
+            assert!(!slaac.process(at(0), &packet));
+        }
+        assert_eq!(slaac.address(), None);
+        assert_eq!(slaac.router(), None);
+    }
+
+    #[test]
+    fn solicits_until_advertised() {
+        let mut slaac = Slaac::new(MAC);
+        let source = link_local(MAC).address();
+        let mut buffer = [0u8; 128];
+        let len = slaac.solicit(at(0), source, &mut buffer).unwrap();
+
+        let packet = Ipv6Packet::new_checked(&buffer[..len]).unwrap();
+        let ip = Ipv6Repr::parse(&packet).unwrap();
+        assert_eq!(ip.dst_addr, IPV6_LINK_LOCAL_ALL_ROUTERS);
+        assert_eq!(ip.hop_limit, HOP_LIMIT);
+        let icmp = Icmpv6Packet::new_checked(packet.payload()).unwrap();
+        let solicitation = Icmpv6Repr::parse(&source, &ip.dst_addr, &icmp, &ChecksumCapabilities::default()).unwrap();
+        assert_eq!(solicitation, Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit { lladdr: Some(MAC.into()) }));
+
+        assert!(slaac.solicit(at(3), source, &mut buffer).is_none());
+        assert!(slaac.solicit(at(4), source, &mut buffer).is_some());
+        assert!(slaac.solicit(at(8), source, &mut buffer).is_some());
+        // Only three
+        assert!(slaac.solicit(at(12), source, &mut buffer).is_none());
+
+        let mut slaac = Slaac::new(MAC);
+        slaac.solicit(at(0), source, &mut buffer);
+        slaac.process(at(1), &advert(1800, None));
+        assert!(slaac.solicit(at(4), source, &mut buffer).is_none());
+    }
+}
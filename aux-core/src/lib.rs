@@ -0,0 +1,33 @@
+#![no_std]
+//! Sensor and timing logic shared by the firmware, kept free of the HAL and
+//! RTIC so it can be tested on the host.
+
+// Logs through defmt when it's enabled, and compiles away on the host
+#[allow(unused_macros)]
+macro_rules! log {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::$level!($($arg)*);
+    };
+}
+
+#[cfg(any(feature = "network", test))]
+pub mod channel;
+#[cfg(any(feature = "network", test))]
+pub mod dhcp_server;
+pub mod dht;
+pub mod discovery;
+pub mod fan_curve;
+pub mod frequency;
+pub mod hostname;
+#[cfg(any(feature = "network", test))]
+pub mod ipv4;
+#[cfg(any(feature = "network", test))]
+pub mod ipv6;
+#[cfg(any(feature = "network", test))]
+pub mod mdns;
+pub mod peripherals;
+pub mod registry;
+#[cfg(any(feature = "network", test))]
+pub mod services;
+pub mod statistics;
@@ -0,0 +1,682 @@
+//! An mDNS responder (RFC 6762) that advertises the device's services with
+//! DNS-SD (RFC 6763), so hosts can find it as `<hostname>.local`, and browse
+//! for its services, without a DNS server that DHCP updates.
+//!
+//! It only answers for its own names. It doesn't probe for conflicts or
+//! suppress known answers, and writes names without compression, which keeps
+//! replies well within a packet for a handful of services.
+
+use smoltcp::wire::{ Ipv4Address, Ipv6Address };
+
+pub const PORT: u16 = 5353;
+pub const GROUP: Ipv4Address = Ipv4Address::new(224, 0, 0, 251);
+pub const GROUP_IPV6: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const CLASS_ANY: u16 = 255;
+// In a record, it replaces what's cached for the name. In a question, it asks
+// for a unicast reply, which it's fine to multicast anyway.
+const CACHE_FLUSH: u16 = 0x8000;
+const RESPONSE: u16 = 0x8000;
+const AUTHORITATIVE: u16 = 0x0400;
+
+// RFC 6762 section 10: records about the host, and the rest
+const HOST_TTL: u32 = 120;
+const OTHER_TTL: u32 = 4500;
+// Section 6.7: a resolver that isn't mDNS aware queried from another port
+const LEGACY_TTL: u32 = 10;
+
+const HEADER: usize = 12;
+const MAX_NAME: usize = 255;
+const MAX_LABEL: usize = 63;
+// The records are a bitmap in a u64: the addresses, then four per service
+pub const MAX_SERVICES: usize = 15;
+
+/// A reply doesn't fit in the buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Overflow;
+
+// What a query asks for: the ID and questions to echo back to a legacy
+// resolver, and the bitmaps of answer and additional records
+type Question<'q> = (u16, (u16, &'q [u8]), u64, u64);
+
+/// The host's addresses, for its A and AAAA records. Without an IPv4 address
+/// it only answers with IPv6 ones.
+#[derive(Clone, Copy, Debug)]
+pub struct Addresses<'a> {
+    pub ipv4: Option<Ipv4Address>,
+    pub ipv6: &'a [Ipv6Address],
+}
+
+/// A service the device offers, like "_grbl._tcp" on port 1337
+#[derive(Clone, Copy, Debug)]
+pub struct Service<'a> {
+    pub kind: &'a str,
+    pub port: u16,
+}
+
+/// A name in wire format, without compression
+struct Name {
+    bytes: [u8; MAX_NAME],
+    len: usize,
+}
+
+impl Name {
+    /// Each part can be several labels, like "_grbl._tcp"
+    fn new(parts: &[&str]) -> Option<Self> {
+        let mut name = Name { bytes: [0; MAX_NAME], len: 0 };
+        for label in parts.iter().flat_map(|part| part.split('.')) {
+            name.push(label.as_bytes())?;
+        }
+        name.finish()
+    }
+
+    fn push(&mut self, label: &[u8]) -> Option<()> {
+        if label.is_empty() || label.len() > MAX_LABEL || self.len + 1 + label.len() >= MAX_NAME {
+            return None;
+        }
+        self.bytes[self.len] = label.len() as u8;
+        self.bytes[self.len + 1..self.len + 1 + label.len()].copy_from_slice(label);
+        self.len += 1 + label.len();
+        Some(())
+    }
+
+    fn finish(mut self) -> Option<Self> {
+        self.bytes[self.len] = 0;
+        self.len += 1;
+        Some(self)
+    }
+
+    /// Reads a name from a message, following compression pointers. Returns
+    /// the name and the offset after it.
+    fn read(message: &[u8], mut offset: usize) -> Option<(Self, usize)> {
+        let mut name = Name { bytes: [0; MAX_NAME], len: 0 };
+        let mut end = None;
+        // Bounds the pointers followed, in case they loop
+        for _ in 0..MAX_NAME {
+            let len = *message.get(offset)? as usize;
+            match len {
+                0 => return Some((name.finish()?, end.unwrap_or(offset + 1))),
+                0xc0..=0xff => {
+                    let pointer = ((len & 0x3f) << 8) | *message.get(offset + 1)? as usize;
+                    end.get_or_insert(offset + 2);
+                    offset = pointer;
+                },
+                1..=MAX_LABEL => {
+                    name.push(message.get(offset + 1..offset + 1 + len)?)?;
+                    offset += 1 + len;
+                },
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    fn matches(&self, other: &Name) -> bool {
+        // Length bytes are below 64, so they're unchanged by ignoring case
+        self.bytes[..self.len].eq_ignore_ascii_case(&other.bytes[..other.len])
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Record {
+    /// <hostname>.local A
+    Address,
+    /// <hostname>.local AAAA, one for each IPv6 address
+    Address6,
+    /// _services._dns-sd._udp.local PTR to a service type
+    Browse(usize),
+    /// <service>.local PTR to the instance
+    Instance(usize),
+    /// <hostname>.<service>.local SRV
+    Location(usize),
+    /// <hostname>.<service>.local TXT
+    Text(usize),
+}
+
+impl Record {
+    fn index(self) -> usize {
+        match self {
+            Record::Address => 0,
+            Record::Address6 => 1,
+            Record::Browse(service) => 2 + 4 * service,
+            Record::Instance(service) => 3 + 4 * service,
+            Record::Location(service) => 4 + 4 * service,
+            Record::Text(service) => 5 + 4 * service,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index.checked_sub(2).map(|index| (index / 4, index % 4)) {
+            None if index == 0 => Record::Address,
+            None => Record::Address6,
+            Some((service, 0)) => Record::Browse(service),
+            Some((service, 1)) => Record::Instance(service),
+            Some((service, 2)) => Record::Location(service),
+            Some((service, _)) => Record::Text(service),
+        }
+    }
+
+    fn kind(self) -> u16 {
+        match self {
+            Record::Address => TYPE_A,
+            Record::Address6 => TYPE_AAAA,
+            Record::Browse(_) | Record::Instance(_) => TYPE_PTR,
+            Record::Location(_) => TYPE_SRV,
+            Record::Text(_) => TYPE_TXT,
+        }
+    }
+
+    // Pointers are shared between devices offering the same service, the
+    // rest are unique to this one
+    fn shared(self) -> bool {
+        matches!(self, Record::Browse(_) | Record::Instance(_))
+    }
+
+    fn ttl(self) -> u32 {
+        match self {
+            Record::Address | Record::Address6 | Record::Location(_) => HOST_TTL,
+            _ => OTHER_TTL,
+        }
+    }
+}
+
+struct Writer<'b> {
+    buffer: &'b mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) -> Option<()> {
+        self.buffer.get_mut(self.len..self.len + bytes.len())?.copy_from_slice(bytes);
+        self.len += bytes.len();
+        Some(())
+    }
+
+    fn u16(&mut self, value: u16) -> Option<()> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn u32(&mut self, value: u32) -> Option<()> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn name(&mut self, name: &Name) -> Option<()> {
+        self.bytes(&name.bytes[..name.len])
+    }
+
+    fn patch(&mut self, offset: usize, value: u16) {
+        self.buffer[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+pub struct Responder<'a> {
+    hostname: &'a str,
+    services: &'a [Service<'a>],
+    txt: &'a [&'a str],
+}
+
+impl <'a> Responder<'a> {
+    /// txt: the strings in each service's TXT record, like "id=..."
+    pub fn new(hostname: &'a str, services: &'a [Service<'a>], txt: &'a [&'a str]) -> Self {
+        Responder { hostname, services: &services[..services.len().min(MAX_SERVICES)], txt }
+    }
+
+    fn count(&self) -> usize {
+        2 + 4 * self.services.len()
+    }
+
+    fn owner(&self, record: Record) -> Option<Name> {
+        match record {
+            Record::Address | Record::Address6 => Name::new(&[self.hostname, "local"]),
+            Record::Browse(_) => Name::new(&["_services._dns-sd._udp.local"]),
+            Record::Instance(service) => Name::new(&[self.services[service].kind, "local"]),
+            Record::Location(service) | Record::Text(service) =>
+                Name::new(&[self.hostname, self.services[service].kind, "local"]),
+        }
+    }
+
+    // The records that help a client use an answer
+    fn additional(&self, record: Record) -> u64 {
+        let bit = |record: Record| 1u64 << record.index();
+        let addresses = bit(Record::Address) | bit(Record::Address6);
+        match record {
+            Record::Instance(service) =>
+                bit(Record::Location(service)) | bit(Record::Text(service)) | addresses,
+            Record::Location(_) => addresses,
+            _ => 0,
+        }
+    }
+
+    // The records it has data for: no A without an IPv4 address, and no
+    // AAAA without an IPv6 one
+    fn present(&self, addresses: &Addresses) -> u64 {
+        let mut present = (1u64 << self.count()) - 1;
+        if addresses.ipv4.is_none() {
+            present &= !(1 << Record::Address.index());
+        }
+        if addresses.ipv6.is_empty() {
+            present &= !(1 << Record::Address6.index());
+        }
+        present
+    }
+
+    /// Answers a query received on the mDNS port, writing the reply into
+    /// buffer. A legacy query came from a port other than 5353: the reply goes
+    /// back to where it came from, not to the group. Returns the reply's
+    /// length, or None when there's nothing to answer.
+    pub fn process(&self, query: &[u8], legacy: bool, addresses: &Addresses, buffer: &mut [u8])
+        -> Result<Option<usize>, Overflow>
+    {
+        let present = self.present(addresses);
+        let Some((id, questions, answers, additional)) = self.question(query, legacy, present) else { return Ok(None) };
+        self.reply(id, questions, answers, additional, legacy, addresses, buffer).map(Some).ok_or(Overflow)
+    }
+
+    fn question<'q>(&self, query: &'q [u8], legacy: bool, present: u64) -> Option<Question<'q>> {
+        let header = query.get(..HEADER)?;
+        let flags = u16::from_be_bytes([header[2], header[3]]);
+        if flags & RESPONSE != 0 {
+            return None;
+        }
+        let questions = u16::from_be_bytes([header[4], header[5]]);
+
+        let mut offset = HEADER;
+        let mut answers = 0u64;
+        for _ in 0..questions {
+            let (name, next) = Name::read(query, offset)?;
+            let fields = query.get(next..next + 4)?;
+            let kind = u16::from_be_bytes([fields[0], fields[1]]);
+            let class = u16::from_be_bytes([fields[2], fields[3]]) & !CACHE_FLUSH;
+            offset = next + 4;
+            if class != CLASS_IN && class != CLASS_ANY {
+                continue;
+            }
+            for index in (0..self.count()).filter(|index| present & (1 << index) != 0) {
+                let record = Record::from_index(index);
+                if (kind == record.kind() || kind == TYPE_ANY) && self.owner(record)?.matches(&name) {
+                    answers |= 1 << index;
+                }
+            }
+        }
+        if answers == 0 {
+            return None;
+        }
+
+        let additional = (0..self.count())
+            .filter(|index| answers & (1 << index) != 0)
+            .fold(0, |additional, index| additional | self.additional(Record::from_index(index)))
+            & present & !answers;
+        // A legacy resolver expects its ID and questions back
+        let (id, questions) = match legacy {
+            true => (u16::from_be_bytes([header[0], header[1]]), (questions, &query[HEADER..offset])),
+            false => (0, (0, &query[..0])),
+        };
+        Some((id, questions, answers, additional))
+    }
+
+    /// An unsolicited reply with all the records, to send when the address
+    /// changes
+    pub fn announce(&self, addresses: &Addresses, buffer: &mut [u8]) -> Result<usize, Overflow> {
+        let all = self.present(addresses);
+        self.reply(0, (0, &[]), all, 0, false, addresses, buffer).ok_or(Overflow)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn reply(&self,
+        id: u16,
+        (question_count, questions): (u16, &[u8]),
+        answers: u64,
+        additional: u64,
+        legacy: bool,
+        addresses: &Addresses,
+        buffer: &mut [u8]) -> Option<usize>
+    {
+        let mut writer = Writer { buffer, len: 0 };
+        writer.u16(id)?;
+        writer.u16(RESPONSE | AUTHORITATIVE)?;
+        writer.u16(question_count)?;
+        // The record counts, filled in once written: there's an AAAA record
+        // for each IPv6 address
+        writer.bytes(&[0; 6])?;
+        writer.bytes(questions)?;
+        for (records, count) in [(answers, 6), (additional, 10)] {
+            let mut written = 0;
+            for index in (0..self.count()).filter(|index| records & (1 << index) != 0) {
+                written += self.records(&mut writer, Record::from_index(index), legacy, addresses)?;
+            }
+            writer.patch(count, written);
+        }
+        Some(writer.len)
+    }
+
+    // Writes a record, or for the addresses, one for each. Returns how many.
+    fn records(&self, writer: &mut Writer, record: Record, legacy: bool, addresses: &Addresses) -> Option<u16> {
+        match record {
+            Record::Address => match addresses.ipv4 {
+                Some(address) => self.record(writer, record, legacy, &address.octets()).map(|_| 1),
+                None => Some(0),
+            },
+            Record::Address6 => {
+                for address in addresses.ipv6 {
+                    self.record(writer, record, legacy, &address.octets())?;
+                }
+                Some(addresses.ipv6.len() as u16)
+            },
+            _ => self.record(writer, record, legacy, &[]).map(|_| 1),
+        }
+    }
+
+    // address: the data of an address record
+    fn record(&self, writer: &mut Writer, record: Record, legacy: bool, address: &[u8]) -> Option<()> {
+        let class = match legacy || record.shared() {
+            true => CLASS_IN,
+            false => CLASS_IN | CACHE_FLUSH,
+        };
+        let ttl = match legacy {
+            true => record.ttl().min(LEGACY_TTL),
+            false => record.ttl(),
+        };
+        writer.name(&self.owner(record)?)?;
+        writer.u16(record.kind())?;
+        writer.u16(class)?;
+        writer.u32(ttl)?;
+        let length = writer.len;
+        writer.u16(0)?;
+        match record {
+            Record::Address | Record::Address6 => writer.bytes(address)?,
+            Record::Browse(service) => writer.name(&Name::new(&[self.services[service].kind, "local"])?)?,
+            Record::Instance(service) => writer.name(&self.owner(Record::Location(service))?)?,
+            Record::Location(service) => {
+                // priority, weight, port, target
+                writer.u16(0)?;
+                writer.u16(0)?;
+                writer.u16(self.services[service].port)?;
+                writer.name(&self.owner(Record::Address)?)?;
+            },
+            Record::Text(_) => {
+                for text in self.txt {
+                    writer.bytes(&[text.len().min(255) as u8])?;
+                    writer.bytes(&text.as_bytes()[..text.len().min(255)])?;
+                }
+                if self.txt.is_empty() {
+                    // A TXT record can't be empty
+                    writer.bytes(&[0])?;
+                }
+            },
+        }
+        let rdata = (writer.len - length - 2) as u16;
+        writer.patch(length, rdata);
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ string::String, vec, vec::Vec };
+    use super::*;
+
+    const SERVICES: [Service; 2] = [
+        Service { kind: "_ant-aux-rpc._tcp", port: 1338 },
+        Service { kind: "_grbl._tcp", port: 1337 },
+    ];
+    const TXT: [&str; 1] = ["id=0123456789ab"];
+    const ADDRESS: Ipv4Address = Ipv4Address::new(192, 168, 7, 1);
+    const LINK_LOCAL: Ipv6Address = Ipv6Address::new(0xfe80, 0, 0, 0, 0x0211, 0x22ff, 0xfe33, 0x4455);
+    const SLAAC: Ipv6Address = Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0x0211, 0x22ff, 0xfe33, 0x4455);
+    const ADDRESSES: Addresses = Addresses { ipv4: Some(ADDRESS), ipv6: &[LINK_LOCAL] };
+
+    fn responder() -> Responder<'static> {
+        Responder::new("ant-auxiliary", &SERVICES, &TXT)
+    }
+
+    fn encode(name: &str) -> Vec<u8> {
+        let name = Name::new(&[name]).unwrap();
+        name.bytes[..name.len].to_vec()
+    }
+
+    fn query(id: u16, questions: &[(&str, u16)]) -> Vec<u8> {
+        let mut query = vec![];
+        query.extend(id.to_be_bytes());
+        query.extend([0, 0]);
+        query.extend((questions.len() as u16).to_be_bytes());
+        query.extend([0, 0, 0, 0, 0, 0]);
+        for (name, kind) in questions {
+            query.extend(encode(name));
+            query.extend(kind.to_be_bytes());
+            query.extend(CLASS_IN.to_be_bytes());
+        }
+        query
+    }
+
+    #[derive(Debug)]
+    struct Answer {
+        name: String,
+        kind: u16,
+        class: u16,
+        ttl: u32,
+        data: Vec<u8>,
+    }
+
+    fn name(message: &[u8], offset: usize) -> (String, usize) {
+        let (name, next) = Name::read(message, offset).unwrap();
+        let mut text = String::new();
+        let mut offset = 0;
+        while name.bytes[offset] != 0 {
+            let len = name.bytes[offset] as usize;
+            if !text.is_empty() {
+                text.push('.');
+            }
+            text.push_str(core::str::from_utf8(&name.bytes[offset + 1..offset + 1 + len]).unwrap());
+            offset += 1 + len;
+        }
+        (text, next)
+    }
+
+    // Returns the ID, the question count, and the answer and additional records
+    fn parse(reply: &[u8]) -> (u16, u16, Vec<Answer>, Vec<Answer>) {
+        let field = |offset: usize| u16::from_be_bytes([reply[offset], reply[offset + 1]]);
+        assert_eq!(field(2), RESPONSE | AUTHORITATIVE);
+        let (questions, answers, additional) = (field(4), field(6), field(10));
+        let mut offset = HEADER;
+        for _ in 0..questions {
+            offset = name(reply, offset).1 + 4;
+        }
+        let mut records = vec![];
+        for _ in 0..answers + additional {
+            let (name, next) = name(reply, offset);
+            let len = field(next + 8) as usize;
+            records.push(Answer {
+                name,
+                kind: field(next),
+                class: field(next + 2),
+                ttl: u32::from_be_bytes(reply[next + 4..next + 8].try_into().unwrap()),
+                data: reply[next + 10..next + 10 + len].to_vec(),
+            });
+            offset = next + 10 + len;
+        }
+        assert_eq!(offset, reply.len());
+        let additional = records.split_off(answers as usize);
+        (field(0), questions, records, additional)
+    }
+
+    fn ask(query: &[u8], legacy: bool) -> Option<(u16, u16, Vec<Answer>, Vec<Answer>)> {
+        ask_with(query, legacy, &ADDRESSES)
+    }
+
+    fn ask_with(query: &[u8], legacy: bool, addresses: &Addresses) -> Option<(u16, u16, Vec<Answer>, Vec<Answer>)> {
+        let mut buffer = [0u8; 1500];
+        let len = responder().process(query, legacy, addresses, &mut buffer).unwrap()?;
+        Some(parse(&buffer[..len]))
+    }
+
+    #[test]
+    fn record_indices() {
+        for index in 0..10 {
+            assert_eq!(Record::from_index(index).index(), index);
+        }
+    }
+
+    #[test]
+    fn address() {
+        let (id, questions, answers, additional) = ask(&query(7, &[("ant-auxiliary.local", TYPE_A)]), false).unwrap();
+        assert_eq!((id, questions), (0, 0));
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].name, "ant-auxiliary.local");
+        assert_eq!(answers[0].kind, TYPE_A);
+        assert_eq!(answers[0].class, CLASS_IN | CACHE_FLUSH);
+        assert_eq!(answers[0].ttl, HOST_TTL);
+        assert_eq!(answers[0].data, [192, 168, 7, 1]);
+        assert!(additional.is_empty());
+    }
+
+    #[test]
+    fn ignores_case() {
+        assert!(ask(&query(0, &[("Ant-Auxiliary.LOCAL", TYPE_A)]), false).is_some());
+    }
+
+    #[test]
+    fn other_names() {
+        assert!(ask(&query(0, &[("printer.local", TYPE_A)]), false).is_none());
+        assert!(ask(&query(0, &[("_http._tcp.local", TYPE_PTR)]), false).is_none());
+        // The right name, but a type it doesn't have
+        assert!(ask(&query(0, &[("ant-auxiliary.local", TYPE_SRV)]), false).is_none());
+    }
+
+    #[test]
+    fn ipv6_addresses() {
+        let addresses = Addresses { ipv4: Some(ADDRESS), ipv6: &[LINK_LOCAL, SLAAC] };
+        let (_, _, answers, additional) = ask_with(&query(0, &[("ant-auxiliary.local", TYPE_AAAA)]), false, &addresses).unwrap();
+        let data: Vec<_> = answers.iter().map(|answer| {
+            assert_eq!(answer.name, "ant-auxiliary.local");
+            assert_eq!(answer.kind, TYPE_AAAA);
+            assert_eq!(answer.class, CLASS_IN | CACHE_FLUSH);
+            answer.data.clone()
+        }).collect();
+        assert_eq!(data, [LINK_LOCAL.octets(), SLAAC.octets()]);
+        assert!(additional.is_empty());
+    }
+
+    #[test]
+    fn ipv6_only() {
+        let addresses = Addresses { ipv4: None, ipv6: &[LINK_LOCAL] };
+        assert!(ask_with(&query(0, &[("ant-auxiliary.local", TYPE_A)]), false, &addresses).is_none());
+        let (_, _, answers, _) = ask_with(&query(0, &[("ant-auxiliary.local", TYPE_ANY)]), false, &addresses).unwrap();
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].kind, TYPE_AAAA);
+        // Only the address it has comes with a service
+        let (_, _, _, additional) = ask_with(&query(0, &[("_grbl._tcp.local", TYPE_PTR)]), false, &addresses).unwrap();
+        let kinds: Vec<_> = additional.iter().map(|record| record.kind).collect();
+        assert_eq!(kinds, [TYPE_AAAA, TYPE_SRV, TYPE_TXT]);
+    }
+
+    #[test]
+    fn browse() {
+        let (_, _, answers, additional) = ask(&query(0, &[("_services._dns-sd._udp.local", TYPE_PTR)]), false).unwrap();
+        let services: Vec<_> = answers.iter().map(|answer| {
+            assert_eq!(answer.class, CLASS_IN);
+            name(&answer.data, 0).0
+        }).collect();
+        assert_eq!(services, ["_ant-aux-rpc._tcp.local", "_grbl._tcp.local"]);
+        assert!(additional.is_empty());
+    }
+
+    #[test]
+    fn service() {
+        let (_, _, answers, additional) = ask(&query(0, &[("_grbl._tcp.local", TYPE_PTR)]), false).unwrap();
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].class, CLASS_IN);
+        assert_eq!(answers[0].ttl, OTHER_TTL);
+        assert_eq!(name(&answers[0].data, 0).0, "ant-auxiliary._grbl._tcp.local");
+
+        // Everything needed to connect comes with it
+        let kinds: Vec<_> = additional.iter().map(|record| (record.name.as_str(), record.kind)).collect();
+        assert_eq!(kinds, [
+            ("ant-auxiliary.local", TYPE_A),
+            ("ant-auxiliary.local", TYPE_AAAA),
+            ("ant-auxiliary._grbl._tcp.local", TYPE_SRV),
+            ("ant-auxiliary._grbl._tcp.local", TYPE_TXT),
+        ]);
+        let srv = &additional[2].data;
+        assert_eq!(u16::from_be_bytes([srv[4], srv[5]]), 1337);
+        assert_eq!(name(srv, 6).0, "ant-auxiliary.local");
+        assert_eq!(additional[3].data, b"\x0fid=0123456789ab");
+    }
+
+    #[test]
+    fn any() {
+        let (_, _, answers, additional) = ask(&query(0, &[("ant-auxiliary._ant-aux-rpc._tcp.local", TYPE_ANY)]), false).unwrap();
+        let kinds: Vec<_> = answers.iter().map(|record| record.kind).collect();
+        assert_eq!(kinds, [TYPE_SRV, TYPE_TXT]);
+        let kinds: Vec<_> = additional.iter().map(|record| record.kind).collect();
+        assert_eq!(kinds, [TYPE_A, TYPE_AAAA]);
+    }
+
+    #[test]
+    fn compressed_question() {
+        // The second question points into the first for "local"
+        let mut query = query(0, &[("printer.local", TYPE_A)]);
+        query[5] = 2;
+        query.extend([13]);
+        query.extend(b"ant-auxiliary");
+        query.extend([0xc0, (HEADER + 8) as u8]);
+        query.extend(TYPE_A.to_be_bytes());
+        query.extend(CLASS_IN.to_be_bytes());
+        let (_, _, answers, _) = ask(&query, false).unwrap();
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].name, "ant-auxiliary.local");
+    }
+
+    #[test]
+    fn pointer_loop() {
+        let mut query = query(0, &[]);
+        query[5] = 1;
+        query.extend([0xc0, HEADER as u8, 0, 1, 0, 1]);
+        assert!(ask(&query, false).is_none());
+    }
+
+    #[test]
+    fn legacy() {
+        let (id, questions, answers, _) = ask(&query(0x1234, &[("ant-auxiliary.local", TYPE_A)]), true).unwrap();
+        assert_eq!((id, questions), (0x1234, 1));
+        assert_eq!(answers[0].class, CLASS_IN);
+        assert_eq!(answers[0].ttl, LEGACY_TTL);
+    }
+
+    #[test]
+    fn responses_ignored() {
+        let mut query = query(0, &[("ant-auxiliary.local", TYPE_A)]);
+        query[2] = 0x84;
+        assert!(ask(&query, false).is_none());
+    }
+
+    #[test]
+    fn truncated() {
+        let query = query(0, &[("ant-auxiliary.local", TYPE_A)]);
+        for len in 0..query.len() {
+            assert!(ask(&query[..len], false).is_none());
+        }
+    }
+
+    #[test]
+    fn announce() {
+        let mut buffer = [0u8; 1500];
+        let addresses = Addresses { ipv4: Some(ADDRESS), ipv6: &[LINK_LOCAL, SLAAC] };
+        let len = responder().announce(&addresses, &mut buffer).unwrap();
+        let (_, _, answers, additional) = parse(&buffer[..len]);
+        assert_eq!(answers.len(), 11);
+        assert!(additional.is_empty());
+        // Too small a buffer
+        assert_eq!(responder().announce(&ADDRESSES, &mut buffer[..100]), Err(Overflow));
+        let query = query(0, &[("_services._dns-sd._udp.local", TYPE_PTR)]);
+        assert_eq!(responder().process(&query, false, &ADDRESSES, &mut buffer[..100]), Err(Overflow));
+    }
+}
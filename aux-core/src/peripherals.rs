@@ -0,0 +1,16 @@
+//! The peripherals the firmware registers, with the field numbers aux.proto
+//! gives their requests in the `Request` oneof and their commands in each
+//! request's `command` oneof. The discovery crate, which has the host's
+//! messages generated from aux.proto, tests them against it.
+
+use crate::registry::Description;
+
+pub const FAN: Description = Description { name: "fan", tag: 1, commands: &[1, 2, 3, 4, 5] };
+pub const TEMP: Description = Description { name: "temp", tag: 2, commands: &[1, 2, 3] };
+pub const LIGHT: Description = Description { name: "light", tag: 3, commands: &[1, 2] };
+pub const PWM: Description = Description { name: "pwm", tag: 4, commands: &[2, 3] };
+pub const SETTINGS: Description = Description { name: "settings", tag: 5, commands: &[1, 2, 3, 4] };
+pub const PROBE: Description = Description { name: "probe", tag: 6, commands: &[1, 2] };
+pub const HEALTH: Description = Description { name: "health", tag: 7, commands: &[1] };
+
+pub const ALL: [Description; 7] = [FAN, TEMP, LIGHT, PWM, SETTINGS, PROBE, HEALTH];
@@ -0,0 +1,217 @@
+//! Routing of requests to the peripherals that handle them. Each peripheral
+//! either takes a request, hands it back as not its own, or refuses it, so the
+//! dispatcher can answer every request it can't deliver in the same way.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Description {
+    pub name: &'static str,
+    /// The field number of the peripheral's requests in the request oneof
+    pub tag: u32,
+    /// The field numbers of the commands it handles, in its request's oneof
+    pub commands: &'static [u32],
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Route<R> {
+    /// Queued for the peripheral, which responds itself
+    Accepted,
+    /// The peripheral's queue is full, or it's still handling a request
+    Busy,
+    /// The peripheral has stopped
+    Closed,
+    /// For some other peripheral
+    Unrouted(R),
+}
+
+pub trait Peripheral<R> {
+    fn description(&self) -> Description;
+    fn route(&mut self, request: R) -> Route<R>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DispatchError {
+    /// No registered peripheral takes the request
+    Unknown,
+    Busy(Description),
+    Closed(Description),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegistryFull;
+
+pub struct Registry<'a, R, const N: usize> {
+    peripherals: [Option<&'a mut dyn Peripheral<R>>; N],
+}
+
+impl <'a, R, const N: usize> Default for Registry<'a, R, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a, R, const N: usize> Registry<'a, R, N> {
+    pub fn new() -> Self {
+        Registry { peripherals: [(); N].map(|_| None) }
+    }
+
+    pub fn register(&mut self, peripheral: &'a mut dyn Peripheral<R>) -> Result<(), RegistryFull> {
+        let slot = self.peripherals.iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(RegistryFull)?;
+        *slot = Some(peripheral);
+        Ok(())
+    }
+
+    /// Offers the request to each peripheral in the order they were
+    /// registered, until one takes or refuses it
+    pub fn dispatch(&mut self, mut request: R) -> Result<Description, DispatchError> {
+        for peripheral in self.peripherals.iter_mut().flatten() {
+            request = match peripheral.route(request) {
+                Route::Accepted => return Ok(peripheral.description()),
+                Route::Busy => return Err(DispatchError::Busy(peripheral.description())),
+                Route::Closed => return Err(DispatchError::Closed(peripheral.description())),
+                Route::Unrouted(request) => request,
+            };
+        }
+        Err(DispatchError::Unknown)
+    }
+
+    pub fn descriptions(&self) -> impl Iterator<Item = Description> + use<'_, 'a, R, N> {
+        self.peripherals.iter().flatten().map(|peripheral| peripheral.description())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Request {
+        Fan(u8),
+        Light(u8),
+        Relay,
+    }
+
+    struct Fan {
+        queue: [Option<u8>; 2],
+        closed: bool,
+    }
+
+    impl Fan {
+        fn new() -> Self {
+            Fan { queue: [None; 2], closed: false }
+        }
+    }
+
+    impl Peripheral<Request> for Fan {
+        fn description(&self) -> Description {
+            Description { name: "fan", tag: 1, commands: &[1] }
+        }
+
+        fn route(&mut self, request: Request) -> Route<Request> {
+            match request {
+                Request::Fan(_) if self.closed => Route::Closed,
+                Request::Fan(speed) => match self.queue.iter_mut().find(|slot| slot.is_none()) {
+                    Some(slot) => {
+                        *slot = Some(speed);
+                        Route::Accepted
+                    },
+                    None => Route::Busy,
+                },
+                request => Route::Unrouted(request),
+            }
+        }
+    }
+
+    struct Light {
+        last: Option<u8>,
+    }
+
+    impl Peripheral<Request> for Light {
+        fn description(&self) -> Description {
+            Description { name: "light", tag: 3, commands: &[1] }
+        }
+
+        fn route(&mut self, request: Request) -> Route<Request> {
+            match request {
+                Request::Light(level) => {
+                    self.last = Some(level);
+                    Route::Accepted
+                },
+                request => Route::Unrouted(request),
+            }
+        }
+    }
+
+    #[test]
+    fn routes_to_owner() {
+        let mut fan = Fan::new();
+        let mut light = Light { last: None };
+        {
+            let mut registry = Registry::<Request, 4>::new();
+            registry.register(&mut fan).unwrap();
+            registry.register(&mut light).unwrap();
+            assert_eq!(registry.dispatch(Request::Light(7)).unwrap().name, "light");
+            assert_eq!(registry.dispatch(Request::Fan(50)).unwrap().name, "fan");
+        }
+        assert_eq!(light.last, Some(7));
+        assert_eq!(fan.queue, [Some(50), None]);
+    }
+
+    #[test]
+    fn unknown() {
+        let mut fan = Fan::new();
+        let mut registry = Registry::<Request, 4>::new();
+        registry.register(&mut fan).unwrap();
+        assert_eq!(registry.dispatch(Request::Relay), Err(DispatchError::Unknown));
+        assert_eq!(registry.dispatch(Request::Light(1)), Err(DispatchError::Unknown));
+    }
+
+    #[test]
+    fn busy() {
+        let mut fan = Fan::new();
+        let mut registry = Registry::<Request, 1>::new();
+        registry.register(&mut fan).unwrap();
+        registry.dispatch(Request::Fan(1)).unwrap();
+        registry.dispatch(Request::Fan(2)).unwrap();
+        assert_eq!(registry.dispatch(Request::Fan(3)),
+            Err(DispatchError::Busy(Description { name: "fan", tag: 1, commands: &[1] })));
+    }
+
+    #[test]
+    fn closed() {
+        let mut fan = Fan::new();
+        fan.closed = true;
+        let mut registry = Registry::<Request, 1>::new();
+        registry.register(&mut fan).unwrap();
+        assert_eq!(registry.dispatch(Request::Fan(1)),
+            Err(DispatchError::Closed(Description { name: "fan", tag: 1, commands: &[1] })));
+    }
+
+    #[test]
+    fn full() {
+        let mut fan = Fan::new();
+        let mut light = Light { last: None };
+        let mut registry = Registry::<Request, 1>::new();
+        registry.register(&mut fan).unwrap();
+        assert_eq!(registry.register(&mut light), Err(RegistryFull));
+    }
+
+    #[test]
+    fn descriptions() {
+        let mut fan = Fan::new();
+        let mut light = Light { last: None };
+        let mut registry = Registry::<Request, 4>::new();
+        assert_eq!(registry.descriptions().count(), 0);
+        registry.register(&mut fan).unwrap();
+        registry.register(&mut light).unwrap();
+        let names: [&str; 2] = {
+            let mut descriptions = registry.descriptions().map(|d| d.name);
+            [descriptions.next().unwrap(), descriptions.next().unwrap()]
+        };
+        assert_eq!(names, ["fan", "light"]);
+    }
+}
@@ -0,0 +1,513 @@
+//! The sockets behind the services the device answers itself, rather than an
+//! application task: the DHCP server, SLAAC, mDNS and discovery. The firmware
+//! gives each its socket buffers from its own storage, polls them after the
+//! interface, and polls the interface's egress when any of them has something
+//! to send. The tests here drive them the same way on smoltcp's loopback.
+
+use heapless::Vec;
+use smoltcp::{
+    iface::{ Interface, SocketHandle, SocketSet },
+    socket::{ raw, udp },
+    time::Instant,
+    wire::{
+        EthernetAddress, IpAddress, IpCidr, IpEndpoint, IpProtocol, IpVersion, Ipv4Address, Ipv6Address,
+        Ipv6Cidr, DHCP_CLIENT_PORT, DHCP_SERVER_PORT,
+    },
+};
+
+use crate::{
+    dhcp_server::Server,
+    discovery,
+    ipv6::{ self, Slaac },
+    mdns::{ self, Overflow, Responder },
+};
+
+/// The largest DHCP message a client has to accept
+pub const DHCP_MESSAGE: usize = 576;
+/// Queries are small, but the announcement carries every record, with the
+/// host name in most of them. Two services, a 32 character host name, the
+/// firmware's 27 character TXT and two IPv6 addresses take 899 bytes.
+pub const MDNS_MESSAGE: usize = 1024;
+/// Router advertisements are small, unless they carry a lot of options
+pub const NDISC_MESSAGE: usize = 256;
+/// An announcement with two IPv6 addresses and a few services
+pub const ANNOUNCEMENT: usize = 256;
+/// Room for discovery queries from later versions
+pub const QUERY: usize = 64;
+// The IPv6 addresses announced, by mDNS or discovery
+const ADDRESSES: usize = 4;
+
+// The addresses in use on the interface
+#[derive(Clone, PartialEq)]
+struct Addresses {
+    ipv4: Option<Ipv4Address>,
+    ipv6: Vec<Ipv6Address, ADDRESSES>,
+}
+
+impl Addresses {
+    fn of(interface: &Interface) -> Self {
+        Addresses {
+            ipv4: interface.ipv4_addr().filter(|address| !address.is_unspecified()),
+            ipv6: interface.ip_addrs().iter()
+                .filter_map(|cidr| match cidr {
+                    IpCidr::Ipv6(cidr) => Some(cidr.address()),
+                    _ => None,
+                })
+                .take(ADDRESSES)
+                .collect(),
+        }
+    }
+
+    fn mdns(&self) -> mdns::Addresses<'_> {
+        mdns::Addresses { ipv4: self.ipv4, ipv6: &self.ipv6 }
+    }
+}
+
+/// Answers DHCP requests on the server port
+pub struct DhcpServer {
+    server: Server,
+    handle: SocketHandle,
+}
+
+impl DhcpServer {
+    pub fn new<'a>(server: Server, rx: udp::PacketBuffer<'a>, tx: udp::PacketBuffer<'a>, sockets: &mut SocketSet<'a>) -> Self {
+        let mut socket = udp::Socket::new(rx, tx);
+        socket.bind(DHCP_SERVER_PORT).unwrap();
+        DhcpServer { server, handle: sockets.add(socket) }
+    }
+
+    pub fn server(&self) -> &Server {
+        &self.server
+    }
+
+    /// Returns whether there are replies to send
+    pub fn poll(&self, sockets: &mut SocketSet<'_>) -> bool {
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        let mut reply = [0u8; DHCP_MESSAGE];
+        let mut replied = false;
+        while let Ok((request, _)) = socket.recv() {
+            let Some((destination, len)) = self.server.process(request, &mut reply) else { continue };
+            log!(debug, "DHCP reply to {}", destination);
+            match socket.send_slice(&reply[..len], IpEndpoint::new(destination.into(), DHCP_CLIENT_PORT)) {
+                Ok(()) => replied = true,
+                Err(_err) => { log!(warn, "DHCP reply: {}", _err); },
+            }
+        }
+        replied
+    }
+}
+
+/// The IPv6 link-local address, and SLAAC from router advertisements read
+/// from a raw ICMPv6 socket
+pub struct Autoconf {
+    slaac: Slaac,
+    link_local: Ipv6Cidr,
+    handle: SocketHandle,
+}
+
+impl Autoconf {
+    /// Adds the link-local address to the interface
+    pub fn new<'a>(
+        mac_address: EthernetAddress,
+        rx: raw::PacketBuffer<'a>,
+        tx: raw::PacketBuffer<'a>,
+        interface: &mut Interface,
+        sockets: &mut SocketSet<'a>) -> Self
+    {
+        let link_local = ipv6::link_local(mac_address);
+        log!(info, "IPv6 link-local address: {}", link_local);
+        interface.update_ip_addrs(|addrs| addrs.push(IpCidr::Ipv6(link_local)).unwrap());
+        let socket = raw::Socket::new(IpVersion::Ipv6, IpProtocol::Icmpv6, rx, tx);
+        Autoconf { slaac: Slaac::new(mac_address), link_local, handle: sockets.add(socket) }
+    }
+
+    pub fn link_local(&self) -> Ipv6Cidr {
+        self.link_local
+    }
+
+    /// Applies router advertisements and expiries to the interface. Returns
+    /// whether there's a router solicitation to send. Solicitations sent
+    /// before the host has configured its end of the link would be lost, so
+    /// they're only sent when `solicit` is true.
+    pub fn poll(&mut self, now: Instant, solicit: bool, interface: &mut Interface, sockets: &mut SocketSet<'_>) -> bool {
+        let socket = sockets.get_mut::<raw::Socket>(self.handle);
+        let mut changed = self.slaac.poll(now);
+        while let Ok(packet) = socket.recv() {
+            changed |= self.slaac.process(now, packet);
+        }
+
+        let mut solicitation = [0u8; NDISC_MESSAGE];
+        let solicited = solicit && match self.slaac.solicit(now, self.link_local.address(), &mut solicitation) {
+            Some(len) => socket.send_slice(&solicitation[..len]).is_ok(),
+            None => false,
+        };
+
+        if changed {
+            let (address, router) = (self.slaac.address(), self.slaac.router());
+            log!(info, "SLAAC address: {}, router: {}", address, router);
+            let link_local = self.link_local;
+            interface.update_ip_addrs(|addrs| {
+                addrs.retain(|cidr| !matches!(cidr, IpCidr::Ipv6(cidr) if *cidr != link_local));
+                if let Some(address) = address {
+                    addrs.push(IpCidr::Ipv6(address)).unwrap();
+                }
+            });
+            match router {
+                Some(router) => { interface.routes_mut().add_default_ipv6_route(router).unwrap(); },
+                None => { interface.routes_mut().remove_default_ipv6_route(); },
+            }
+        }
+        solicited
+    }
+}
+
+/// Answers mDNS queries for the responder's host name and services, over
+/// IPv4 and IPv6, and announces them when the addresses change
+pub struct Mdns<'r> {
+    responder: Responder<'r>,
+    handle: SocketHandle,
+    // The addresses last announced, to announce again when they change
+    announced: Option<Addresses>,
+}
+
+impl <'r> Mdns<'r> {
+    /// Joins the IPv4 and IPv6 mDNS groups on the interface
+    pub fn new<'a>(
+        responder: Responder<'r>,
+        rx: udp::PacketBuffer<'a>,
+        tx: udp::PacketBuffer<'a>,
+        interface: &mut Interface,
+        sockets: &mut SocketSet<'a>) -> Self
+    {
+        if let Err(_err) = interface.join_multicast_group(mdns::GROUP) {
+            log!(error, "Failed to join the mDNS group: {}", _err);
+        }
+        if let Err(_err) = interface.join_multicast_group(mdns::GROUP_IPV6) {
+            log!(error, "Failed to join the IPv6 mDNS group: {}", _err);
+        }
+        let mut socket = udp::Socket::new(rx, tx);
+        socket.bind(mdns::PORT).unwrap();
+        // RFC 6762 has responders send with a hop limit of 255
+        socket.set_hop_limit(Some(255));
+        Mdns { responder, handle: sockets.add(socket), announced: None }
+    }
+
+    /// Returns whether there are replies to send
+    pub fn poll(&mut self, interface: &Interface, sockets: &mut SocketSet<'_>) -> bool {
+        let addresses = Addresses::of(interface);
+        // Nothing to answer for until there's an address
+        if addresses.ipv4.is_none() && addresses.ipv6.is_empty() {
+            return false;
+        }
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        let mut reply = [0u8; MDNS_MESSAGE];
+        let mut replied = false;
+        if self.announced.as_ref() != Some(&addresses) {
+            match self.responder.announce(&addresses.mdns(), &mut reply) {
+                Ok(len) => {
+                    // To each group there's an address to send from
+                    let groups = [
+                        addresses.ipv4.map(|_| IpAddress::from(mdns::GROUP)),
+                        (!addresses.ipv6.is_empty()).then_some(IpAddress::from(mdns::GROUP_IPV6)),
+                    ];
+                    let mut sent = true;
+                    for group in groups.into_iter().flatten() {
+                        match socket.send_slice(&reply[..len], (group, mdns::PORT)) {
+                            Ok(()) => replied = true,
+                            // Tried again on the next poll
+                            Err(_err) => {
+                                log!(debug, "mDNS announcement to {}: {}", group, _err);
+                                sent = false;
+                            },
+                        }
+                    }
+                    if sent {
+                        self.announced = Some(addresses.clone());
+                    }
+                },
+                // It never will fit, so don't try again until the addresses change
+                Err(Overflow) => {
+                    log!(warn, "mDNS announcement doesn't fit in {} bytes", MDNS_MESSAGE);
+                    self.announced = Some(addresses.clone());
+                },
+            }
+        }
+        while let Ok((query, metadata)) = socket.recv() {
+            // A resolver that isn't a full mDNS querier sends from another
+            // port, and expects a reply straight back to it
+            let legacy = metadata.endpoint.port != mdns::PORT;
+            let endpoint = metadata.endpoint;
+            let len = match self.responder.process(query, legacy, &addresses.mdns(), &mut reply) {
+                Ok(Some(len)) => len,
+                Ok(None) => continue,
+                Err(Overflow) => {
+                    log!(warn, "mDNS reply to {} doesn't fit in {} bytes", endpoint, MDNS_MESSAGE);
+                    continue;
+                },
+            };
+            log!(debug, "mDNS reply to {}", endpoint);
+            // Multicast to the group of the family it was asked in
+            let destination = match (legacy, endpoint.addr) {
+                (true, _) => endpoint,
+                (false, IpAddress::Ipv4(_)) => IpEndpoint::new(mdns::GROUP.into(), mdns::PORT),
+                (false, IpAddress::Ipv6(_)) => IpEndpoint::new(mdns::GROUP_IPV6.into(), mdns::PORT),
+            };
+            match socket.send_slice(&reply[..len], destination) {
+                Ok(()) => replied = true,
+                Err(_err) => { log!(warn, "mDNS reply: {}", _err); },
+            }
+        }
+        replied
+    }
+}
+
+/// The reply to a discovery query, a `DeviceAnnouncement` encoded by
+/// whichever protobuf codec the caller has
+pub trait Announcement {
+    /// Encodes the announcement with the addresses in use into buffer,
+    /// returning its length, or None if it doesn't fit
+    fn encode(&mut self, ipv4: Option<Ipv4Address>, ipv6: &[Ipv6Address], buffer: &mut [u8]) -> Option<usize>;
+}
+
+/// Answers discovery queries on `discovery::PORT`
+pub struct Discovery<A> {
+    announcement: A,
+    handle: SocketHandle,
+}
+
+impl <A: Announcement> Discovery<A> {
+    pub fn new<'a>(announcement: A, rx: udp::PacketBuffer<'a>, tx: udp::PacketBuffer<'a>, sockets: &mut SocketSet<'a>) -> Self {
+        let mut socket = udp::Socket::new(rx, tx);
+        socket.bind(discovery::PORT).unwrap();
+        Discovery { announcement, handle: sockets.add(socket) }
+    }
+
+    /// Returns whether there are replies to send
+    pub fn poll(&mut self, interface: &Interface, sockets: &mut SocketSet<'_>) -> bool {
+        let addresses = Addresses::of(interface);
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        let mut reply = [0u8; ANNOUNCEMENT];
+        let mut replied = false;
+        while let Ok((query, metadata)) = socket.recv() {
+            if !discovery::is_query(query) {
+                continue;
+            }
+            let endpoint = metadata.endpoint;
+            let Some(len) = self.announcement.encode(addresses.ipv4, &addresses.ipv6, &mut reply) else {
+                log!(warn, "Discovery announcement doesn't fit");
+                continue;
+            };
+            log!(debug, "Discovery reply to {}", endpoint);
+            match socket.send_slice(&reply[..len], endpoint) {
+                Ok(()) => replied = true,
+                Err(_err) => { log!(warn, "Discovery reply: {}", _err); },
+            }
+        }
+        replied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ vec, vec::Vec };
+
+    use smoltcp::{
+        iface::Config,
+        phy::{ Loopback, Medium },
+        time::Duration,
+        wire::HardwareAddress,
+    };
+
+    use super::*;
+    use crate::mdns::Service;
+
+    const ADDRESS: Ipv4Address = Ipv4Address::new(127, 0, 0, 1);
+    const ADDRESS_IPV6: Ipv6Address = Ipv6Address::LOCALHOST;
+    const CLIENT_PORT: u16 = 49152;
+    const SERVICES: [Service; 1] = [Service { kind: "_ant-aux-rpc._tcp", port: 1338 }];
+
+    fn udp_buffer(packets: usize, message: usize) -> udp::PacketBuffer<'static> {
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; packets], vec![0; packets * message])
+    }
+
+    // A service and a client socket on the loopback device
+    struct Loop {
+        device: Loopback,
+        interface: Interface,
+        sockets: SocketSet<'static>,
+        now: Instant,
+        client: SocketHandle,
+    }
+
+    impl Loop {
+        fn new() -> Self {
+            Self::with_address(IpCidr::new(ADDRESS.into(), 8))
+        }
+
+        fn with_address(address: IpCidr) -> Self {
+            let mut device = Loopback::new(Medium::Ip);
+            let now = Instant::from_millis(0);
+            let mut interface = Interface::new(Config::new(HardwareAddress::Ip), &mut device, now);
+            interface.update_ip_addrs(|addrs| addrs.push(address).unwrap());
+            let mut sockets = SocketSet::new(vec![]);
+            let mut client = udp::Socket::new(udp_buffer(4, 1024), udp_buffer(4, 1024));
+            client.bind(CLIENT_PORT).unwrap();
+            let client = sockets.add(client);
+            Loop { device, interface, sockets, now, client }
+        }
+
+        // Polls the interface, then the service as the firmware's USB task does
+        fn poll(&mut self, mut service: impl FnMut(&mut Interface, &mut SocketSet<'static>) -> bool) {
+            for _ in 0..5 {
+                self.now += Duration::from_millis(10);
+                self.interface.poll(self.now, &mut self.device, &mut self.sockets);
+                if service(&mut self.interface, &mut self.sockets) {
+                    self.interface.poll_egress(self.now, &mut self.device, &mut self.sockets);
+                }
+            }
+        }
+
+        fn send(&mut self, port: u16, bytes: &[u8]) {
+            let address = self.interface.ip_addrs()[0].address();
+            let client = self.sockets.get_mut::<udp::Socket>(self.client);
+            client.send_slice(bytes, (address, port)).unwrap();
+        }
+
+        fn received(&mut self) -> Vec<Vec<u8>> {
+            let client = self.sockets.get_mut::<udp::Socket>(self.client);
+            let mut received = vec![];
+            while let Ok((bytes, _)) = client.recv() {
+                received.push(bytes.to_vec());
+            }
+            received
+        }
+    }
+
+    // Only the IPv4 address, which is enough to see it was filled in
+    struct Ipv4Only;
+
+    impl Announcement for Ipv4Only {
+        fn encode(&mut self, ipv4: Option<Ipv4Address>, _ipv6: &[Ipv6Address], buffer: &mut [u8]) -> Option<usize> {
+            buffer.get_mut(..4)?.copy_from_slice(&ipv4?.octets());
+            Some(4)
+        }
+    }
+
+    #[test]
+    fn discovery() {
+        let mut net = Loop::new();
+        let mut discovery = Discovery::new(Ipv4Only, udp_buffer(2, QUERY), udp_buffer(2, ANNOUNCEMENT), &mut net.sockets);
+
+        net.send(discovery::PORT, b"something else");
+        net.poll(|interface, sockets| discovery.poll(interface, sockets));
+        assert!(net.received().is_empty());
+
+        net.send(discovery::PORT, discovery::QUERY);
+        net.poll(|interface, sockets| discovery.poll(interface, sockets));
+        assert_eq!(net.received(), [ADDRESS.octets()]);
+    }
+
+    const TYPE_A: u16 = 1;
+    const TYPE_PTR: u16 = 12;
+    const TYPE_AAAA: u16 = 28;
+
+    // A query with one question, for a name in wire format
+    fn query(id: u16, name: &[u8], kind: u16) -> Vec<u8> {
+        let mut query = vec![];
+        query.extend(id.to_be_bytes());
+        query.extend([0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        query.extend(name);
+        query.extend(kind.to_be_bytes());
+        query.extend([0, 1]);
+        query
+    }
+
+    // As the firmware advertises them
+    const FIRMWARE_SERVICES: [Service; 2] = [
+        Service { kind: "_ant-aux-rpc._tcp", port: 1338 },
+        Service { kind: "_grbl._tcp", port: 1337 },
+    ];
+    // id= and the hex of the STM32's 96 bit unique ID
+    const FIRMWARE_TXT: [&str; 1] = ["id=0123456789abcdef01234567"];
+
+    #[test]
+    fn longest_host_name_fits() {
+        let hostname = "a".repeat(crate::hostname::MAX_LEN);
+        assert!(crate::hostname::valid(&hostname));
+        let responder = Responder::new(&hostname, &FIRMWARE_SERVICES, &FIRMWARE_TXT);
+        // A link-local and a SLAAC address
+        let ipv6 = [ADDRESS_IPV6; 2];
+        let addresses = mdns::Addresses { ipv4: Some(ADDRESS), ipv6: &ipv6 };
+        let mut reply = [0u8; MDNS_MESSAGE];
+        assert!(responder.announce(&addresses, &mut reply).is_ok());
+        // The reply to a query for one service carries most of the records
+        let query = query(0, b"\x0c_ant-aux-rpc\x04_tcp\x05local\x00", TYPE_PTR);
+        assert!(matches!(responder.process(&query, false, &addresses, &mut reply), Ok(Some(_))));
+    }
+
+    #[test]
+    fn announces_until_sent() {
+        let mut net = Loop::new();
+        // Too little room to queue the announcement
+        let mut mdns = Mdns::new(
+            Responder::new("ant-auxiliary", &SERVICES, &[]),
+            udp_buffer(2, MDNS_MESSAGE),
+            udp_buffer(1, 64),
+            &mut net.interface,
+            &mut net.sockets);
+        net.poll(|interface, sockets| mdns.poll(interface, sockets));
+        assert!(mdns.announced.is_none());
+
+        let mut mdns = Mdns::new(
+            Responder::new("ant-auxiliary", &SERVICES, &[]),
+            udp_buffer(2, MDNS_MESSAGE),
+            udp_buffer(2, MDNS_MESSAGE),
+            &mut net.interface,
+            &mut net.sockets);
+        net.poll(|interface, sockets| mdns.poll(interface, sockets));
+        assert!(mdns.announced.is_some_and(|announced| announced.ipv4 == Some(ADDRESS)));
+    }
+
+    #[test]
+    fn mdns_legacy_query() {
+        let mut net = Loop::new();
+        let responder = Responder::new("ant-auxiliary", &SERVICES, &[]);
+        let mut mdns = Mdns::new(
+            responder,
+            udp_buffer(2, MDNS_MESSAGE),
+            udp_buffer(2, MDNS_MESSAGE),
+            &mut net.interface,
+            &mut net.sockets);
+
+        // From a port other than 5353
+        net.send(mdns::PORT, &query(0x1234, b"\x0dant-auxiliary\x05local\x00", TYPE_A));
+        net.poll(|interface, sockets| mdns.poll(interface, sockets));
+        let received = net.received();
+        assert_eq!(received.len(), 1);
+        let reply = &received[0];
+        // The query's ID, and one answer
+        assert_eq!(reply[..2], [0x12, 0x34]);
+        assert_eq!(reply[6..8], [0, 1]);
+        assert!(reply.ends_with(&ADDRESS.octets()));
+    }
+
+    #[test]
+    fn mdns_ipv6_only() {
+        let mut net = Loop::with_address(IpCidr::new(ADDRESS_IPV6.into(), 128));
+        let mut mdns = Mdns::new(
+            Responder::new("ant-auxiliary", &SERVICES, &[]),
+            udp_buffer(2, MDNS_MESSAGE),
+            udp_buffer(2, MDNS_MESSAGE),
+            &mut net.interface,
+            &mut net.sockets);
+
+        net.send(mdns::PORT, &query(0x1234, b"\x0dant-auxiliary\x05local\x00", TYPE_AAAA));
+        net.poll(|interface, sockets| mdns.poll(interface, sockets));
+        assert!(mdns.announced.as_ref().is_some_and(|announced| announced.ipv6 == [ADDRESS_IPV6]));
+        let received = net.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0][6..8], [0, 1]);
+        assert!(received[0].ends_with(&ADDRESS_IPV6.octets()));
+    }
+}
@@ -0,0 +1,148 @@
+
+//! Running count, mean, variance and bounds, by Welford's method, so the mean
+//! doesn't lose precision as the count grows.
+
+use micromath::F32Ext;
+
+#[derive(Clone, Copy)]
+pub struct StatsAccumulator<COUNT, VALUE> {
+    count: COUNT,
+    mean: VALUE,
+    sum_of_squares_of_deltas: VALUE,
+    min: VALUE,
+    max: VALUE,
+}
+
+impl Default for StatsAccumulator<u32, f32> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatsAccumulator<u32, f32>{
+    pub const fn new() -> Self {
+        StatsAccumulator {
+            count: 0,
+            mean: 0.0,
+            sum_of_squares_of_deltas: 0.0,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        }
+    }
+
+    pub fn add(&mut self, value: f32) {
+        self.count += 1;
+        if self.count == 1 {
+            self.mean = value;
+            self.min = value;
+            self.max = value;
+        } else {
+            let delta = value - self.mean;
+            self.mean += delta / self.count as f32;
+            self.sum_of_squares_of_deltas += delta * (value - self.mean);
+            if value < self.min {
+                self.min = value;
+            }
+            if value > self.max {
+                self.max = value;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    #[inline]
+    pub fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    #[inline]
+    pub fn min(&self) -> f32 {
+        self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    pub fn variance(&self) -> Option<f32> {
+        if self.count > 1 {
+            Some(self.sum_of_squares_of_deltas / (self.count as f32 - 1.0))
+        } else {
+            None
+        }
+    }
+
+    pub fn stddev(&self) -> Option<f32> {
+        self.variance().map(F32Ext::sqrt)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for StatsAccumulator<u32, f32> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "StatsAccumulator(count: {}, mean: {}, min: {}, max: {}, variance: {:?}, stddev: {:?})",
+            self.count(),
+            self.mean(),
+            self.min(),
+            self.max(),
+            self.variance(),
+            self.stddev()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn empty() {
+        let stats = StatsAccumulator::new();
+        assert_eq!(stats.count(), 0);
+        assert_eq!(stats.min(), f32::INFINITY);
+        assert_eq!(stats.max(), f32::NEG_INFINITY);
+        assert_eq!(stats.variance(), None);
+    }
+
+    #[test]
+    fn single() {
+        let mut stats = StatsAccumulator::new();
+        stats.add(77.0);
+        assert_eq!(stats.count(), 1);
+        assert_eq!((stats.mean(), stats.min(), stats.max()), (77.0, 77.0, 77.0));
+        assert_eq!(stats.stddev(), None);
+    }
+
+    #[test]
+    fn sample_variance() {
+        let mut stats = StatsAccumulator::new();
+        [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].iter().for_each(|value| stats.add(*value));
+        assert_eq!(stats.count(), 8);
+        assert_close(stats.mean(), 5.0);
+        assert_close(stats.variance().unwrap(), 32.0 / 7.0);
+        // micromath's square root is an approximation
+        let stddev = (32.0f32 / 7.0).sqrt();
+        assert!((stats.stddev().unwrap() - stddev).abs() < stddev * 0.01, "{:?}", stats.stddev());
+        assert_eq!((stats.min(), stats.max()), (2.0, 9.0));
+    }
+
+    // A naive sum of squares loses everything to rounding with a large offset
+    #[test]
+    fn large_offset() {
+        let mut stats = StatsAccumulator::new();
+        (0..10_000).for_each(|i| stats.add(1_000_000.0 + (i % 2) as f32));
+        assert_close(stats.mean(), 1_000_000.5);
+        assert!((stats.variance().unwrap() - 0.25).abs() < 0.01, "{:?}", stats.variance());
+    }
+}
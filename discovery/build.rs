@@ -0,0 +1,21 @@
+use prost::Message;
+
+// Generate the host's messages from the firmware's .proto, with the pure
+// Rust parser so there's no protoc to install
+fn proto_generate() {
+    let descriptors = protobuf_parse::Parser::new()
+        .pure()
+        .include("../microcontroller/proto")
+        .input("../microcontroller/proto/aux.proto")
+        .file_descriptor_set()
+        .unwrap();
+    let descriptors = protobuf::Message::write_to_bytes(&descriptors).unwrap();
+    prost_build::Config::new()
+        .compile_fds(prost_types::FileDescriptorSet::decode(descriptors.as_slice()).unwrap())
+        .unwrap();
+    println!("cargo:rerun-if-changed=../microcontroller/proto");
+}
+
+fn main() {
+    proto_generate();
+}
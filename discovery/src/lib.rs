@@ -0,0 +1,248 @@
+//! Finds boards on networks mDNS doesn't reach: sends the discovery query
+//! (see aux-core's discovery module) to broadcast or unicast addresses, and
+//! collects the `DeviceAnnouncement`s the boards reply with.
+//!
+//! The messages are generated from microcontroller/proto/aux.proto, the one
+//! the firmware encodes with, by a protobuf parser written in Rust, so the
+//! host needs no protoc.
+
+use std::{
+    io,
+    net::{ Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket },
+    thread,
+    time::{ Duration, Instant },
+};
+
+use prost::Message;
+
+pub use aux_core::discovery::{ is_query, PORT, QUERY };
+
+/// The messages of microcontroller/proto/aux.proto, generated by build.rs
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/_.rs"));
+}
+
+pub use proto::{ DeviceAnnouncement, ServicePort };
+
+impl DeviceAnnouncement {
+    pub fn ipv4_address(&self) -> Option<Ipv4Addr> {
+        Some(Ipv4Addr::from_bits(self.ipv4_address)).filter(|address| !address.is_unspecified())
+    }
+
+    pub fn ipv6_addresses(&self) -> impl Iterator<Item = Ipv6Addr> + '_ {
+        self.ipv6_addresses.iter()
+            .filter_map(|address| <[u8; 16]>::try_from(address.as_slice()).ok())
+            .map(Ipv6Addr::from)
+    }
+
+    /// The port of a service, by its DNS-SD type
+    pub fn port(&self, name: &str) -> Option<u16> {
+        self.services.iter()
+            .find(|service| service.name == name)
+            .and_then(|service| u16::try_from(service.port).ok())
+    }
+}
+
+/// A board that replied, and where the reply came from
+#[derive(Clone, Debug, PartialEq)]
+pub struct Board {
+    pub from: SocketAddr,
+    pub announcement: DeviceAnnouncement,
+}
+
+// How often to look for replies on each socket
+const POLL: Duration = Duration::from_millis(10);
+
+/// Sends the query to each target, such as 255.255.255.255:1339 or
+/// [ff02::1%usb0]:1339, and returns the boards that reply within the
+/// timeout. A board that replies more than once, to several targets or
+/// over both IPv4 and IPv6, is listed once.
+pub fn discover(targets: &[SocketAddr], timeout: Duration) -> io::Result<Vec<Board>> {
+    let mut sockets = Vec::new();
+    if targets.iter().any(SocketAddr::is_ipv4) {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        sockets.push(socket);
+    }
+    if targets.iter().any(SocketAddr::is_ipv6) {
+        sockets.push(UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?);
+    }
+    for socket in &sockets {
+        socket.set_nonblocking(true)?;
+        let ipv4 = socket.local_addr()?.is_ipv4();
+        for target in targets.iter().filter(|target| target.is_ipv4() == ipv4) {
+            socket.send_to(QUERY, target)?;
+        }
+    }
+
+    let deadline = Instant::now() + timeout;
+    let mut boards: Vec<Board> = Vec::new();
+    let mut buffer = [0u8; 1500];
+    while Instant::now() < deadline {
+        for socket in &sockets {
+            loop {
+                let (len, from) = match socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(err),
+                };
+                // Anything else on the port isn't an announcement
+                let Ok(announcement) = DeviceAnnouncement::decode(&buffer[..len]) else { continue };
+                if !boards.iter().any(|board| board.announcement.id == announcement.id) {
+                    boards.push(Board { from, announcement });
+                }
+            }
+        }
+        thread::sleep(POLL);
+    }
+    Ok(boards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement(id: &str) -> DeviceAnnouncement {
+        DeviceAnnouncement {
+            hostname: format!("ant-auxiliary-{}", id),
+            id: id.into(),
+            mac_address: vec![0x02, 0, 0, 0x41, 0x55, 0x58],
+            firmware_version: "0.1.0".into(),
+            interface_mac_address: vec![0x02, 0, 0, 0x41, 0x55, 0x59],
+            ipv4_address: Ipv4Addr::new(192, 168, 7, 1).to_bits(),
+            ipv6_addresses: vec!["fe80::ff:fe41:5558".parse::<Ipv6Addr>().unwrap().octets().to_vec()],
+            services: vec![
+                ServicePort { name: "_ant-aux-rpc._tcp".into(), port: 1338 },
+                ServicePort { name: "_grbl._tcp".into(), port: 1337 },
+            ],
+        }
+    }
+
+    // Answers queries as a board does, until it has answered count of them
+    fn board(socket: UdpSocket, id: &'static str, count: usize) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let mut buffer = [0u8; 64];
+            let mut answered = 0;
+            while answered < count {
+                let (len, from) = socket.recv_from(&mut buffer).unwrap();
+                if is_query(&buffer[..len]) {
+                    socket.send_to(&announcement(id).encode_to_vec(), from).unwrap();
+                    answered += 1;
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn accessors() {
+        let announcement = announcement("a");
+        assert_eq!(announcement.ipv4_address(), Some(Ipv4Addr::new(192, 168, 7, 1)));
+        assert_eq!(announcement.ipv6_addresses().collect::<Vec<_>>(), ["fe80::ff:fe41:5558".parse::<Ipv6Addr>().unwrap()]);
+        assert_eq!(announcement.port("_grbl._tcp"), Some(1337));
+        assert_eq!(announcement.port("_http._tcp"), None);
+        assert_eq!(DeviceAnnouncement::default().ipv4_address(), None);
+    }
+
+    #[test]
+    fn discovers_boards() {
+        let first = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let second = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let targets = [first.local_addr().unwrap(), second.local_addr().unwrap()];
+        let boards = [board(first, "a", 1), board(second, "b", 1)];
+
+        let found = discover(&targets, Duration::from_millis(300)).unwrap();
+        for board in boards {
+            board.join().unwrap();
+        }
+        let mut ids: Vec<_> = found.iter().map(|board| board.announcement.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(found[0].announcement, announcement(&found[0].announcement.id));
+    }
+
+    #[test]
+    fn listed_once() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let target = socket.local_addr().unwrap();
+        let board = board(socket, "a", 2);
+        let found = discover(&[target, target], Duration::from_millis(300)).unwrap();
+        board.join().unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].from, target);
+    }
+
+    #[test]
+    fn ignores_other_replies() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let target = socket.local_addr().unwrap();
+        let board = thread::spawn(move || {
+            let mut buffer = [0u8; 64];
+            let (_, from) = socket.recv_from(&mut buffer).unwrap();
+            // Not a protobuf: a field with wire type 7
+            socket.send_to(&[0x0f, 0xff], from).unwrap();
+        });
+        assert!(discover(&[target], Duration::from_millis(200)).unwrap().is_empty());
+        board.join().unwrap();
+    }
+
+    // The field number of the first field of an encoded message
+    fn field(message: &impl Message) -> u32 {
+        (message.encode_to_vec()[0] >> 3) as u32
+    }
+
+    // The firmware's peripheral descriptions live in aux-core, which has no
+    // messages of its own, so they're checked here against aux.proto
+    #[test]
+    fn peripheral_field_numbers() {
+        use aux_core::peripherals;
+        use proto::*;
+
+        let request = |peripheral| field(&Request { peripheral: Some(peripheral) });
+        let fan = |command| field(&FanRequest { command: Some(command) });
+        let temp = |command| field(&TempRequest { command: Some(command) });
+        let light = |command| field(&LightRequest { command: Some(command) });
+        let pwm = |command| field(&PwmRequest { command: Some(command), ..Default::default() });
+        let settings = |command| field(&SettingsRequest { command: Some(command) });
+        let probe = |command| field(&ProbeRequest { command: Some(command) });
+        let health = |command| field(&HealthRequest { command: Some(command) });
+        let described = [
+            (request(request::Peripheral::Fan(Default::default())), vec![
+                fan(fan_request::Command::Get(Default::default())),
+                fan(fan_request::Command::Set(Default::default())),
+                fan(fan_request::Command::Curve(Default::default())),
+                fan(fan_request::Command::StallDetection(Default::default())),
+                fan(fan_request::Command::Start(Default::default())),
+            ]),
+            (request(request::Peripheral::Temp(Default::default())), vec![
+                temp(temp_request::Command::Get(Default::default())),
+                temp(temp_request::Command::Diagnostics(Default::default())),
+                temp(temp_request::Command::History(Default::default())),
+            ]),
+            (request(request::Peripheral::Light(Default::default())), vec![
+                light(light_request::Command::Get(Default::default())),
+                light(light_request::Command::Set(Default::default())),
+            ]),
+            (request(request::Peripheral::Pwm(Default::default())), vec![
+                pwm(pwm_request::Command::Get(Default::default())),
+                pwm(pwm_request::Command::Set(Default::default())),
+            ]),
+            (request(request::Peripheral::Settings(Default::default())), vec![
+                settings(settings_request::Command::Get(Default::default())),
+                settings(settings_request::Command::Set(Default::default())),
+                settings(settings_request::Command::Remove(Default::default())),
+                settings(settings_request::Command::FactoryReset(Default::default())),
+            ]),
+            (request(request::Peripheral::Probe(Default::default())), vec![
+                probe(probe_request::Command::Get(Default::default())),
+                probe(probe_request::Command::Search(Default::default())),
+            ]),
+            (request(request::Peripheral::Health(Default::default())), vec![
+                health(health_request::Command::Get(Default::default())),
+            ]),
+        ];
+        for (description, (tag, commands)) in peripherals::ALL.iter().zip(described) {
+            assert_eq!(description.tag, tag, "{}", description.name);
+            assert_eq!(description.commands, commands, "{}", description.name);
+        }
+    }
+}
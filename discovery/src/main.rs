@@ -0,0 +1,106 @@
+//! Lists the boards that answer the discovery query.
+//!
+//!     ant-aux-discover [--timeout MILLIS] [ADDRESS...]
+//!
+//! Each ADDRESS is a broadcast or unicast address, with or without a port,
+//! like 192.168.2.255, 10.0.0.7:1339 or ff02::1%usb0, which asks every board
+//! on the USB link usb0 over IPv6. Without any, the query is broadcast on the
+//! local network.
+
+use std::{
+    env,
+    fs,
+    net::{ IpAddr, Ipv4Addr, SocketAddr },
+    process,
+    time::Duration,
+};
+
+use discovery::{ discover, Board, PORT };
+
+const USAGE: &str = "usage: ant-aux-discover [--timeout MILLIS] [ADDRESS...]";
+
+fn parse() -> Result<(Vec<SocketAddr>, Duration), String> {
+    let mut targets = Vec::new();
+    let mut timeout = Duration::from_secs(1);
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        if arg == "--timeout" {
+            let value = argv.next().ok_or("--timeout needs a value")?;
+            let millis = value.parse().map_err(|_| format!("invalid --timeout: {}", value))?;
+            timeout = Duration::from_millis(millis);
+        } else {
+            targets.push(target(&arg).ok_or_else(|| format!("invalid address: {}", arg))?);
+        }
+    }
+    if targets.is_empty() {
+        targets.push(SocketAddr::new(Ipv4Addr::BROADCAST.into(), PORT));
+    }
+    Ok((targets, timeout))
+}
+
+// An address, with the port if it's given, and the scope of an IPv6 address
+// by interface name or index
+fn target(arg: &str) -> Option<SocketAddr> {
+    let arg = match arg.split_once('%') {
+        Some((address, scope)) => {
+            let (name, rest) = scope.split_at(scope.find(']').unwrap_or(scope.len()));
+            let index = match name.parse::<u32>() {
+                Ok(index) => index,
+                Err(_) => fs::read_to_string(format!("/sys/class/net/{}/ifindex", name)).ok()?.trim().parse().ok()?,
+            };
+            format!("{}%{}{}", address, index, rest)
+        },
+        None => arg.to_string(),
+    };
+    if let Ok(target) = arg.parse::<SocketAddr>() {
+        return Some(target);
+    }
+    let bare = arg.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(address) = bare.parse::<IpAddr>() {
+        return Some(SocketAddr::new(address, PORT));
+    }
+    // An IPv6 address with a scope and no port
+    format!("[{}]:{}", bare, PORT).parse().ok()
+}
+
+fn mac(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(":")
+}
+
+fn print(board: &Board) {
+    let announcement = &board.announcement;
+    println!("{} ({}) from {}", announcement.hostname, announcement.id, board.from.ip());
+    println!("  firmware {}", announcement.firmware_version);
+    if announcement.interface_mac_address.is_empty() {
+        println!("  MAC {}", mac(&announcement.mac_address));
+    } else {
+        println!("  MAC {}, host side {}", mac(&announcement.mac_address), mac(&announcement.interface_mac_address));
+    }
+    if let Some(address) = announcement.ipv4_address() {
+        println!("  {}", address);
+    }
+    for address in announcement.ipv6_addresses() {
+        println!("  {}", address);
+    }
+    for service in &announcement.services {
+        println!("  {} on port {}", service.name, service.port);
+    }
+}
+
+fn main() {
+    let (targets, timeout) = parse().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+    match discover(&targets, timeout) {
+        Ok(boards) if boards.is_empty() => {
+            eprintln!("no boards answered");
+            process::exit(1);
+        },
+        Ok(boards) => boards.iter().for_each(print),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        },
+    }
+}
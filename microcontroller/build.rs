@@ -1,11 +1,37 @@
 
-use micropb_gen::{ Generator };
+use micropb_gen::{ Config, Generator };
 
 // Generate Rust module from .proto files
 fn proto_generate() {
     let mut gen = Generator::new();
     gen.use_container_heapless()
         .add_protoc_arg("-Iproto")
+        .configure(".FanCurve.temperature_celsius", Config::new().max_len(8))
+        .configure(".FanCurve.humidity_percent", Config::new().max_len(8))
+        .configure(".Setting.string_value", Config::new().max_bytes(32))
+        .configure(".SettingsRequest.Set.settings", Config::new().max_len(8))
+        .configure(".SettingsRequest.Remove.keys", Config::new().max_len(8))
+        .configure(".SettingsResponse.settings", Config::new().max_len(16))
+        .configure(".TempDiagnostics.statistics", Config::new().max_len(4))
+        .configure(".TempHistory.samples", Config::new().max_len(30))
+        .configure(".TempResponse.name", Config::new().max_bytes(16))
+        .configure(".ProbeResponse.probes", Config::new().max_len(8))
+        .configure(".PeripheralInfo.name", Config::new().max_bytes(16))
+        .configure(".PeripheralInfo.commands", Config::new().max_len(8))
+        .configure(".PeripheralsResponse.peripherals", Config::new().max_len(8))
+        .configure(".DeviceResponse.hostname", Config::new().max_bytes(32))
+        .configure(".DeviceResponse.id", Config::new().max_bytes(24))
+        .configure(".DeviceResponse.mac_address", Config::new().max_bytes(6))
+        .configure(".DeviceResponse.firmware_version", Config::new().max_bytes(16))
+        .configure(".ServicePort.name", Config::new().max_bytes(24))
+        .configure(".DeviceAnnouncement.hostname", Config::new().max_bytes(32))
+        .configure(".DeviceAnnouncement.id", Config::new().max_bytes(24))
+        .configure(".DeviceAnnouncement.mac_address", Config::new().max_bytes(6))
+        .configure(".DeviceAnnouncement.firmware_version", Config::new().max_bytes(16))
+        .configure(".DeviceAnnouncement.interface_mac_address", Config::new().max_bytes(6))
+        .configure(".DeviceAnnouncement.ipv6_addresses", Config::new().max_len(2))
+        .configure(".DeviceAnnouncement.ipv6_addresses.elem", Config::new().max_bytes(16))
+        .configure(".DeviceAnnouncement.services", Config::new().max_len(4))
         .compile_protos(
             &[
                 "aux.proto",
@@ -18,4 +44,4 @@ fn proto_generate() {
 
 fn main() {
     proto_generate();
-}
\ No newline at end of file
+}
@@ -1,6 +1,13 @@
 from . import aux_pb2 as aux
 from cobs import cobs
 import asyncio
+import socket
+import time
+
+RPC_PORT = 1338
+RPC_SERVICE = "_ant-aux-rpc._tcp"
+DISCOVERY_PORT = 1339
+DISCOVERY_QUERY = b"ant-aux discover"
 
 class AuxClient:
     def __init__(self, 
@@ -39,6 +46,38 @@ class AuxClient:
         from google.protobuf.json_format import MessageToJson
         return MessageToJson(message, indent=2)
 
-async def connect() -> AuxClient:
-    (reader, writer) = await asyncio.open_connection("ant-auxiliary", 1338)
+def discover(target: str = "255.255.255.255", timeout: float = 1.0) -> list[tuple[str, aux.DeviceAnnouncement]]:
+    """Sends the discovery query, and returns the address and announcement of
+    each board that replies within the timeout"""
+    boards = {}
+    with socket.socket(socket.AF_INET, socket.SOCK_DGRAM) as sock:
+        sock.setsockopt(socket.SOL_SOCKET, socket.SO_BROADCAST, 1)
+        sock.sendto(DISCOVERY_QUERY, (target, DISCOVERY_PORT))
+        deadline = time.monotonic() + timeout
+        while (remaining := deadline - time.monotonic()) > 0:
+            sock.settimeout(remaining)
+            try:
+                (data, (address, _)) = sock.recvfrom(1500)
+            except socket.timeout:
+                break
+            announcement = aux.DeviceAnnouncement()
+            try:
+                announcement.ParseFromString(data)
+            except Exception:
+                continue
+            boards.setdefault(announcement.id, (address, announcement))
+    return list(boards.values())
+
+async def connect(host: str | None = None, port: int | None = None) -> AuxClient:
+    """Connects to the board at host, like ant-auxiliary-415558.local or an
+    address. Without one, connects to the first board that answers discovery,
+    on the RPC port it announces."""
+    if host is None:
+        boards = await asyncio.get_running_loop().run_in_executor(None, discover)
+        if not boards:
+            raise ConnectionError("no board answered discovery")
+        (host, announcement) = boards[0]
+        if port is None:
+            port = next((service.port for service in announcement.services if service.name == RPC_SERVICE), None)
+    (reader, writer) = await asyncio.open_connection(host, port or RPC_PORT)
     return AuxClient(reader, writer)
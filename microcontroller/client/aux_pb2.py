@@ -24,29 +24,137 @@ _sym_db = _symbol_database.Default()
 
 
 
-DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\taux.proto\"Q\n\x07Request\x12\x1a\n\x03\x66\x61n\x18\x01 \x01(\x0b\x32\x0b.FanRequestH\x00\x12\x1c\n\x04temp\x18\x02 \x01(\x0b\x32\x0c.TempRequestH\x00\x42\x0c\n\nperipheral\"s\n\nFanRequest\x12\x1e\n\x03get\x18\x01 \x01(\x0b\x32\x0f.FanRequest.GetH\x00\x12\x1e\n\x03set\x18\x02 \x01(\x0b\x32\x0f.FanRequest.SetH\x00\x1a\x13\n\x03Set\x12\x0c\n\x04\x64uty\x18\x01 \x01(\x02\x1a\x05\n\x03GetB\t\n\x07\x63ommand\"@\n\x0bTempRequest\x12\x1f\n\x03get\x18\x01 \x01(\x0b\x32\x10.TempRequest.GetH\x00\x1a\x05\n\x03GetB\t\n\x07\x63ommand\"(\n\x0b\x46\x61nResponse\x12\x0c\n\x04\x64uty\x18\x01 \x01(\x02\x12\x0b\n\x03rpm\x18\x02 \x01(\x05\"E\n\x0cTempResponse\x12\x1b\n\x13temperature_celsius\x18\x01 \x01(\x02\x12\x18\n\x10humidity_percent\x18\x02 \x01(\x02\"T\n\x08Response\x12\x1b\n\x03\x66\x61n\x18\x01 \x01(\x0b\x32\x0c.FanResponseH\x00\x12\x1d\n\x04temp\x18\x02 \x01(\x0b\x32\r.TempResponseH\x00\x42\x0c\n\nperipheralb\x06proto3')
+DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\taux.proto\"\xc3\x02\n\x07Request\x12\x1a\n\x03\x66\x61n\x18\x01 \x01(\x0b\x32\x0b.FanRequestH\x00\x12\x1c\n\x04temp\x18\x02 \x01(\x0b\x32\x0c.TempRequestH\x00\x12\x1e\n\x05light\x18\x03 \x01(\x0b\x32\r.LightRequestH\x00\x12\x1a\n\x03pwm\x18\x04 \x01(\x0b\x32\x0b.PwmRequestH\x00\x12$\n\x08settings\x18\x05 \x01(\x0b\x32\x10.SettingsRequestH\x00\x12\x1e\n\x05probe\x18\x06 \x01(\x0b\x32\r.ProbeRequestH\x00\x12 \n\x06health\x18\x07 \x01(\x0b\x32\x0e.HealthRequestH\x00\x12*\n\x0bperipherals\x18\x08 \x01(\x0b\x32\x13.PeripheralsRequestH\x00\x12 \n\x06\x64\x65vice\x18\t \x01(\x0b\x32\x0e.DeviceRequestH\x00\x42\x0c\n\nperipheral\"\xe9\x02\n\nFanRequest\x12\x1e\n\x03get\x18\x01 \x01(\x0b\x32\x0f.FanRequest.GetH\x00\x12\x1e\n\x03set\x18\x02 \x01(\x0b\x32\x0f.FanRequest.SetH\x00\x12\x1a\n\x05\x63urve\x18\x03 \x01(\x0b\x32\t.FanCurveH\x00\x12\x35\n\x0fstall_detection\x18\x04 \x01(\x0b\x32\x1a.FanRequest.StallDetectionH\x00\x12\"\n\x05start\x18\x05 \x01(\x0b\x32\x11.FanRequest.StartH\x00\x1a\x13\n\x03Set\x12\x0c\n\x04\x64uty\x18\x01 \x01(\x02\x1a\x05\n\x03Get\x1a(\n\x0eStallDetection\x12\x16\n\x0espin_up_millis\x18\x01 \x01(\r\x1aS\n\x05Start\x12\x0e\n\x06\x63utoff\x18\x01 \x01(\x02\x12\x10\n\x08min_duty\x18\x02 \x01(\x02\x12\x13\n\x0bkick_millis\x18\x03 \x01(\r\x12\x13\n\x0bramp_millis\x18\x04 \x01(\rB\t\n\x07\x63ommand\"\xbe\x01\n\x08\x46\x61nCurve\x12,\n\x13temperature_celsius\x18\x01 \x03(\x0b\x32\x0f.FanCurve.Point\x12)\n\x10humidity_percent\x18\x02 \x03(\x0b\x32\x0f.FanCurve.Point\x12\x1a\n\x12hysteresis_celsius\x18\x03 \x01(\x02\x12\x17\n\x0f\x66\x61ilsafe_errors\x18\x04 \x01(\r\x1a$\n\x05Point\x12\r\n\x05input\x18\x01 \x01(\x02\x12\x0c\n\x04\x64uty\x18\x02 \x01(\x02\"\x94\x01\n\x0cLightRequest\x12 \n\x03get\x18\x01 \x01(\x0b\x32\x11.LightRequest.GetH\x00\x12 \n\x03set\x18\x02 \x01(\x0b\x32\x11.LightRequest.SetH\x00\x1a.\n\x03Set\x12\x0c\n\x04\x64uty\x18\x01 \x01(\x02\x12\x19\n\x11transition_millis\x18\x02 \x01(\r\x1a\x05\n\x03GetB\t\n\x07\x63ommand\"\x97\x01\n\nPwmRequest\x12\x1a\n\x06output\x18\x01 \x01(\x0e\x32\n.PwmOutput\x12\x1e\n\x03get\x18\x02 \x01(\x0b\x32\x0f.PwmRequest.GetH\x00\x12\x1e\n\x03set\x18\x03 \x01(\x0b\x32\x0f.PwmRequest.SetH\x00\x1a\x1b\n\x03Set\x12\x14\n\x0c\x66requency_hz\x18\x01 \x01(\r\x1a\x05\n\x03GetB\t\n\x07\x63ommand\"b\n\x07Setting\x12\x18\n\x03key\x18\x01 \x01(\x0e\x32\x0b.SettingKey\x12\x13\n\x0b\x66loat_value\x18\x02 \x01(\x02\x12\x12\n\nuint_value\x18\x03 \x01(\r\x12\x14\n\x0cstring_value\x18\x04 \x01(\t\"\xa8\x02\n\x0fSettingsRequest\x12#\n\x03get\x18\x01 \x01(\x0b\x32\x14.SettingsRequest.GetH\x00\x12#\n\x03set\x18\x02 \x01(\x0b\x32\x14.SettingsRequest.SetH\x00\x12)\n\x06remove\x18\x03 \x01(\x0b\x32\x17.SettingsRequest.RemoveH\x00\x12\x36\n\rfactory_reset\x18\x04 \x01(\x0b\x32\x1d.SettingsRequest.FactoryResetH\x00\x1a\x05\n\x03Get\x1a!\n\x03Set\x12\x1a\n\x08settings\x18\x01 \x03(\x0b\x32\x08.Setting\x1a#\n\x06Remove\x12\x19\n\x04keys\x18\x01 \x03(\x0e\x32\x0b.SettingKey\x1a\x0e\n\x0c\x46\x61\x63toryResetB\t\n\x07\x63ommand\"\xf0\x01\n\x0bTempRequest\x12\x1f\n\x03get\x18\x01 \x01(\x0b\x32\x10.TempRequest.GetH\x00\x12/\n\x0b\x64iagnostics\x18\x02 \x01(\x0b\x32\x18.TempRequest.DiagnosticsH\x00\x12\'\n\x07history\x18\x03 \x01(\x0b\x32\x14.TempRequest.HistoryH\x00\x1a\"\n\x03Get\x12\x0e\n\x06sensor\x18\x01 \x01(\r\x12\x0b\n\x03\x61ll\x18\x02 \x01(\x08\x1a\x1d\n\x0b\x44iagnostics\x12\x0e\n\x06sensor\x18\x01 \x01(\r\x1a\x18\n\x07History\x12\r\n\x05start\x18\x01 \x01(\rB\t\n\x07\x63ommand\"D\n\rHealthRequest\x12!\n\x03get\x18\x01 \x01(\x0b\x32\x12.HealthRequest.GetH\x00\x1a\x05\n\x03GetB\t\n\x07\x63ommand\"\x14\n\x12PeripheralsRequest\"\x0f\n\rDeviceRequest\"t\n\x0cProbeRequest\x12 \n\x03get\x18\x01 \x01(\x0b\x32\x11.ProbeRequest.GetH\x00\x12&\n\x06search\x18\x02 \x01(\x0b\x32\x14.ProbeRequest.SearchH\x00\x1a\x05\n\x03Get\x1a\x08\n\x06SearchB\t\n\x07\x63ommand\"\\\n\x0b\x46\x61nResponse\x12\x0c\n\x04\x64uty\x18\x01 \x01(\x02\x12\x0b\n\x03rpm\x18\x02 \x01(\x05\x12\x16\n\x04mode\x18\x03 \x01(\x0e\x32\x08.FanMode\x12\x1a\n\x06status\x18\x04 \x01(\x0e\x32\n.FanStatus\"4\n\x08\x46\x61nFault\x12\x1a\n\x06status\x18\x01 \x01(\x0e\x32\n.FanStatus\x12\x0c\n\x04\x64uty\x18\x02 \x01(\x02\"3\n\rLightResponse\x12\x0c\n\x04\x64uty\x18\x01 \x01(\x02\x12\x14\n\x0c\x63urrent_duty\x18\x02 \x01(\x02\"c\n\x0bPwmResponse\x12\x1a\n\x06output\x18\x01 \x01(\x0e\x32\n.PwmOutput\x12\x14\n\x0c\x66requency_hz\x18\x02 \x01(\r\x12\x12\n\nresolution\x18\x03 \x01(\r\x12\x0e\n\x06shared\x18\x04 \x01(\x08\"M\n\x10SettingsResponse\x12\x1a\n\x08settings\x18\x01 \x03(\x0b\x32\x08.Setting\x12\x1d\n\x05\x65rror\x18\x02 \x01(\x0e\x32\x0e.SettingsError\"\xc8\x01\n\x0cTempResponse\x12\x1b\n\x13temperature_celsius\x18\x01 \x01(\x02\x12\x18\n\x10humidity_percent\x18\x02 \x01(\x02\x12\x12\n\nage_millis\x18\x03 \x01(\r\x12\x1b\n\x06status\x18\x04 \x01(\x0e\x32\x0b.TempStatus\x12\x1c\n\x14\x63onsecutive_failures\x18\x05 \x01(\r\x12\x0e\n\x06sensor\x18\x06 \x01(\r\x12\x0c\n\x04name\x18\x07 \x01(\t\x12\x14\n\x0cpressure_hpa\x18\x08 \x01(\x02\"\xaa\x01\n\nTempSample\x12\x17\n\x0ftemperature_min\x18\x01 \x01(\x11\x12\x18\n\x10temperature_mean\x18\x02 \x01(\x11\x12\x17\n\x0ftemperature_max\x18\x03 \x01(\x11\x12\x14\n\x0chumidity_min\x18\x04 \x01(\x11\x12\x15\n\rhumidity_mean\x18\x05 \x01(\x11\x12\x14\n\x0chumidity_max\x18\x06 \x01(\x11\x12\r\n\x05reads\x18\x07 \x01(\r\"x\n\x0bTempHistory\x12\r\n\x05total\x18\x01 \x01(\r\x12\r\n\x05start\x18\x02 \x01(\r\x12\x18\n\x10interval_seconds\x18\x03 \x01(\r\x12\x13\n\x0b\x61ge_seconds\x18\x04 \x01(\r\x12\x1c\n\x07samples\x18\x05 \x03(\x0b\x32\x0b.TempSample\"\x98\x01\n\x10TimingStatistics\x12!\n\x08interval\x18\x01 \x01(\x0e\x32\x0f.TimingInterval\x12\r\n\x05\x63ount\x18\x02 \x01(\r\x12\x13\n\x0bmean_micros\x18\x03 \x01(\x02\x12\x12\n\nmin_micros\x18\x04 \x01(\x02\x12\x12\n\nmax_micros\x18\x05 \x01(\x02\x12\x15\n\rstddev_micros\x18\x06 \x01(\x02\"\xf8\x01\n\x0fTempDiagnostics\x12\r\n\x05reads\x18\x01 \x01(\r\x12\x17\n\x0f\x63hecksum_errors\x18\x02 \x01(\r\x12\x10\n\x08timeouts\x18\x03 \x01(\r\x12\x15\n\rtiming_errors\x18\x04 \x01(\r\x12%\n\nstatistics\x18\x05 \x03(\x0b\x32\x11.TimingStatistics\x12\'\n\x11last_timing_state\x18\x06 \x01(\x0e\x32\x0c.TimingState\x12\x18\n\x10last_timing_bits\x18\x07 \x01(\r\x12\x1a\n\x12last_timing_micros\x18\x08 \x01(\r\x12\x0e\n\x06sensor\x18\t \x01(\r\"O\n\x05Probe\x12\x0b\n\x03rom\x18\x01 \x01(\x06\x12\x1b\n\x13temperature_celsius\x18\x02 \x01(\x02\x12\x1c\n\x06status\x18\x03 \x01(\x0e\x32\x0c.ProbeStatus\"\'\n\rProbeResponse\x12\x16\n\x06probes\x18\x01 \x03(\x0b\x32\x06.Probe\"\x93\x01\n\x0eHealthResponse\x12\x1f\n\x17mcu_temperature_celsius\x18\x01 \x01(\x02\x12\x12\n\nvdda_volts\x18\x02 \x01(\x02\x12\x16\n\x0emin_vdda_volts\x18\x03 \x01(\x02\x12\x12\n\nsupply_low\x18\x04 \x01(\x08\x12 \n\x0breset_cause\x18\x05 \x01(\x0e\x32\x0b.ResetCause\"0\n\rSupplyWarning\x12\x12\n\nvdda_volts\x18\x01 \x01(\x02\x12\x0b\n\x03low\x18\x02 \x01(\x08\"A\n\x0cRequestError\x12\x1d\n\x05\x65rror\x18\x01 \x01(\x0e\x32\x0e.DispatchError\x12\x12\n\nperipheral\x18\x02 \x01(\r\"=\n\x0ePeripheralInfo\x12\x0c\n\x04name\x18\x01 \x01(\t\x12\x0b\n\x03tag\x18\x02 \x01(\r\x12\x10\n\x08\x63ommands\x18\x03 \x03(\r\";\n\x13PeripheralsResponse\x12$\n\x0bperipherals\x18\x01 \x03(\x0b\x32\x0f.PeripheralInfo\"]\n\x0e\x44\x65viceResponse\x12\x10\n\x08hostname\x18\x01 \x01(\t\x12\n\n\x02id\x18\x02 \x01(\t\x12\x13\n\x0bmac_address\x18\x03 \x01(\x0c\x12\x18\n\x10\x66irmware_version\x18\x04 \x01(\t\")\n\x0bServicePort\x12\x0c\n\x04name\x18\x01 \x01(\t\x12\x0c\n\x04port\x18\x02 \x01(\r\"\xce\x01\n\x12\x44\x65viceAnnouncement\x12\x10\n\x08hostname\x18\x01 \x01(\t\x12\n\n\x02id\x18\x02 \x01(\t\x12\x13\n\x0bmac_address\x18\x03 \x01(\x0c\x12\x18\n\x10\x66irmware_version\x18\x04 \x01(\t\x12\x1d\n\x15interface_mac_address\x18\x05 \x01(\x0c\x12\x14\n\x0cipv4_address\x18\x06 \x01(\x07\x12\x16\n\x0eipv6_addresses\x18\x07 \x03(\x0c\x12\x1e\n\x08services\x18\x08 \x03(\x0b\x32\x0c.ServicePort\"\x93\x04\n\x08Response\x12\x1b\n\x03\x66\x61n\x18\x01 \x01(\x0b\x32\x0c.FanResponseH\x00\x12\x1d\n\x04temp\x18\x02 \x01(\x0b\x32\r.TempResponseH\x00\x12\x1f\n\x05light\x18\x03 \x01(\x0b\x32\x0e.LightResponseH\x00\x12\x1e\n\tfan_fault\x18\x04 \x01(\x0b\x32\t.FanFaultH\x00\x12\x1b\n\x03pwm\x18\x05 \x01(\x0b\x32\x0c.PwmResponseH\x00\x12%\n\x08settings\x18\x06 \x01(\x0b\x32\x11.SettingsResponseH\x00\x12,\n\x10temp_diagnostics\x18\x07 \x01(\x0b\x32\x10.TempDiagnosticsH\x00\x12$\n\x0ctemp_history\x18\x08 \x01(\x0b\x32\x0c.TempHistoryH\x00\x12\x1f\n\x05probe\x18\t \x01(\x0b\x32\x0e.ProbeResponseH\x00\x12!\n\x06health\x18\n \x01(\x0b\x32\x0f.HealthResponseH\x00\x12(\n\x0esupply_warning\x18\x0b \x01(\x0b\x32\x0e.SupplyWarningH\x00\x12&\n\rrequest_error\x18\x0c \x01(\x0b\x32\r.RequestErrorH\x00\x12+\n\x0bperipherals\x18\r \x01(\x0b\x32\x14.PeripheralsResponseH\x00\x12!\n\x06\x64\x65vice\x18\x0e \x01(\x0b\x32\x0f.DeviceResponseH\x00\x42\x0c\n\nperipheral*I\n\x07\x46\x61nMode\x12\x13\n\x0f\x46\x41N_MODE_MANUAL\x10\x00\x12\x12\n\x0e\x46\x41N_MODE_CURVE\x10\x01\x12\x15\n\x11\x46\x41N_MODE_FAILSAFE\x10\x02*\x8d\x01\n\tFanStatus\x12\x12\n\x0e\x46\x41N_STATUS_OFF\x10\x00\x12\x1a\n\x16\x46\x41N_STATUS_SPINNING_UP\x10\x01\x12\x16\n\x12\x46\x41N_STATUS_RUNNING\x10\x02\x12\x16\n\x12\x46\x41N_STATUS_STALLED\x10\x03\x12 \n\x1c\x46\x41N_STATUS_TACH_DISCONNECTED\x10\x04*5\n\tPwmOutput\x12\x12\n\x0ePWM_OUTPUT_FAN\x10\x00\x12\x14\n\x10PWM_OUTPUT_LIGHT\x10\x01*\xd7\x02\n\nSettingKey\x12\x14\n\x10SETTING_KEY_NONE\x10\x00\x12\x18\n\x14SETTING_KEY_FAN_DUTY\x10\x01\x12\x1a\n\x16SETTING_KEY_LIGHT_DUTY\x10\x02\x12\x1a\n\x16SETTING_KEY_FAN_PWM_HZ\x10\x03\x12\x1c\n\x18SETTING_KEY_LIGHT_PWM_HZ\x10\x04\x12\x1c\n\x18SETTING_KEY_IPV4_ADDRESS\x10\x05\x12\x1c\n\x18SETTING_KEY_IPV4_GATEWAY\x10\x06\x12\x1b\n\x17SETTING_KEY_DHCP_SERVER\x10\x07\x12\x18\n\x14SETTING_KEY_HOSTNAME\x10\x08\x12\x19\n\x15SETTING_KEY_GRBL_PORT\x10\t\x12\x18\n\x14SETTING_KEY_RPC_PORT\x10\n\x12\x1b\n\x17SETTING_KEY_DHT_SENSORS\x10\x0b*w\n\rSettingsError\x12\x17\n\x13SETTINGS_ERROR_NONE\x10\x00\x12\x18\n\x14SETTINGS_ERROR_FLASH\x10\x01\x12\x17\n\x13SETTINGS_ERROR_FULL\x10\x02\x12\x1a\n\x16SETTINGS_ERROR_INVALID\x10\x03*\xff\x01\n\nTempStatus\x12\x12\n\x0eTEMP_STATUS_OK\x10\x00\x12\x1a\n\x16TEMP_STATUS_NEVER_READ\x10\x01\x12\x15\n\x11TEMP_STATUS_STALE\x10\x02\x12\x18\n\x14TEMP_STATUS_CHECKSUM\x10\x03\x12\x17\n\x13TEMP_STATUS_TIMEOUT\x10\x04\x12\x16\n\x12TEMP_STATUS_TIMING\x10\x05\x12\x14\n\x10TEMP_STATUS_BUSY\x10\x06\x12\x19\n\x15TEMP_STATUS_NO_SENSOR\x10\x07\x12\x13\n\x0fTEMP_STATUS_BUS\x10\x08\x12\x19\n\x15TEMP_STATUS_SUSPENDED\x10\t*V\n\x0bTimingState\x12\x18\n\x14TIMING_STATE_STANDBY\x10\x00\x12\x15\n\x11TIMING_STATE_DATA\x10\x01\x12\x16\n\x12TIMING_STATE_ERROR\x10\x02*\x80\x01\n\x0eTimingInterval\x12\x1d\n\x19TIMING_INTERVAL_INTERRUPT\x10\x00\x12\x1c\n\x18TIMING_INTERVAL_RESPONSE\x10\x01\x12\x18\n\x14TIMING_INTERVAL_ZERO\x10\x02\x12\x17\n\x13TIMING_INTERVAL_ONE\x10\x03*\x8e\x01\n\x0bProbeStatus\x12\x13\n\x0fPROBE_STATUS_OK\x10\x00\x12\x1b\n\x17PROBE_STATUS_NEVER_READ\x10\x01\x12\x14\n\x10PROBE_STATUS_CRC\x10\x02\x12\x1c\n\x18PROBE_STATUS_NO_RESPONSE\x10\x03\x12\x19\n\x15PROBE_STATUS_POWER_ON\x10\x04*\xbe\x01\n\nResetCause\x12\x17\n\x13RESET_CAUSE_UNKNOWN\x10\x00\x12\x18\n\x14RESET_CAUSE_POWER_ON\x10\x01\x12\x19\n\x15RESET_CAUSE_BROWN_OUT\x10\x02\x12\x13\n\x0fRESET_CAUSE_PIN\x10\x03\x12\x18\n\x14RESET_CAUSE_SOFTWARE\x10\x04\x12\x18\n\x14RESET_CAUSE_WATCHDOG\x10\x05\x12\x19\n\x15RESET_CAUSE_LOW_POWER\x10\x06*_\n\rDispatchError\x12\x1a\n\x16\x44ISPATCH_ERROR_UNKNOWN\x10\x00\x12\x17\n\x13\x44ISPATCH_ERROR_BUSY\x10\x01\x12\x19\n\x15\x44ISPATCH_ERROR_CLOSED\x10\x02\x62\x06proto3')
 
 _globals = globals()
 _builder.BuildMessageAndEnumDescriptors(DESCRIPTOR, _globals)
 _builder.BuildTopDescriptorsAndMessages(DESCRIPTOR, 'aux_pb2', _globals)
 if not _descriptor._USE_C_DESCRIPTORS:
   DESCRIPTOR._loaded_options = None
-  _globals['_REQUEST']._serialized_start=13
-  _globals['_REQUEST']._serialized_end=94
-  _globals['_FANREQUEST']._serialized_start=96
-  _globals['_FANREQUEST']._serialized_end=211
-  _globals['_FANREQUEST_SET']._serialized_start=174
-  _globals['_FANREQUEST_SET']._serialized_end=193
-  _globals['_FANREQUEST_GET']._serialized_start=195
-  _globals['_FANREQUEST_GET']._serialized_end=200
-  _globals['_TEMPREQUEST']._serialized_start=213
-  _globals['_TEMPREQUEST']._serialized_end=277
-  _globals['_TEMPREQUEST_GET']._serialized_start=195
-  _globals['_TEMPREQUEST_GET']._serialized_end=200
-  _globals['_FANRESPONSE']._serialized_start=279
-  _globals['_FANRESPONSE']._serialized_end=319
-  _globals['_TEMPRESPONSE']._serialized_start=321
-  _globals['_TEMPRESPONSE']._serialized_end=390
-  _globals['_RESPONSE']._serialized_start=392
-  _globals['_RESPONSE']._serialized_end=476
+  _globals['_FANMODE']._serialized_start=4749
+  _globals['_FANMODE']._serialized_end=4822
+  _globals['_FANSTATUS']._serialized_start=4825
+  _globals['_FANSTATUS']._serialized_end=4966
+  _globals['_PWMOUTPUT']._serialized_start=4968
+  _globals['_PWMOUTPUT']._serialized_end=5021
+  _globals['_SETTINGKEY']._serialized_start=5024
+  _globals['_SETTINGKEY']._serialized_end=5367
+  _globals['_SETTINGSERROR']._serialized_start=5369
+  _globals['_SETTINGSERROR']._serialized_end=5488
+  _globals['_TEMPSTATUS']._serialized_start=5491
+  _globals['_TEMPSTATUS']._serialized_end=5746
+  _globals['_TIMINGSTATE']._serialized_start=5748
+  _globals['_TIMINGSTATE']._serialized_end=5834
+  _globals['_TIMINGINTERVAL']._serialized_start=5837
+  _globals['_TIMINGINTERVAL']._serialized_end=5965
+  _globals['_PROBESTATUS']._serialized_start=5968
+  _globals['_PROBESTATUS']._serialized_end=6110
+  _globals['_RESETCAUSE']._serialized_start=6113
+  _globals['_RESETCAUSE']._serialized_end=6303
+  _globals['_DISPATCHERROR']._serialized_start=6305
+  _globals['_DISPATCHERROR']._serialized_end=6400
+  _globals['_REQUEST']._serialized_start=14
+  _globals['_REQUEST']._serialized_end=337
+  _globals['_FANREQUEST']._serialized_start=340
+  _globals['_FANREQUEST']._serialized_end=701
+  _globals['_FANREQUEST_SET']._serialized_start=537
+  _globals['_FANREQUEST_SET']._serialized_end=556
+  _globals['_FANREQUEST_GET']._serialized_start=558
+  _globals['_FANREQUEST_GET']._serialized_end=563
+  _globals['_FANREQUEST_STALLDETECTION']._serialized_start=565
+  _globals['_FANREQUEST_STALLDETECTION']._serialized_end=605
+  _globals['_FANREQUEST_START']._serialized_start=607
+  _globals['_FANREQUEST_START']._serialized_end=690
+  _globals['_FANCURVE']._serialized_start=704
+  _globals['_FANCURVE']._serialized_end=894
+  _globals['_FANCURVE_POINT']._serialized_start=858
+  _globals['_FANCURVE_POINT']._serialized_end=894
+  _globals['_LIGHTREQUEST']._serialized_start=897
+  _globals['_LIGHTREQUEST']._serialized_end=1045
+  _globals['_LIGHTREQUEST_SET']._serialized_start=981
+  _globals['_LIGHTREQUEST_SET']._serialized_end=1027
+  _globals['_LIGHTREQUEST_GET']._serialized_start=558
+  _globals['_LIGHTREQUEST_GET']._serialized_end=563
+  _globals['_PWMREQUEST']._serialized_start=1048
+  _globals['_PWMREQUEST']._serialized_end=1199
+  _globals['_PWMREQUEST_SET']._serialized_start=1154
+  _globals['_PWMREQUEST_SET']._serialized_end=1181
+  _globals['_PWMREQUEST_GET']._serialized_start=558
+  _globals['_PWMREQUEST_GET']._serialized_end=563
+  _globals['_SETTING']._serialized_start=1201
+  _globals['_SETTING']._serialized_end=1299
+  _globals['_SETTINGSREQUEST']._serialized_start=1302
+  _globals['_SETTINGSREQUEST']._serialized_end=1598
+  _globals['_SETTINGSREQUEST_GET']._serialized_start=558
+  _globals['_SETTINGSREQUEST_GET']._serialized_end=563
+  _globals['_SETTINGSREQUEST_SET']._serialized_start=1501
+  _globals['_SETTINGSREQUEST_SET']._serialized_end=1534
+  _globals['_SETTINGSREQUEST_REMOVE']._serialized_start=1536
+  _globals['_SETTINGSREQUEST_REMOVE']._serialized_end=1571
+  _globals['_SETTINGSREQUEST_FACTORYRESET']._serialized_start=1573
+  _globals['_SETTINGSREQUEST_FACTORYRESET']._serialized_end=1587
+  _globals['_TEMPREQUEST']._serialized_start=1601
+  _globals['_TEMPREQUEST']._serialized_end=1841
+  _globals['_TEMPREQUEST_GET']._serialized_start=1739
+  _globals['_TEMPREQUEST_GET']._serialized_end=1773
+  _globals['_TEMPREQUEST_DIAGNOSTICS']._serialized_start=1775
+  _globals['_TEMPREQUEST_DIAGNOSTICS']._serialized_end=1804
+  _globals['_TEMPREQUEST_HISTORY']._serialized_start=1806
+  _globals['_TEMPREQUEST_HISTORY']._serialized_end=1830
+  _globals['_HEALTHREQUEST']._serialized_start=1843
+  _globals['_HEALTHREQUEST']._serialized_end=1911
+  _globals['_HEALTHREQUEST_GET']._serialized_start=558
+  _globals['_HEALTHREQUEST_GET']._serialized_end=563
+  _globals['_PERIPHERALSREQUEST']._serialized_start=1913
+  _globals['_PERIPHERALSREQUEST']._serialized_end=1933
+  _globals['_DEVICEREQUEST']._serialized_start=1935
+  _globals['_DEVICEREQUEST']._serialized_end=1950
+  _globals['_PROBEREQUEST']._serialized_start=1952
+  _globals['_PROBEREQUEST']._serialized_end=2068
+  _globals['_PROBEREQUEST_GET']._serialized_start=558
+  _globals['_PROBEREQUEST_GET']._serialized_end=563
+  _globals['_PROBEREQUEST_SEARCH']._serialized_start=2049
+  _globals['_PROBEREQUEST_SEARCH']._serialized_end=2057
+  _globals['_FANRESPONSE']._serialized_start=2070
+  _globals['_FANRESPONSE']._serialized_end=2162
+  _globals['_FANFAULT']._serialized_start=2164
+  _globals['_FANFAULT']._serialized_end=2216
+  _globals['_LIGHTRESPONSE']._serialized_start=2218
+  _globals['_LIGHTRESPONSE']._serialized_end=2269
+  _globals['_PWMRESPONSE']._serialized_start=2271
+  _globals['_PWMRESPONSE']._serialized_end=2370
+  _globals['_SETTINGSRESPONSE']._serialized_start=2372
+  _globals['_SETTINGSRESPONSE']._serialized_end=2449
+  _globals['_TEMPRESPONSE']._serialized_start=2452
+  _globals['_TEMPRESPONSE']._serialized_end=2652
+  _globals['_TEMPSAMPLE']._serialized_start=2655
+  _globals['_TEMPSAMPLE']._serialized_end=2825
+  _globals['_TEMPHISTORY']._serialized_start=2827
+  _globals['_TEMPHISTORY']._serialized_end=2947
+  _globals['_TIMINGSTATISTICS']._serialized_start=2950
+  _globals['_TIMINGSTATISTICS']._serialized_end=3102
+  _globals['_TEMPDIAGNOSTICS']._serialized_start=3105
+  _globals['_TEMPDIAGNOSTICS']._serialized_end=3353
+  _globals['_PROBE']._serialized_start=3355
+  _globals['_PROBE']._serialized_end=3434
+  _globals['_PROBERESPONSE']._serialized_start=3436
+  _globals['_PROBERESPONSE']._serialized_end=3475
+  _globals['_HEALTHRESPONSE']._serialized_start=3478
+  _globals['_HEALTHRESPONSE']._serialized_end=3625
+  _globals['_SUPPLYWARNING']._serialized_start=3627
+  _globals['_SUPPLYWARNING']._serialized_end=3675
+  _globals['_REQUESTERROR']._serialized_start=3677
+  _globals['_REQUESTERROR']._serialized_end=3742
+  _globals['_PERIPHERALINFO']._serialized_start=3744
+  _globals['_PERIPHERALINFO']._serialized_end=3805
+  _globals['_PERIPHERALSRESPONSE']._serialized_start=3807
+  _globals['_PERIPHERALSRESPONSE']._serialized_end=3866
+  _globals['_DEVICERESPONSE']._serialized_start=3868
+  _globals['_DEVICERESPONSE']._serialized_end=3961
+  _globals['_SERVICEPORT']._serialized_start=3963
+  _globals['_SERVICEPORT']._serialized_end=4004
+  _globals['_DEVICEANNOUNCEMENT']._serialized_start=4007
+  _globals['_DEVICEANNOUNCEMENT']._serialized_end=4213
+  _globals['_RESPONSE']._serialized_start=4216
+  _globals['_RESPONSE']._serialized_end=4747
 # @@protoc_insertion_point(module_scope)
@@ -1,19 +1,184 @@
+from google.protobuf.internal import containers as _containers
+from google.protobuf.internal import enum_type_wrapper as _enum_type_wrapper
 from google.protobuf import descriptor as _descriptor
 from google.protobuf import message as _message
-from typing import ClassVar as _ClassVar, Mapping as _Mapping, Optional as _Optional, Union as _Union
+from typing import ClassVar as _ClassVar, Iterable as _Iterable, Mapping as _Mapping, Optional as _Optional, Union as _Union
 
 DESCRIPTOR: _descriptor.FileDescriptor
 
+class FanMode(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    FAN_MODE_MANUAL: _ClassVar[FanMode]
+    FAN_MODE_CURVE: _ClassVar[FanMode]
+    FAN_MODE_FAILSAFE: _ClassVar[FanMode]
+
+class FanStatus(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    FAN_STATUS_OFF: _ClassVar[FanStatus]
+    FAN_STATUS_SPINNING_UP: _ClassVar[FanStatus]
+    FAN_STATUS_RUNNING: _ClassVar[FanStatus]
+    FAN_STATUS_STALLED: _ClassVar[FanStatus]
+    FAN_STATUS_TACH_DISCONNECTED: _ClassVar[FanStatus]
+
+class PwmOutput(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    PWM_OUTPUT_FAN: _ClassVar[PwmOutput]
+    PWM_OUTPUT_LIGHT: _ClassVar[PwmOutput]
+
+class SettingKey(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    SETTING_KEY_NONE: _ClassVar[SettingKey]
+    SETTING_KEY_FAN_DUTY: _ClassVar[SettingKey]
+    SETTING_KEY_LIGHT_DUTY: _ClassVar[SettingKey]
+    SETTING_KEY_FAN_PWM_HZ: _ClassVar[SettingKey]
+    SETTING_KEY_LIGHT_PWM_HZ: _ClassVar[SettingKey]
+    SETTING_KEY_IPV4_ADDRESS: _ClassVar[SettingKey]
+    SETTING_KEY_IPV4_GATEWAY: _ClassVar[SettingKey]
+    SETTING_KEY_DHCP_SERVER: _ClassVar[SettingKey]
+    SETTING_KEY_HOSTNAME: _ClassVar[SettingKey]
+    SETTING_KEY_GRBL_PORT: _ClassVar[SettingKey]
+    SETTING_KEY_RPC_PORT: _ClassVar[SettingKey]
+    SETTING_KEY_DHT_SENSORS: _ClassVar[SettingKey]
+
+class SettingsError(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    SETTINGS_ERROR_NONE: _ClassVar[SettingsError]
+    SETTINGS_ERROR_FLASH: _ClassVar[SettingsError]
+    SETTINGS_ERROR_FULL: _ClassVar[SettingsError]
+    SETTINGS_ERROR_INVALID: _ClassVar[SettingsError]
+
+class TempStatus(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    TEMP_STATUS_OK: _ClassVar[TempStatus]
+    TEMP_STATUS_NEVER_READ: _ClassVar[TempStatus]
+    TEMP_STATUS_STALE: _ClassVar[TempStatus]
+    TEMP_STATUS_CHECKSUM: _ClassVar[TempStatus]
+    TEMP_STATUS_TIMEOUT: _ClassVar[TempStatus]
+    TEMP_STATUS_TIMING: _ClassVar[TempStatus]
+    TEMP_STATUS_BUSY: _ClassVar[TempStatus]
+    TEMP_STATUS_NO_SENSOR: _ClassVar[TempStatus]
+    TEMP_STATUS_BUS: _ClassVar[TempStatus]
+    TEMP_STATUS_SUSPENDED: _ClassVar[TempStatus]
+
+class TimingState(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    TIMING_STATE_STANDBY: _ClassVar[TimingState]
+    TIMING_STATE_DATA: _ClassVar[TimingState]
+    TIMING_STATE_ERROR: _ClassVar[TimingState]
+
+class TimingInterval(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    TIMING_INTERVAL_INTERRUPT: _ClassVar[TimingInterval]
+    TIMING_INTERVAL_RESPONSE: _ClassVar[TimingInterval]
+    TIMING_INTERVAL_ZERO: _ClassVar[TimingInterval]
+    TIMING_INTERVAL_ONE: _ClassVar[TimingInterval]
+
+class ProbeStatus(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    PROBE_STATUS_OK: _ClassVar[ProbeStatus]
+    PROBE_STATUS_NEVER_READ: _ClassVar[ProbeStatus]
+    PROBE_STATUS_CRC: _ClassVar[ProbeStatus]
+    PROBE_STATUS_NO_RESPONSE: _ClassVar[ProbeStatus]
+    PROBE_STATUS_POWER_ON: _ClassVar[ProbeStatus]
+
+class ResetCause(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    RESET_CAUSE_UNKNOWN: _ClassVar[ResetCause]
+    RESET_CAUSE_POWER_ON: _ClassVar[ResetCause]
+    RESET_CAUSE_BROWN_OUT: _ClassVar[ResetCause]
+    RESET_CAUSE_PIN: _ClassVar[ResetCause]
+    RESET_CAUSE_SOFTWARE: _ClassVar[ResetCause]
+    RESET_CAUSE_WATCHDOG: _ClassVar[ResetCause]
+    RESET_CAUSE_LOW_POWER: _ClassVar[ResetCause]
+
+class DispatchError(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = ()
+    DISPATCH_ERROR_UNKNOWN: _ClassVar[DispatchError]
+    DISPATCH_ERROR_BUSY: _ClassVar[DispatchError]
+    DISPATCH_ERROR_CLOSED: _ClassVar[DispatchError]
+FAN_MODE_MANUAL: FanMode
+FAN_MODE_CURVE: FanMode
+FAN_MODE_FAILSAFE: FanMode
+FAN_STATUS_OFF: FanStatus
+FAN_STATUS_SPINNING_UP: FanStatus
+FAN_STATUS_RUNNING: FanStatus
+FAN_STATUS_STALLED: FanStatus
+FAN_STATUS_TACH_DISCONNECTED: FanStatus
+PWM_OUTPUT_FAN: PwmOutput
+PWM_OUTPUT_LIGHT: PwmOutput
+SETTING_KEY_NONE: SettingKey
+SETTING_KEY_FAN_DUTY: SettingKey
+SETTING_KEY_LIGHT_DUTY: SettingKey
+SETTING_KEY_FAN_PWM_HZ: SettingKey
+SETTING_KEY_LIGHT_PWM_HZ: SettingKey
+SETTING_KEY_IPV4_ADDRESS: SettingKey
+SETTING_KEY_IPV4_GATEWAY: SettingKey
+SETTING_KEY_DHCP_SERVER: SettingKey
+SETTING_KEY_HOSTNAME: SettingKey
+SETTING_KEY_GRBL_PORT: SettingKey
+SETTING_KEY_RPC_PORT: SettingKey
+SETTING_KEY_DHT_SENSORS: SettingKey
+SETTINGS_ERROR_NONE: SettingsError
+SETTINGS_ERROR_FLASH: SettingsError
+SETTINGS_ERROR_FULL: SettingsError
+SETTINGS_ERROR_INVALID: SettingsError
+TEMP_STATUS_OK: TempStatus
+TEMP_STATUS_NEVER_READ: TempStatus
+TEMP_STATUS_STALE: TempStatus
+TEMP_STATUS_CHECKSUM: TempStatus
+TEMP_STATUS_TIMEOUT: TempStatus
+TEMP_STATUS_TIMING: TempStatus
+TEMP_STATUS_BUSY: TempStatus
+TEMP_STATUS_NO_SENSOR: TempStatus
+TEMP_STATUS_BUS: TempStatus
+TEMP_STATUS_SUSPENDED: TempStatus
+TIMING_STATE_STANDBY: TimingState
+TIMING_STATE_DATA: TimingState
+TIMING_STATE_ERROR: TimingState
+TIMING_INTERVAL_INTERRUPT: TimingInterval
+TIMING_INTERVAL_RESPONSE: TimingInterval
+TIMING_INTERVAL_ZERO: TimingInterval
+TIMING_INTERVAL_ONE: TimingInterval
+PROBE_STATUS_OK: ProbeStatus
+PROBE_STATUS_NEVER_READ: ProbeStatus
+PROBE_STATUS_CRC: ProbeStatus
+PROBE_STATUS_NO_RESPONSE: ProbeStatus
+PROBE_STATUS_POWER_ON: ProbeStatus
+RESET_CAUSE_UNKNOWN: ResetCause
+RESET_CAUSE_POWER_ON: ResetCause
+RESET_CAUSE_BROWN_OUT: ResetCause
+RESET_CAUSE_PIN: ResetCause
+RESET_CAUSE_SOFTWARE: ResetCause
+RESET_CAUSE_WATCHDOG: ResetCause
+RESET_CAUSE_LOW_POWER: ResetCause
+DISPATCH_ERROR_UNKNOWN: DispatchError
+DISPATCH_ERROR_BUSY: DispatchError
+DISPATCH_ERROR_CLOSED: DispatchError
+
 class Request(_message.Message):
-    __slots__ = ("fan", "temp")
+    __slots__ = ("fan", "temp", "light", "pwm", "settings", "probe", "health", "peripherals", "device")
     FAN_FIELD_NUMBER: _ClassVar[int]
     TEMP_FIELD_NUMBER: _ClassVar[int]
+    LIGHT_FIELD_NUMBER: _ClassVar[int]
+    PWM_FIELD_NUMBER: _ClassVar[int]
+    SETTINGS_FIELD_NUMBER: _ClassVar[int]
+    PROBE_FIELD_NUMBER: _ClassVar[int]
+    HEALTH_FIELD_NUMBER: _ClassVar[int]
+    PERIPHERALS_FIELD_NUMBER: _ClassVar[int]
+    DEVICE_FIELD_NUMBER: _ClassVar[int]
     fan: FanRequest
     temp: TempRequest
-    def __init__(self, fan: _Optional[_Union[FanRequest, _Mapping]] = ..., temp: _Optional[_Union[TempRequest, _Mapping]] = ...) -> None: ...
+    light: LightRequest
+    pwm: PwmRequest
+    settings: SettingsRequest
+    probe: ProbeRequest
+    health: HealthRequest
+    peripherals: PeripheralsRequest
+    device: DeviceRequest
+    def __init__(self, fan: _Optional[_Union[FanRequest, _Mapping]] = ..., temp: _Optional[_Union[TempRequest, _Mapping]] = ..., light: _Optional[_Union[LightRequest, _Mapping]] = ..., pwm: _Optional[_Union[PwmRequest, _Mapping]] = ..., settings: _Optional[_Union[SettingsRequest, _Mapping]] = ..., probe: _Optional[_Union[ProbeRequest, _Mapping]] = ..., health: _Optional[_Union[HealthRequest, _Mapping]] = ..., peripherals: _Optional[_Union[PeripheralsRequest, _Mapping]] = ..., device: _Optional[_Union[DeviceRequest, _Mapping]] = ...) -> None: ...
 
 class FanRequest(_message.Message):
-    __slots__ = ("get", "set")
+    __slots__ = ("get", "set", "curve", "stall_detection", "start")
     class Set(_message.Message):
         __slots__ = ("duty",)
         DUTY_FIELD_NUMBER: _ClassVar[int]
@@ -22,41 +187,455 @@ class FanRequest(_message.Message):
     class Get(_message.Message):
         __slots__ = ()
         def __init__(self) -> None: ...
+    class StallDetection(_message.Message):
+        __slots__ = ("spin_up_millis",)
+        SPIN_UP_MILLIS_FIELD_NUMBER: _ClassVar[int]
+        spin_up_millis: int
+        def __init__(self, spin_up_millis: _Optional[int] = ...) -> None: ...
+    class Start(_message.Message):
+        __slots__ = ("cutoff", "min_duty", "kick_millis", "ramp_millis")
+        CUTOFF_FIELD_NUMBER: _ClassVar[int]
+        MIN_DUTY_FIELD_NUMBER: _ClassVar[int]
+        KICK_MILLIS_FIELD_NUMBER: _ClassVar[int]
+        RAMP_MILLIS_FIELD_NUMBER: _ClassVar[int]
+        cutoff: float
+        min_duty: float
+        kick_millis: int
+        ramp_millis: int
+        def __init__(self, cutoff: _Optional[float] = ..., min_duty: _Optional[float] = ..., kick_millis: _Optional[int] = ..., ramp_millis: _Optional[int] = ...) -> None: ...
     GET_FIELD_NUMBER: _ClassVar[int]
     SET_FIELD_NUMBER: _ClassVar[int]
+    CURVE_FIELD_NUMBER: _ClassVar[int]
+    STALL_DETECTION_FIELD_NUMBER: _ClassVar[int]
+    START_FIELD_NUMBER: _ClassVar[int]
     get: FanRequest.Get
     set: FanRequest.Set
-    def __init__(self, get: _Optional[_Union[FanRequest.Get, _Mapping]] = ..., set: _Optional[_Union[FanRequest.Set, _Mapping]] = ...) -> None: ...
+    curve: FanCurve
+    stall_detection: FanRequest.StallDetection
+    start: FanRequest.Start
+    def __init__(self, get: _Optional[_Union[FanRequest.Get, _Mapping]] = ..., set: _Optional[_Union[FanRequest.Set, _Mapping]] = ..., curve: _Optional[_Union[FanCurve, _Mapping]] = ..., stall_detection: _Optional[_Union[FanRequest.StallDetection, _Mapping]] = ..., start: _Optional[_Union[FanRequest.Start, _Mapping]] = ...) -> None: ...
+
+class FanCurve(_message.Message):
+    __slots__ = ("temperature_celsius", "humidity_percent", "hysteresis_celsius", "failsafe_errors")
+    class Point(_message.Message):
+        __slots__ = ("input", "duty")
+        INPUT_FIELD_NUMBER: _ClassVar[int]
+        DUTY_FIELD_NUMBER: _ClassVar[int]
+        input: float
+        duty: float
+        def __init__(self, input: _Optional[float] = ..., duty: _Optional[float] = ...) -> None: ...
+    TEMPERATURE_CELSIUS_FIELD_NUMBER: _ClassVar[int]
+    HUMIDITY_PERCENT_FIELD_NUMBER: _ClassVar[int]
+    HYSTERESIS_CELSIUS_FIELD_NUMBER: _ClassVar[int]
+    FAILSAFE_ERRORS_FIELD_NUMBER: _ClassVar[int]
+    temperature_celsius: _containers.RepeatedCompositeFieldContainer[FanCurve.Point]
+    humidity_percent: _containers.RepeatedCompositeFieldContainer[FanCurve.Point]
+    hysteresis_celsius: float
+    failsafe_errors: int
+    def __init__(self, temperature_celsius: _Optional[_Iterable[_Union[FanCurve.Point, _Mapping]]] = ..., humidity_percent: _Optional[_Iterable[_Union[FanCurve.Point, _Mapping]]] = ..., hysteresis_celsius: _Optional[float] = ..., failsafe_errors: _Optional[int] = ...) -> None: ...
+
+class LightRequest(_message.Message):
+    __slots__ = ("get", "set")
+    class Set(_message.Message):
+        __slots__ = ("duty", "transition_millis")
+        DUTY_FIELD_NUMBER: _ClassVar[int]
+        TRANSITION_MILLIS_FIELD_NUMBER: _ClassVar[int]
+        duty: float
+        transition_millis: int
+        def __init__(self, duty: _Optional[float] = ..., transition_millis: _Optional[int] = ...) -> None: ...
+    class Get(_message.Message):
+        __slots__ = ()
+        def __init__(self) -> None: ...
+    GET_FIELD_NUMBER: _ClassVar[int]
+    SET_FIELD_NUMBER: _ClassVar[int]
+    get: LightRequest.Get
+    set: LightRequest.Set
+    def __init__(self, get: _Optional[_Union[LightRequest.Get, _Mapping]] = ..., set: _Optional[_Union[LightRequest.Set, _Mapping]] = ...) -> None: ...
+
+class PwmRequest(_message.Message):
+    __slots__ = ("output", "get", "set")
+    class Set(_message.Message):
+        __slots__ = ("frequency_hz",)
+        FREQUENCY_HZ_FIELD_NUMBER: _ClassVar[int]
+        frequency_hz: int
+        def __init__(self, frequency_hz: _Optional[int] = ...) -> None: ...
+    class Get(_message.Message):
+        __slots__ = ()
+        def __init__(self) -> None: ...
+    OUTPUT_FIELD_NUMBER: _ClassVar[int]
+    GET_FIELD_NUMBER: _ClassVar[int]
+    SET_FIELD_NUMBER: _ClassVar[int]
+    output: PwmOutput
+    get: PwmRequest.Get
+    set: PwmRequest.Set
+    def __init__(self, output: _Optional[_Union[PwmOutput, str]] = ..., get: _Optional[_Union[PwmRequest.Get, _Mapping]] = ..., set: _Optional[_Union[PwmRequest.Set, _Mapping]] = ...) -> None: ...
+
+class Setting(_message.Message):
+    __slots__ = ("key", "float_value", "uint_value", "string_value")
+    KEY_FIELD_NUMBER: _ClassVar[int]
+    FLOAT_VALUE_FIELD_NUMBER: _ClassVar[int]
+    UINT_VALUE_FIELD_NUMBER: _ClassVar[int]
+    STRING_VALUE_FIELD_NUMBER: _ClassVar[int]
+    key: SettingKey
+    float_value: float
+    uint_value: int
+    string_value: str
+    def __init__(self, key: _Optional[_Union[SettingKey, str]] = ..., float_value: _Optional[float] = ..., uint_value: _Optional[int] = ..., string_value: _Optional[str] = ...) -> None: ...
+
+class SettingsRequest(_message.Message):
+    __slots__ = ("get", "set", "remove", "factory_reset")
+    class Get(_message.Message):
+        __slots__ = ()
+        def __init__(self) -> None: ...
+    class Set(_message.Message):
+        __slots__ = ("settings",)
+        SETTINGS_FIELD_NUMBER: _ClassVar[int]
+        settings: _containers.RepeatedCompositeFieldContainer[Setting]
+        def __init__(self, settings: _Optional[_Iterable[_Union[Setting, _Mapping]]] = ...) -> None: ...
+    class Remove(_message.Message):
+        __slots__ = ("keys",)
+        KEYS_FIELD_NUMBER: _ClassVar[int]
+        keys: _containers.RepeatedScalarFieldContainer[SettingKey]
+        def __init__(self, keys: _Optional[_Iterable[_Union[SettingKey, str]]] = ...) -> None: ...
+    class FactoryReset(_message.Message):
+        __slots__ = ()
+        def __init__(self) -> None: ...
+    GET_FIELD_NUMBER: _ClassVar[int]
+    SET_FIELD_NUMBER: _ClassVar[int]
+    REMOVE_FIELD_NUMBER: _ClassVar[int]
+    FACTORY_RESET_FIELD_NUMBER: _ClassVar[int]
+    get: SettingsRequest.Get
+    set: SettingsRequest.Set
+    remove: SettingsRequest.Remove
+    factory_reset: SettingsRequest.FactoryReset
+    def __init__(self, get: _Optional[_Union[SettingsRequest.Get, _Mapping]] = ..., set: _Optional[_Union[SettingsRequest.Set, _Mapping]] = ..., remove: _Optional[_Union[SettingsRequest.Remove, _Mapping]] = ..., factory_reset: _Optional[_Union[SettingsRequest.FactoryReset, _Mapping]] = ...) -> None: ...
 
 class TempRequest(_message.Message):
+    __slots__ = ("get", "diagnostics", "history")
+    class Get(_message.Message):
+        __slots__ = ("sensor", "all")
+        SENSOR_FIELD_NUMBER: _ClassVar[int]
+        ALL_FIELD_NUMBER: _ClassVar[int]
+        sensor: int
+        all: bool
+        def __init__(self, sensor: _Optional[int] = ..., all: bool = ...) -> None: ...
+    class Diagnostics(_message.Message):
+        __slots__ = ("sensor",)
+        SENSOR_FIELD_NUMBER: _ClassVar[int]
+        sensor: int
+        def __init__(self, sensor: _Optional[int] = ...) -> None: ...
+    class History(_message.Message):
+        __slots__ = ("start",)
+        START_FIELD_NUMBER: _ClassVar[int]
+        start: int
+        def __init__(self, start: _Optional[int] = ...) -> None: ...
+    GET_FIELD_NUMBER: _ClassVar[int]
+    DIAGNOSTICS_FIELD_NUMBER: _ClassVar[int]
+    HISTORY_FIELD_NUMBER: _ClassVar[int]
+    get: TempRequest.Get
+    diagnostics: TempRequest.Diagnostics
+    history: TempRequest.History
+    def __init__(self, get: _Optional[_Union[TempRequest.Get, _Mapping]] = ..., diagnostics: _Optional[_Union[TempRequest.Diagnostics, _Mapping]] = ..., history: _Optional[_Union[TempRequest.History, _Mapping]] = ...) -> None: ...
+
+class HealthRequest(_message.Message):
     __slots__ = ("get",)
     class Get(_message.Message):
         __slots__ = ()
         def __init__(self) -> None: ...
     GET_FIELD_NUMBER: _ClassVar[int]
-    get: TempRequest.Get
-    def __init__(self, get: _Optional[_Union[TempRequest.Get, _Mapping]] = ...) -> None: ...
+    get: HealthRequest.Get
+    def __init__(self, get: _Optional[_Union[HealthRequest.Get, _Mapping]] = ...) -> None: ...
+
+class PeripheralsRequest(_message.Message):
+    __slots__ = ()
+    def __init__(self) -> None: ...
+
+class DeviceRequest(_message.Message):
+    __slots__ = ()
+    def __init__(self) -> None: ...
+
+class ProbeRequest(_message.Message):
+    __slots__ = ("get", "search")
+    class Get(_message.Message):
+        __slots__ = ()
+        def __init__(self) -> None: ...
+    class Search(_message.Message):
+        __slots__ = ()
+        def __init__(self) -> None: ...
+    GET_FIELD_NUMBER: _ClassVar[int]
+    SEARCH_FIELD_NUMBER: _ClassVar[int]
+    get: ProbeRequest.Get
+    search: ProbeRequest.Search
+    def __init__(self, get: _Optional[_Union[ProbeRequest.Get, _Mapping]] = ..., search: _Optional[_Union[ProbeRequest.Search, _Mapping]] = ...) -> None: ...
 
 class FanResponse(_message.Message):
-    __slots__ = ("duty", "rpm")
+    __slots__ = ("duty", "rpm", "mode", "status")
     DUTY_FIELD_NUMBER: _ClassVar[int]
     RPM_FIELD_NUMBER: _ClassVar[int]
+    MODE_FIELD_NUMBER: _ClassVar[int]
+    STATUS_FIELD_NUMBER: _ClassVar[int]
     duty: float
     rpm: int
-    def __init__(self, duty: _Optional[float] = ..., rpm: _Optional[int] = ...) -> None: ...
+    mode: FanMode
+    status: FanStatus
+    def __init__(self, duty: _Optional[float] = ..., rpm: _Optional[int] = ..., mode: _Optional[_Union[FanMode, str]] = ..., status: _Optional[_Union[FanStatus, str]] = ...) -> None: ...
+
+class FanFault(_message.Message):
+    __slots__ = ("status", "duty")
+    STATUS_FIELD_NUMBER: _ClassVar[int]
+    DUTY_FIELD_NUMBER: _ClassVar[int]
+    status: FanStatus
+    duty: float
+    def __init__(self, status: _Optional[_Union[FanStatus, str]] = ..., duty: _Optional[float] = ...) -> None: ...
+
+class LightResponse(_message.Message):
+    __slots__ = ("duty", "current_duty")
+    DUTY_FIELD_NUMBER: _ClassVar[int]
+    CURRENT_DUTY_FIELD_NUMBER: _ClassVar[int]
+    duty: float
+    current_duty: float
+    def __init__(self, duty: _Optional[float] = ..., current_duty: _Optional[float] = ...) -> None: ...
+
+class PwmResponse(_message.Message):
+    __slots__ = ("output", "frequency_hz", "resolution", "shared")
+    OUTPUT_FIELD_NUMBER: _ClassVar[int]
+    FREQUENCY_HZ_FIELD_NUMBER: _ClassVar[int]
+    RESOLUTION_FIELD_NUMBER: _ClassVar[int]
+    SHARED_FIELD_NUMBER: _ClassVar[int]
+    output: PwmOutput
+    frequency_hz: int
+    resolution: int
+    shared: bool
+    def __init__(self, output: _Optional[_Union[PwmOutput, str]] = ..., frequency_hz: _Optional[int] = ..., resolution: _Optional[int] = ..., shared: bool = ...) -> None: ...
+
+class SettingsResponse(_message.Message):
+    __slots__ = ("settings", "error")
+    SETTINGS_FIELD_NUMBER: _ClassVar[int]
+    ERROR_FIELD_NUMBER: _ClassVar[int]
+    settings: _containers.RepeatedCompositeFieldContainer[Setting]
+    error: SettingsError
+    def __init__(self, settings: _Optional[_Iterable[_Union[Setting, _Mapping]]] = ..., error: _Optional[_Union[SettingsError, str]] = ...) -> None: ...
 
 class TempResponse(_message.Message):
-    __slots__ = ("temperature_celsius", "humidity_percent")
+    __slots__ = ("temperature_celsius", "humidity_percent", "age_millis", "status", "consecutive_failures", "sensor", "name", "pressure_hpa")
     TEMPERATURE_CELSIUS_FIELD_NUMBER: _ClassVar[int]
     HUMIDITY_PERCENT_FIELD_NUMBER: _ClassVar[int]
+    AGE_MILLIS_FIELD_NUMBER: _ClassVar[int]
+    STATUS_FIELD_NUMBER: _ClassVar[int]
+    CONSECUTIVE_FAILURES_FIELD_NUMBER: _ClassVar[int]
+    SENSOR_FIELD_NUMBER: _ClassVar[int]
+    NAME_FIELD_NUMBER: _ClassVar[int]
+    PRESSURE_HPA_FIELD_NUMBER: _ClassVar[int]
     temperature_celsius: float
     humidity_percent: float
-    def __init__(self, temperature_celsius: _Optional[float] = ..., humidity_percent: _Optional[float] = ...) -> None: ...
+    age_millis: int
+    status: TempStatus
+    consecutive_failures: int
+    sensor: int
+    name: str
+    pressure_hpa: float
+    def __init__(self, temperature_celsius: _Optional[float] = ..., humidity_percent: _Optional[float] = ..., age_millis: _Optional[int] = ..., status: _Optional[_Union[TempStatus, str]] = ..., consecutive_failures: _Optional[int] = ..., sensor: _Optional[int] = ..., name: _Optional[str] = ..., pressure_hpa: _Optional[float] = ...) -> None: ...
+
+class TempSample(_message.Message):
+    __slots__ = ("temperature_min", "temperature_mean", "temperature_max", "humidity_min", "humidity_mean", "humidity_max", "reads")
+    TEMPERATURE_MIN_FIELD_NUMBER: _ClassVar[int]
+    TEMPERATURE_MEAN_FIELD_NUMBER: _ClassVar[int]
+    TEMPERATURE_MAX_FIELD_NUMBER: _ClassVar[int]
+    HUMIDITY_MIN_FIELD_NUMBER: _ClassVar[int]
+    HUMIDITY_MEAN_FIELD_NUMBER: _ClassVar[int]
+    HUMIDITY_MAX_FIELD_NUMBER: _ClassVar[int]
+    READS_FIELD_NUMBER: _ClassVar[int]
+    temperature_min: int
+    temperature_mean: int
+    temperature_max: int
+    humidity_min: int
+    humidity_mean: int
+    humidity_max: int
+    reads: int
+    def __init__(self, temperature_min: _Optional[int] = ..., temperature_mean: _Optional[int] = ..., temperature_max: _Optional[int] = ..., humidity_min: _Optional[int] = ..., humidity_mean: _Optional[int] = ..., humidity_max: _Optional[int] = ..., reads: _Optional[int] = ...) -> None: ...
+
+class TempHistory(_message.Message):
+    __slots__ = ("total", "start", "interval_seconds", "age_seconds", "samples")
+    TOTAL_FIELD_NUMBER: _ClassVar[int]
+    START_FIELD_NUMBER: _ClassVar[int]
+    INTERVAL_SECONDS_FIELD_NUMBER: _ClassVar[int]
+    AGE_SECONDS_FIELD_NUMBER: _ClassVar[int]
+    SAMPLES_FIELD_NUMBER: _ClassVar[int]
+    total: int
+    start: int
+    interval_seconds: int
+    age_seconds: int
+    samples: _containers.RepeatedCompositeFieldContainer[TempSample]
+    def __init__(self, total: _Optional[int] = ..., start: _Optional[int] = ..., interval_seconds: _Optional[int] = ..., age_seconds: _Optional[int] = ..., samples: _Optional[_Iterable[_Union[TempSample, _Mapping]]] = ...) -> None: ...
+
+class TimingStatistics(_message.Message):
+    __slots__ = ("interval", "count", "mean_micros", "min_micros", "max_micros", "stddev_micros")
+    INTERVAL_FIELD_NUMBER: _ClassVar[int]
+    COUNT_FIELD_NUMBER: _ClassVar[int]
+    MEAN_MICROS_FIELD_NUMBER: _ClassVar[int]
+    MIN_MICROS_FIELD_NUMBER: _ClassVar[int]
+    MAX_MICROS_FIELD_NUMBER: _ClassVar[int]
+    STDDEV_MICROS_FIELD_NUMBER: _ClassVar[int]
+    interval: TimingInterval
+    count: int
+    mean_micros: float
+    min_micros: float
+    max_micros: float
+    stddev_micros: float
+    def __init__(self, interval: _Optional[_Union[TimingInterval, str]] = ..., count: _Optional[int] = ..., mean_micros: _Optional[float] = ..., min_micros: _Optional[float] = ..., max_micros: _Optional[float] = ..., stddev_micros: _Optional[float] = ...) -> None: ...
+
+class TempDiagnostics(_message.Message):
+    __slots__ = ("reads", "checksum_errors", "timeouts", "timing_errors", "statistics", "last_timing_state", "last_timing_bits", "last_timing_micros", "sensor")
+    READS_FIELD_NUMBER: _ClassVar[int]
+    CHECKSUM_ERRORS_FIELD_NUMBER: _ClassVar[int]
+    TIMEOUTS_FIELD_NUMBER: _ClassVar[int]
+    TIMING_ERRORS_FIELD_NUMBER: _ClassVar[int]
+    STATISTICS_FIELD_NUMBER: _ClassVar[int]
+    LAST_TIMING_STATE_FIELD_NUMBER: _ClassVar[int]
+    LAST_TIMING_BITS_FIELD_NUMBER: _ClassVar[int]
+    LAST_TIMING_MICROS_FIELD_NUMBER: _ClassVar[int]
+    SENSOR_FIELD_NUMBER: _ClassVar[int]
+    reads: int
+    checksum_errors: int
+    timeouts: int
+    timing_errors: int
+    statistics: _containers.RepeatedCompositeFieldContainer[TimingStatistics]
+    last_timing_state: TimingState
+    last_timing_bits: int
+    last_timing_micros: int
+    sensor: int
+    def __init__(self, reads: _Optional[int] = ..., checksum_errors: _Optional[int] = ..., timeouts: _Optional[int] = ..., timing_errors: _Optional[int] = ..., statistics: _Optional[_Iterable[_Union[TimingStatistics, _Mapping]]] = ..., last_timing_state: _Optional[_Union[TimingState, str]] = ..., last_timing_bits: _Optional[int] = ..., last_timing_micros: _Optional[int] = ..., sensor: _Optional[int] = ...) -> None: ...
+
+class Probe(_message.Message):
+    __slots__ = ("rom", "temperature_celsius", "status")
+    ROM_FIELD_NUMBER: _ClassVar[int]
+    TEMPERATURE_CELSIUS_FIELD_NUMBER: _ClassVar[int]
+    STATUS_FIELD_NUMBER: _ClassVar[int]
+    rom: int
+    temperature_celsius: float
+    status: ProbeStatus
+    def __init__(self, rom: _Optional[int] = ..., temperature_celsius: _Optional[float] = ..., status: _Optional[_Union[ProbeStatus, str]] = ...) -> None: ...
+
+class ProbeResponse(_message.Message):
+    __slots__ = ("probes",)
+    PROBES_FIELD_NUMBER: _ClassVar[int]
+    probes: _containers.RepeatedCompositeFieldContainer[Probe]
+    def __init__(self, probes: _Optional[_Iterable[_Union[Probe, _Mapping]]] = ...) -> None: ...
+
+class HealthResponse(_message.Message):
+    __slots__ = ("mcu_temperature_celsius", "vdda_volts", "min_vdda_volts", "supply_low", "reset_cause")
+    MCU_TEMPERATURE_CELSIUS_FIELD_NUMBER: _ClassVar[int]
+    VDDA_VOLTS_FIELD_NUMBER: _ClassVar[int]
+    MIN_VDDA_VOLTS_FIELD_NUMBER: _ClassVar[int]
+    SUPPLY_LOW_FIELD_NUMBER: _ClassVar[int]
+    RESET_CAUSE_FIELD_NUMBER: _ClassVar[int]
+    mcu_temperature_celsius: float
+    vdda_volts: float
+    min_vdda_volts: float
+    supply_low: bool
+    reset_cause: ResetCause
+    def __init__(self, mcu_temperature_celsius: _Optional[float] = ..., vdda_volts: _Optional[float] = ..., min_vdda_volts: _Optional[float] = ..., supply_low: bool = ..., reset_cause: _Optional[_Union[ResetCause, str]] = ...) -> None: ...
+
+class SupplyWarning(_message.Message):
+    __slots__ = ("vdda_volts", "low")
+    VDDA_VOLTS_FIELD_NUMBER: _ClassVar[int]
+    LOW_FIELD_NUMBER: _ClassVar[int]
+    vdda_volts: float
+    low: bool
+    def __init__(self, vdda_volts: _Optional[float] = ..., low: bool = ...) -> None: ...
+
+class RequestError(_message.Message):
+    __slots__ = ("error", "peripheral")
+    ERROR_FIELD_NUMBER: _ClassVar[int]
+    PERIPHERAL_FIELD_NUMBER: _ClassVar[int]
+    error: DispatchError
+    peripheral: int
+    def __init__(self, error: _Optional[_Union[DispatchError, str]] = ..., peripheral: _Optional[int] = ...) -> None: ...
+
+class PeripheralInfo(_message.Message):
+    __slots__ = ("name", "tag", "commands")
+    NAME_FIELD_NUMBER: _ClassVar[int]
+    TAG_FIELD_NUMBER: _ClassVar[int]
+    COMMANDS_FIELD_NUMBER: _ClassVar[int]
+    name: str
+    tag: int
+    commands: _containers.RepeatedScalarFieldContainer[int]
+    def __init__(self, name: _Optional[str] = ..., tag: _Optional[int] = ..., commands: _Optional[_Iterable[int]] = ...) -> None: ...
+
+class PeripheralsResponse(_message.Message):
+    __slots__ = ("peripherals",)
+    PERIPHERALS_FIELD_NUMBER: _ClassVar[int]
+    peripherals: _containers.RepeatedCompositeFieldContainer[PeripheralInfo]
+    def __init__(self, peripherals: _Optional[_Iterable[_Union[PeripheralInfo, _Mapping]]] = ...) -> None: ...
+
+class DeviceResponse(_message.Message):
+    __slots__ = ("hostname", "id", "mac_address", "firmware_version")
+    HOSTNAME_FIELD_NUMBER: _ClassVar[int]
+    ID_FIELD_NUMBER: _ClassVar[int]
+    MAC_ADDRESS_FIELD_NUMBER: _ClassVar[int]
+    FIRMWARE_VERSION_FIELD_NUMBER: _ClassVar[int]
+    hostname: str
+    id: str
+    mac_address: bytes
+    firmware_version: str
+    def __init__(self, hostname: _Optional[str] = ..., id: _Optional[str] = ..., mac_address: _Optional[bytes] = ..., firmware_version: _Optional[str] = ...) -> None: ...
+
+class ServicePort(_message.Message):
+    __slots__ = ("name", "port")
+    NAME_FIELD_NUMBER: _ClassVar[int]
+    PORT_FIELD_NUMBER: _ClassVar[int]
+    name: str
+    port: int
+    def __init__(self, name: _Optional[str] = ..., port: _Optional[int] = ...) -> None: ...
+
+class DeviceAnnouncement(_message.Message):
+    __slots__ = ("hostname", "id", "mac_address", "firmware_version", "interface_mac_address", "ipv4_address", "ipv6_addresses", "services")
+    HOSTNAME_FIELD_NUMBER: _ClassVar[int]
+    ID_FIELD_NUMBER: _ClassVar[int]
+    MAC_ADDRESS_FIELD_NUMBER: _ClassVar[int]
+    FIRMWARE_VERSION_FIELD_NUMBER: _ClassVar[int]
+    INTERFACE_MAC_ADDRESS_FIELD_NUMBER: _ClassVar[int]
+    IPV4_ADDRESS_FIELD_NUMBER: _ClassVar[int]
+    IPV6_ADDRESSES_FIELD_NUMBER: _ClassVar[int]
+    SERVICES_FIELD_NUMBER: _ClassVar[int]
+    hostname: str
+    id: str
+    mac_address: bytes
+    firmware_version: str
+    interface_mac_address: bytes
+    ipv4_address: int
+    ipv6_addresses: _containers.RepeatedScalarFieldContainer[bytes]
+    services: _containers.RepeatedCompositeFieldContainer[ServicePort]
+    def __init__(self, hostname: _Optional[str] = ..., id: _Optional[str] = ..., mac_address: _Optional[bytes] = ..., firmware_version: _Optional[str] = ..., interface_mac_address: _Optional[bytes] = ..., ipv4_address: _Optional[int] = ..., ipv6_addresses: _Optional[_Iterable[bytes]] = ..., services: _Optional[_Iterable[_Union[ServicePort, _Mapping]]] = ...) -> None: ...
 
 class Response(_message.Message):
-    __slots__ = ("fan", "temp")
+    __slots__ = ("fan", "temp", "light", "fan_fault", "pwm", "settings", "temp_diagnostics", "temp_history", "probe", "health", "supply_warning", "request_error", "peripherals", "device")
     FAN_FIELD_NUMBER: _ClassVar[int]
     TEMP_FIELD_NUMBER: _ClassVar[int]
+    LIGHT_FIELD_NUMBER: _ClassVar[int]
+    FAN_FAULT_FIELD_NUMBER: _ClassVar[int]
+    PWM_FIELD_NUMBER: _ClassVar[int]
+    SETTINGS_FIELD_NUMBER: _ClassVar[int]
+    TEMP_DIAGNOSTICS_FIELD_NUMBER: _ClassVar[int]
+    TEMP_HISTORY_FIELD_NUMBER: _ClassVar[int]
+    PROBE_FIELD_NUMBER: _ClassVar[int]
+    HEALTH_FIELD_NUMBER: _ClassVar[int]
+    SUPPLY_WARNING_FIELD_NUMBER: _ClassVar[int]
+    REQUEST_ERROR_FIELD_NUMBER: _ClassVar[int]
+    PERIPHERALS_FIELD_NUMBER: _ClassVar[int]
+    DEVICE_FIELD_NUMBER: _ClassVar[int]
     fan: FanResponse
     temp: TempResponse
-    def __init__(self, fan: _Optional[_Union[FanResponse, _Mapping]] = ..., temp: _Optional[_Union[TempResponse, _Mapping]] = ...) -> None: ...
+    light: LightResponse
+    fan_fault: FanFault
+    pwm: PwmResponse
+    settings: SettingsResponse
+    temp_diagnostics: TempDiagnostics
+    temp_history: TempHistory
+    probe: ProbeResponse
+    health: HealthResponse
+    supply_warning: SupplyWarning
+    request_error: RequestError
+    peripherals: PeripheralsResponse
+    device: DeviceResponse
+    def __init__(self, fan: _Optional[_Union[FanResponse, _Mapping]] = ..., temp: _Optional[_Union[TempResponse, _Mapping]] = ..., light: _Optional[_Union[LightResponse, _Mapping]] = ..., fan_fault: _Optional[_Union[FanFault, _Mapping]] = ..., pwm: _Optional[_Union[PwmResponse, _Mapping]] = ..., settings: _Optional[_Union[SettingsResponse, _Mapping]] = ..., temp_diagnostics: _Optional[_Union[TempDiagnostics, _Mapping]] = ..., temp_history: _Optional[_Union[TempHistory, _Mapping]] = ..., probe: _Optional[_Union[ProbeResponse, _Mapping]] = ..., health: _Optional[_Union[HealthResponse, _Mapping]] = ..., supply_warning: _Optional[_Union[SupplyWarning, _Mapping]] = ..., request_error: _Optional[_Union[RequestError, _Mapping]] = ..., peripherals: _Optional[_Union[PeripheralsResponse, _Mapping]] = ..., device: _Optional[_Union[DeviceResponse, _Mapping]] = ...) -> None: ...
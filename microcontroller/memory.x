@@ -1,7 +1,9 @@
 /* Linker script for the STM32F411CE */
 MEMORY
 {
-  FLASH : ORIGIN = 0x08000000, LENGTH = 512K
+  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
+  /* Sectors 6 and 7 are reserved for the settings log, see settings.rs */
+  SETTINGS : ORIGIN = 0x08040000, LENGTH = 256K
   RAM : ORIGIN = 0x20000000, LENGTH = 128K
 }
 
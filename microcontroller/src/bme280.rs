@@ -0,0 +1,150 @@
+use crate::{ Duration, Mono };
+
+use embedded_hal::i2c::I2c;
+use rtic_monotonics::Monotonic;
+
+/*
+Bosch BME280 temperature, humidity and pressure sensor. Each read triggers a
+forced mode measurement with x1 oversampling, then compensates the raw values
+with the calibration from the sensor's NVM, using the integer formulas from
+section 4.2.3 of the datasheet.
+ */
+pub const ADDRESSES: [u8; 2] = [0x76, 0x77];
+const CHIP_ID: u8 = 0x60;
+
+const REG_CALIBRATION_TP: u8 = 0x88;
+const REG_CALIBRATION_H1: u8 = 0xa1;
+const REG_CHIP_ID: u8 = 0xd0;
+const REG_RESET: u8 = 0xe0;
+const REG_CALIBRATION_H: u8 = 0xe1;
+const REG_CTRL_HUM: u8 = 0xf2;
+const REG_STATUS: u8 = 0xf3;
+const REG_CTRL_MEAS: u8 = 0xf4;
+const REG_DATA: u8 = 0xf7;
+
+const RESET: u8 = 0xb6;
+const STATUS_IM_UPDATE: u8 = 0x01;
+const NVM_POLLS: usize = 100;
+const HUMIDITY_X1: u8 = 0b001;
+// temperature x1, pressure x1, forced mode
+const MEASURE_FORCED: u8 = 0b001_001_01;
+// The longest measurement time with x1 oversampling is 9.3ms
+const MEASUREMENT: Duration = Duration::millis(10);
+
+pub struct Measurement {
+    pub temperature_celsius: f32,
+    pub humidity_percent: f32,
+    pub pressure_hpa: f32,
+}
+
+struct Calibration {
+    t1: u16, t2: i16, t3: i16,
+    p1: u16, p2: i16, p3: i16, p4: i16, p5: i16, p6: i16, p7: i16, p8: i16, p9: i16,
+    h1: u8, h2: i16, h3: u8, h4: i16, h5: i16, h6: i8,
+}
+
+pub struct Bme280 {
+    address: u8,
+    calibration: Calibration,
+}
+
+impl Bme280 {
+    // Returns the sensor if one answers at the address with the BME280 chip id
+    pub fn detect<I2C: I2c>(i2c: &mut I2C, address: u8) -> Option<Self> {
+        let mut id = [0u8];
+        i2c.write_read(address, &[REG_CHIP_ID], &mut id).ok()?;
+        if id[0] != CHIP_ID {
+            return None;
+        }
+
+        i2c.write(address, &[REG_RESET, RESET]).ok()?;
+        // wait for the calibration to be copied from NVM, which takes ~2ms
+        let mut status = [STATUS_IM_UPDATE];
+        for _ in 0..NVM_POLLS {
+            i2c.write_read(address, &[REG_STATUS], &mut status).ok()?;
+            if status[0] & STATUS_IM_UPDATE == 0 {
+                break;
+            }
+        }
+
+        let mut tp = [0u8; 24];
+        let mut h1 = [0u8];
+        let mut h = [0u8; 7];
+        i2c.write_read(address, &[REG_CALIBRATION_TP], &mut tp).ok()?;
+        i2c.write_read(address, &[REG_CALIBRATION_H1], &mut h1).ok()?;
+        i2c.write_read(address, &[REG_CALIBRATION_H], &mut h).ok()?;
+        let u = |i: usize| u16::from_le_bytes([tp[i], tp[i + 1]]);
+        let s = |i: usize| i16::from_le_bytes([tp[i], tp[i + 1]]);
+        let calibration = Calibration {
+            t1: u(0), t2: s(2), t3: s(4),
+            p1: u(6), p2: s(8), p3: s(10), p4: s(12), p5: s(14), p6: s(16), p7: s(18), p8: s(20), p9: s(22),
+            h1: h1[0],
+            h2: i16::from_le_bytes([h[0], h[1]]),
+            h3: h[2],
+            // 12 bit values, sharing the nibbles of 0xe5
+            h4: (h[3] as i8 as i16) << 4 | (h[4] & 0x0f) as i16,
+            h5: (h[5] as i8 as i16) << 4 | (h[4] >> 4) as i16,
+            h6: h[6] as i8,
+        };
+
+        // humidity oversampling only takes effect after a write to ctrl_meas
+        i2c.write(address, &[REG_CTRL_HUM, HUMIDITY_X1]).ok()?;
+        Some(Bme280 { address, calibration })
+    }
+
+    pub async fn read<I2C: I2c>(&self, i2c: &mut I2C) -> Result<Measurement, I2C::Error> {
+        i2c.write(self.address, &[REG_CTRL_MEAS, MEASURE_FORCED])?;
+        Mono::delay(MEASUREMENT).await;
+        let mut data = [0u8; 8];
+        i2c.write_read(self.address, &[REG_DATA], &mut data)?;
+
+        let raw20 = |i: usize| (data[i] as i32) << 12 | (data[i + 1] as i32) << 4 | (data[i + 2] as i32) >> 4;
+        let (pressure, temperature, humidity) = (raw20(0), raw20(3), (data[6] as i32) << 8 | data[7] as i32);
+
+        let (t_fine, temperature) = self.temperature(temperature);
+        Ok(Measurement {
+            temperature_celsius: temperature as f32 / 100.0,
+            humidity_percent: self.humidity(t_fine, humidity) as f32 / 1024.0,
+            pressure_hpa: self.pressure(t_fine, pressure) as f32 / 256.0 / 100.0,
+        })
+    }
+
+    // Returns t_fine and hundredths of a degree
+    fn temperature(&self, adc: i32) -> (i32, i32) {
+        let c = &self.calibration;
+        let var1 = (((adc >> 3) - ((c.t1 as i32) << 1)) * c.t2 as i32) >> 11;
+        let var2 = (((((adc >> 4) - c.t1 as i32) * ((adc >> 4) - c.t1 as i32)) >> 12) * c.t3 as i32) >> 14;
+        let t_fine = var1 + var2;
+        (t_fine, (t_fine * 5 + 128) >> 8)
+    }
+
+    // Pa in Q24.8
+    fn pressure(&self, t_fine: i32, adc: i32) -> u32 {
+        let c = &self.calibration;
+        let mut var1 = t_fine as i64 - 128000;
+        let mut var2 = var1 * var1 * c.p6 as i64;
+        var2 += (var1 * c.p5 as i64) << 17;
+        var2 += (c.p4 as i64) << 35;
+        var1 = ((var1 * var1 * c.p3 as i64) >> 8) + ((var1 * c.p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * c.p1 as i64) >> 33;
+        if var1 == 0 {
+            return 0;
+        }
+        let mut p = 1048576 - adc as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        var1 = (c.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        var2 = (c.p8 as i64 * p) >> 19;
+        (((p + var1 + var2) >> 8) + ((c.p7 as i64) << 4)) as u32
+    }
+
+    // %RH in Q22.10
+    fn humidity(&self, t_fine: i32, adc: i32) -> u32 {
+        let c = &self.calibration;
+        let mut v = t_fine - 76800;
+        v = (((adc << 14) - ((c.h4 as i32) << 20) - (c.h5 as i32 * v) + 16384) >> 15)
+            * (((((((v * c.h6 as i32) >> 10) * (((v * c.h3 as i32) >> 11) + 32768)) >> 10) + 2097152)
+                * c.h2 as i32 + 8192) >> 14);
+        v -= (((v >> 15) * (v >> 15)) >> 7) * c.h1 as i32 >> 4;
+        (v.clamp(0, 419430400) >> 12) as u32
+    }
+}
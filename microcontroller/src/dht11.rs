@@ -1,125 +1,261 @@
 
 use core::{result::Result, u32};
-use defmt::{ debug, error, Format };
+use defmt::{ debug, error, info, warn, Format };
+
+use aux_core::{
+    dht::{ ChecksumError, Edges, InputState, Packet, Reading, Statistics },
+    statistics::StatsAccumulator,
+};
+use heapless::String;
+pub use aux_core::dht::{ ReadError, Sensor };
 
 use futures::{select_biased, FutureExt};
 use rtic_monotonics::Monotonic;
 use rtic_sync::{make_signal, signal::{ SignalReader, SignalWriter }};
-use embedded_hal::digital::{ InputPin, OutputPin };
+use embedded_hal::digital::OutputPin;
 use hal::{ gpio::{ Edge, ExtiPin }, pac::{ EXTI }, syscfg::SysCfg};
 
-use crate::statistics::StatsAccumulator;
+#[cfg(feature = "dht-capture")]
+mod capture;
+#[cfg(feature = "dht-capture")]
+pub use capture::{ Capture, CAPTURES };
 
-#[derive(Clone, Copy, Format)]
-pub struct Packet(u8, [u8;5]);
+fn decode(packet: Packet, sensor: Sensor) -> Result<TempResponse, ReadError> {
+    match sensor.decode(packet.data()) {
+        Result::Ok(Reading { temperature_celsius, humidity_percent }) => {
+            Result::Ok(TempResponse { temperature_celsius, humidity_percent, ..TempResponse::default() })
+        },
+        Result::Err(ChecksumError { computed, received }) => {
+            error!("checksum {} != {} for {}", computed, received, packet.data());
+            Result::Err(ReadError::Checksum)
+        }
+    }
+}
+
+use crate::{ 
+    Duration,
+    Instant,
+    Mono,
+    proto::{ TempDiagnostics, TempResponse, TempStatus, TimingInterval, TimingState, TimingStatistics } };
+/*
+This works purely on falling edge. The time between the current falling
+edge and the previous one gets measured, and decoded by aux_core::dht.
 
-impl Packet {
-    const FIRST: u8 = 40; // MSB first: network data order
-    const LAST: u8 = 0;
+The edges are timed either by an EXTI interrupt on each one, or with the
+dht-capture feature, by timer input capture and DMA (see capture.rs). Each
+sensor on EXTI needs its own EXTI line.
+ */
+// Timeouts in a row after which a sensor is taken to be missing, and only
+// read again when a request asks for it
+pub const SUSPEND_TIMEOUTS: u32 = 5;
+
+// Why reading a temperature sensor failed. The DHT decoder's errors, or the
+// I2C bus, which only the I2C sensor has.
+#[derive(Clone, Copy, PartialEq, Format)]
+pub enum SensorError {
+    Read(ReadError),
+    Bus,
+}
 
-    const fn new() -> Self {
-        Packet(Self::FIRST, [0;5])
+impl From<ReadError> for SensorError {
+    fn from(error: ReadError) -> Self {
+        SensorError::Read(error)
     }
+}
 
-    fn append(&mut self, value: bool) {
-        self.0 -= 1;
-        let byte = (self.0>>3) as usize;
-        self.1[byte] = self.1[byte]<<1 | match value { false => 0, true => 1 };
-    }
+// Whether to read a sensor
+#[derive(Clone, Copy, PartialEq)]
+pub enum Polling {
+    // It isn't fitted
+    Off,
+    // Until it times out SUSPEND_TIMEOUTS times in a row
+    Fitted,
+    // Whatever happens, for the sensor the fan curve's failsafe watches
+    Always,
+}
 
-    fn complete(&self) -> bool {
-        self.0 == Self::LAST
-    }
+// The last good reading, so clients can tell how far to trust it
+#[derive(Clone)]
+pub struct Latest {
+    sensor: u32,
+    name: &'static str,
+    polling: Polling,
+    reading: Option<(Instant, TempResponse)>,
+    error: Option<SensorError>,
+    failures: u32,
+    timeouts: u32,
+    stale: Duration,
+}
 
-    fn decode(self) -> Result<TempResponse, ReadError> {
-        let humidity_percent = self.byte_n(3) as f32 + (self.byte_n(4) as f32 / 10.0);
-        let temperature_celsius = self.word_n(1) as f32 + (self.byte_n(2) as f32 / 10.0);
-        let crc = self.1[4]
-            .wrapping_add(self.1[3])
-            .wrapping_add(self.1[2])
-            .wrapping_add(self.1[1]);
+impl Latest {
+    // sensor: the index of the sensor in requests
+    // stale: the age at which a reading is reported stale, when no read has failed since
+    pub fn new(sensor: u32, name: &'static str, stale: Duration, polling: Polling) -> Self {
+        Latest { sensor, name, polling, reading: None, error: None, failures: 0, timeouts: 0, stale }
+    }
 
-        if crc == self.1[0] {
-            Result::Ok(TempResponse { temperature_celsius, humidity_percent })        
-        } else {
-            error!("checksum {} != {} for {}", crc, self.1[0], self.1);
-            Result::Err(ReadError::Checksum)
+    pub fn update<E: Copy + Into<SensorError>>(&mut self, now: Instant, result: &Result<TempResponse, E>) {
+        match result {
+            Result::Ok(response) => {
+                self.reading = Some((now, response.clone()));
+                self.error = None;
+                self.failures = 0;
+                self.timeouts = 0;
+            },
+            Result::Err(err) => {
+                let err = (*err).into();
+                self.error = Some(err);
+                self.failures += 1;
+                self.timeouts = match err {
+                    SensorError::Read(ReadError::Timeout) => self.timeouts.saturating_add(1),
+                    _ => 0,
+                };
+            },
+        }
+        if self.suspended() && self.timeouts == SUSPEND_TIMEOUTS {
+            warn!("{} timed out {} times, not reading it until it's requested", self.name, self.timeouts);
         }
     }
 
-    fn byte_n(self, n: usize) -> u16 {
-        self.1[n] as u16
+    // Whether the sensor should be read now
+    pub fn due(&self) -> bool {
+        self.polling != Polling::Off && !self.suspended()
     }
 
-    fn word_n(self, n: usize) -> u16 {
-        self.byte_n(n+1)
+    // Nothing answers, so the sensor is probably missing
+    pub fn suspended(&self) -> bool {
+        self.polling == Polling::Fitted && self.timeouts >= SUSPEND_TIMEOUTS
     }
 
-}
+    // Read a suspended sensor again
+    pub fn resume(&mut self) {
+        if self.suspended() {
+            info!("resuming reads of {}", self.name);
+            self.timeouts = 0;
+        }
+    }
 
-use crate::{ 
-    Duration,
-    Mono,
-    proto::TempResponse };
-/*
-This works purely on falling edge. The time between the current falling
-edge and the previous one gets measured. The interrupt handler waits
-for an interval that corresponds to a 'response' of ~160uS. Then
-it transitions into the Data state. In the data state, there's a fixed
-low of 50uS, followed by a high of 26-28uS for a 0, or 70uS for a 1,
-so this looks for an interval of ~77uS or ~120uS respectively
- */
-#[derive(Clone, Copy, Format)]
-pub enum InputState {
-    Standby,
-    Data(Packet),
-    Error
+    pub fn response(&self, now: Instant) -> TempResponse {
+        if self.polling == Polling::Off {
+            return TempResponse {
+                name: String::try_from(self.name).unwrap_or_default(),
+                ..no_sensor(self.sensor)
+            };
+        }
+        let (age, mut response) = match &self.reading {
+            Some((time, response)) => (now - *time, response.clone()),
+            None => (Duration::from_ticks(0), TempResponse::default()),
+        };
+        response.sensor = self.sensor;
+        response.name = String::try_from(self.name).unwrap_or_default();
+        response.age_millis = age.to_millis() as u32;
+        response.consecutive_failures = self.failures;
+        response.status = match (self.error, &self.reading) {
+            _ if self.suspended() => TempStatus::Suspended,
+            (Some(SensorError::Read(ReadError::Checksum)), _) => TempStatus::Checksum,
+            (Some(SensorError::Read(ReadError::Timeout)), _) => TempStatus::Timeout,
+            (Some(SensorError::Read(ReadError::Timing(..))), _) => TempStatus::Timing,
+            (Some(SensorError::Read(ReadError::Busy)), _) => TempStatus::Busy,
+            (Some(SensorError::Bus), _) => TempStatus::Bus,
+            (None, None) => TempStatus::NeverRead,
+            (None, Some(_)) if age > self.stale => TempStatus::Stale,
+            (None, Some(_)) => TempStatus::Ok,
+        };
+        response
+    }
 }
 
-#[derive(Clone, Copy, Format)]
-pub enum ReadError {
-    Checksum,
-    Timeout,
-    Timing(InputState, u32),
-    Busy
+// The response to a request for a sensor that doesn't exist
+pub fn no_sensor(sensor: u32) -> TempResponse {
+    TempResponse { sensor, status: TempStatus::NoSensor, ..TempResponse::default() }
 }
 
+// Everything that happened reading the sensor since boot
 #[derive(Clone, Copy, Format)]
-pub struct Statistics {
-    pub interrupt: StatsAccumulator<u32,f32>,
-    pub response: StatsAccumulator<u32,f32>,
-    pub zero: StatsAccumulator<u32,f32>,
-    pub one: StatsAccumulator<u32,f32>,
+pub struct Diagnostics {
+    pub statistics: Statistics,
+    pub reads: u32,
+    pub checksum_errors: u32,
+    pub timeouts: u32,
+    pub timing_errors: u32,
+    pub last_timing: Option<(InputState, u32)>,
 }
 
-pub struct Dht11Writer<'a, PIN: ExtiPin> {
-    timestamp: u32,
-    state: InputState,
-    writer: SignalWriter<'a, (Statistics, Result<Packet, ReadError>)>,
-    pin: PIN,
-    statistics: Statistics,
-}
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics {
+            statistics: Statistics::new(),
+            reads: 0,
+            checksum_errors: 0,
+            timeouts: 0,
+            timing_errors: 0,
+            last_timing: None,
+        }
+    }
 
-struct DurationRange {
-    min: u32,
-    max: u32,
-    med: u32,
-}
+    fn count(&mut self, result: &Result<TempResponse, ReadError>) {
+        match result {
+            Result::Ok(_) => self.reads += 1,
+            Result::Err(ReadError::Checksum) => self.checksum_errors += 1,
+            Result::Err(ReadError::Timeout) => self.timeouts += 1,
+            Result::Err(ReadError::Timing(state, interval)) => {
+                self.timing_errors += 1;
+                self.last_timing = Some((*state, *interval));
+            },
+            Result::Err(ReadError::Busy) => { },
+        }
+    }
 
-impl DurationRange {
-    const fn micros(min: u32, max: u32) -> Self {
-        DurationRange { min, max, med: (min+max)/2 }
+    pub fn response(&self, sensor: u32) -> TempDiagnostics {
+        let statistics = [
+            (TimingInterval::Interrupt, &self.statistics.interrupt),
+            (TimingInterval::Response, &self.statistics.response),
+            (TimingInterval::Zero, &self.statistics.zero),
+            (TimingInterval::One, &self.statistics.one),
+        ];
+        let (last_timing_state, last_timing_bits, last_timing_micros) = match self.last_timing {
+            Some((InputState::Standby, interval)) => (TimingState::Standby, 0, interval),
+            Some((InputState::Data(packet), interval)) => (TimingState::Data, packet.bits(), interval),
+            Some((InputState::Error, interval)) => (TimingState::Error, 0, interval),
+            None => (TimingState::Standby, 0, 0),
+        };
+
+        TempDiagnostics {
+            sensor,
+            reads: self.reads,
+            checksum_errors: self.checksum_errors,
+            timeouts: self.timeouts,
+            timing_errors: self.timing_errors,
+            statistics: statistics.iter().map(|(interval, stats)| timing_statistics(*interval, stats)).collect(),
+            last_timing_state,
+            last_timing_bits,
+            last_timing_micros,
+        }
     }
+}
 
-    fn contains(self, value: u32) -> bool {
-        value > self.min && value < self.max
+fn timing_statistics(interval: TimingInterval, stats: &StatsAccumulator<u32, f32>) -> TimingStatistics {
+    // An empty accumulator has infinite bounds
+    match stats.count() {
+        0 => TimingStatistics { interval, ..TimingStatistics::default() },
+        count => TimingStatistics {
+            interval,
+            count,
+            mean_micros: stats.mean(),
+            min_micros: stats.min(),
+            max_micros: stats.max(),
+            stddev_micros: stats.stddev().unwrap_or(0.0),
+        },
     }
 }
 
+pub struct Dht11Writer<'a, PIN: ExtiPin> {
+    edges: Edges<u64>,
+    writer: SignalWriter<'a, (Statistics, Result<Packet, ReadError>)>,
+    pin: PIN,
+}
+
 const TIMEOUT: Duration = Duration::micros(2*(160 + 40*120));
-const INITIATE: u32 = 18000;
-const RESPONSE: DurationRange = DurationRange::micros(150, 180);
-const DATA: DurationRange = DurationRange::micros(50, 150);
 
 impl <'a, PIN: ExtiPin> Dht11Writer<'a, PIN> {
     // call on a falling edge.
@@ -128,82 +264,94 @@ impl <'a, PIN: ExtiPin> Dht11Writer<'a, PIN> {
     // opt-level = 0 does not work.
     pub fn falling_edge(&mut self) {
 
-        let before = Mono::now().ticks() as u32;
-        // deal with wrapping
-        let interval = if before < self.timestamp { u32::MAX - self.timestamp + before } else  { before - self.timestamp };
-
-        self.timestamp = before;
-        let initial = self.state;
-        self.state = self.updated(interval, initial);
+        let before = Mono::now().ticks();
+        if let Some(result) = self.edges.falling_edge(before) {
+            self.writer.write((self.edges.decoder.statistics, result));
+        }
 
-        let after = Mono::now().ticks() as u32;
-        self.statistics.interrupt.add((after - before) as f32);
+        let after = Mono::now().ticks();
+        self.edges.decoder.statistics.interrupt.add((after - before) as f32);
 
         self.pin.clear_interrupt_pending_bit();
 
     }
-    
-    fn updated(&mut self, interval: u32, initial: InputState) -> InputState {
-        use InputState::*;
-
-        match initial {
-            // Either this is initiate, or the interval between reads
-            Error | Standby if interval > INITIATE => Standby,
-            Standby if RESPONSE.contains(interval) => {
-                self.statistics.response.add(interval as f32);
-                Data(Packet::new())
-            },
-            Data(mut packet)  if DATA.contains(interval) => {
-                let value = interval > DATA.med;
-                if value { &mut self.statistics.one } else { &mut self.statistics.zero }.add(interval as f32);
-                packet.append(value);
-                if packet.complete() {
-                    self.writer.write((self.statistics, Result::Ok(packet)));
-                    Standby
-                } else {
-                    Data(packet)
-                }
-            },
-            Standby | Data(_) => {
-                self.writer.write((self.statistics, Result::Err(ReadError::Timing(initial, interval))));
-                Error
-            },
-            Error => Error,
-        }
-    }
-
 }
 
 
 
+// How the edges from a sensor are timed
+enum Source<'a> {
+    Exti(SignalReader<'a, (Statistics, Result<Packet, ReadError>)>),
+    #[cfg(feature = "dht-capture")]
+    Capture(Capture<'a>),
+}
+
 pub struct Dht11Reader<'a, PIN> {
-    reader: SignalReader<'a, (Statistics, Result<Packet, ReadError>)>,
+    source: Source<'a>,
     pin: Option<PIN>,
+    sensor: Sensor,
+    name: &'static str,
+    diagnostics: Diagnostics,
 }
 
 impl <'a, PIN> Dht11Reader<'a, PIN>
-where PIN: ExtiPin + InputPin + OutputPin {
+where PIN: OutputPin {
+
+    // The shortest time the sensor allows between reads
+    pub fn interval(&self) -> Duration {
+        Duration::millis(self.sensor.interval_millis() as u64)
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
 
     pub async fn read(&mut self) -> Result<TempResponse, ReadError> {
-        match self.pin.take() {
+        let sensor = self.sensor;
+        let result = match self.pin.take() {
             Some(mut pin) => {
                 pin.set_low().unwrap();
-                Mono::delay(Duration::millis(20)).await;
+                Mono::delay(Duration::millis(sensor.start_millis() as u64)).await;
+                let result = self.receive(&mut pin).await;
+                self.pin.replace(pin);
+                result.and_then(|packet| decode(packet, sensor))
+            },
+            None => Result::Err(ReadError::Busy)
+        };
+        self.diagnostics.count(&result);
+        result
+    }
+
+    async fn receive(&mut self, pin: &mut PIN) -> Result<Packet, ReadError> {
+        match &mut self.source {
+            Source::Exti(reader) => {
                 pin.set_high().unwrap();
-                let result = select_biased! {
-                    (statistics, result) = self.reader.wait_fresh().fuse() => {
-                        debug!("statistics: {}", statistics);
-                        match result {
-                            Result::Ok(packet) => packet.decode(),
-                            Result::Err(err) => Result::Err(err),
-                        }
+                select_biased! {
+                    (statistics, result) = reader.wait_fresh().fuse() => {
+                        debug!("{} statistics: {}", self.name, statistics);
+                        self.diagnostics.statistics = statistics;
+                        result
                     },
                     _ = Mono::delay(TIMEOUT).fuse() => Result::Err(ReadError::Timeout),
-                };
-                self.pin.replace(pin);
+                }
+            },
+            #[cfg(feature = "dht-capture")]
+            Source::Capture(capture) => {
+                // Switching the pin to the timer releases the line, so the capture
+                // is running before the sensor can respond. Setting the output high
+                // keeps it released when it's switched back.
+                capture.arm();
+                pin.set_high().unwrap();
+                Mono::delay(TIMEOUT).await;
+                let result = capture.decode();
+                self.diagnostics.statistics = capture.statistics();
+                debug!("{} statistics: {}", self.name, self.diagnostics.statistics);
                 result
             },
-            None => Result::Err(ReadError::Busy)
         }
     }
 }
@@ -211,9 +359,17 @@ where PIN: ExtiPin + InputPin + OutputPin {
 // altpin: ALTPIN is a hack: it must be another pin which shares the same EXTI. it's
 // used to clear the interrupt, without needing a reference to the output pin, which
 // can't be shared between an interrupt and an async task.
+// sensor: the DHT11 and DHT22 families share the timing of the data bits, but
+// differ in the start signal and how the values are encoded.
+// name: identifies the sensor in logs and responses
+// fitted: a sensor that isn't is never read, and the floating line mustn't
+// raise interrupts
 pub fn make<PIN: ExtiPin + OutputPin, ALTPIN: ExtiPin>(
         pin: PIN,
         altpin: ALTPIN,
+        sensor: Sensor,
+        name: &'static str,
+        fitted: bool,
         syscfg: &mut SysCfg, 
         exti: &mut EXTI) -> (Dht11Writer<'static, ALTPIN>, Dht11Reader<'static, PIN>)
 {
@@ -223,21 +379,39 @@ pub fn make<PIN: ExtiPin + OutputPin, ALTPIN: ExtiPin>(
     let mut io = pin;
     io.make_interrupt_source(syscfg);
     io.trigger_on_edge(exti, Edge::Falling);
-    io.enable_interrupt(exti);
+    if fitted {
+        io.enable_interrupt(exti);
+    }
     
     (
         Dht11Writer { 
             writer, 
-            timestamp: 0, 
-            state: InputState::Standby, 
+            edges: Edges::new(),
             pin: altpin, 
-            statistics: Statistics {
-                interrupt: StatsAccumulator::new(),
-                zero: StatsAccumulator::new(),
-                one: StatsAccumulator::new(),
-                response: StatsAccumulator::new(), 
-            },
         },
-        Dht11Reader { reader, pin : Option::Some(io) },
+        Dht11Reader { 
+            source: Source::Exti(reader), 
+            pin : Option::Some(io), 
+            sensor, 
+            name, 
+            diagnostics: Diagnostics::new(),
+        },
     )
 }
+
+// The pin must be PA2, which the capture switches to TIM5 channel 3 for each read.
+#[cfg(feature = "dht-capture")]
+pub fn make_capture<PIN: OutputPin>(
+        pin: PIN,
+        capture: Capture<'static>,
+        sensor: Sensor,
+        name: &'static str) -> Dht11Reader<'static, PIN>
+{
+    Dht11Reader {
+        source: Source::Capture(capture),
+        pin: Option::Some(pin),
+        sensor,
+        name,
+        diagnostics: Diagnostics::new(),
+    }
+}
@@ -0,0 +1,128 @@
+use core::sync::atomic::{ compiler_fence, Ordering };
+
+use hal::{
+    pac::{ DMA1, GPIOA, RCC, TIM5 },
+    rcc::Clocks,
+};
+
+use aux_core::dht::{ Decoder, Packet, ReadError, Statistics };
+
+/*
+Times the falling edges in hardware, rather than with an interrupt per edge,
+so a read isn't disturbed by higher priority interrupts or a slow build.
+
+TIM5 free runs at 1MHz, and captures the counter into CCR3 on each falling
+edge of PA2 (TIM5_CH3, AF2). Each capture requests a DMA1 stream 0 channel 6
+transfer of CCR3 into the buffer. After a read, the intervals between
+successive captures go through the same decoder as the EXTI backend.
+
+PA2 stays an open drain output between reads, so the reader can pull it low
+for the start signal. It's switched to the timer only while the capture runs.
+ */
+
+// The sensor's response, 40 data bits and some slack for glitches
+pub const CAPTURES: usize = 48;
+
+const CAPTURE_RATE: u32 = 1_000_000;
+const PIN: u32 = 2;
+const MODER_OUTPUT: u32 = 0b01;
+const MODER_ALTERNATE: u32 = 0b10;
+const AF_TIM5: u32 = 2;
+
+const RCC_APB1ENR_TIM5EN: u32 = 1 << 3;
+const RCC_AHB1ENR_DMA1EN: u32 = 1 << 21;
+
+// CC3S = 01: CC3 is an input mapped on TI3, IC3F = 0010: 4 samples at the timer clock
+const CCMR2_IC3: u32 = 0b01 | 0b0010 << 4;
+const CCER_CC3E: u32 = 1 << 8;
+const CCER_CC3P: u32 = 1 << 9; // falling edge
+const DIER_CC3DE: u32 = 1 << 11;
+const CR1_CEN: u32 = 1;
+const EGR_UG: u32 = 1;
+
+// channel 6, 32 bit memory and peripheral, increment memory, peripheral to memory
+const DMA_CR: u32 = 6 << 25 | 0b10 << 13 | 0b10 << 11 | 1 << 10;
+const DMA_EN: u32 = 1;
+// FEIF0, DMEIF0, TEIF0, HTIF0 and TCIF0
+const DMA_STREAM0_FLAGS: u32 = 0x3d;
+
+pub struct Capture<'a> {
+    tim: TIM5,
+    dma: DMA1,
+    buffer: &'a mut [u32; CAPTURES],
+    decoder: Decoder,
+}
+
+impl <'a> Capture<'a> {
+    pub fn new(tim: TIM5, dma: DMA1, buffer: &'a mut [u32; CAPTURES], clocks: &Clocks) -> Self {
+        // Safety: only sets the enable bits for TIM5 and DMA1, which this owns
+        let rcc = unsafe { &*RCC::ptr() };
+        rcc.apb1enr().modify(|r, w| unsafe { w.bits(r.bits() | RCC_APB1ENR_TIM5EN) });
+        rcc.ahb1enr().modify(|r, w| unsafe { w.bits(r.bits() | RCC_AHB1ENR_DMA1EN) });
+
+        tim.psc().write(|w| unsafe { w.bits(clocks.timclk1().raw() / CAPTURE_RATE - 1) });
+        tim.arr().write(|w| unsafe { w.bits(u32::MAX) });
+        tim.ccmr2_input().write(|w| unsafe { w.bits(CCMR2_IC3) });
+        tim.ccer().write(|w| unsafe { w.bits(CCER_CC3E | CCER_CC3P) });
+        tim.dier().write(|w| unsafe { w.bits(DIER_CC3DE) });
+        // load the prescaler
+        tim.egr().write(|w| unsafe { w.bits(EGR_UG) });
+        tim.cr1().write(|w| unsafe { w.bits(CR1_CEN) });
+
+        let gpioa = unsafe { &*GPIOA::ptr() };
+        gpioa.afrl().modify(|r, w| unsafe { w.bits(r.bits() & !(0xf << (PIN * 4)) | AF_TIM5 << (PIN * 4)) });
+
+        Capture { tim, dma, buffer, decoder: Decoder::new() }
+    }
+
+    // Start capturing edges, and release the line
+    pub fn arm(&mut self) {
+        let stream = self.dma.st(0);
+        stream.cr().write(|w| unsafe { w.bits(0) });
+        while stream.cr().read().bits() & DMA_EN != 0 {}
+        self.dma.lifcr().write(|w| unsafe { w.bits(DMA_STREAM0_FLAGS) });
+
+        // reading CCR3 clears a capture left from the start signal
+        let _ = self.tim.ccr(2).read();
+        stream.par().write(|w| unsafe { w.bits(self.tim.ccr(2).as_ptr() as u32) });
+        stream.m0ar().write(|w| unsafe { w.bits(self.buffer.as_mut_ptr() as u32) });
+        stream.ndtr().write(|w| unsafe { w.bits(CAPTURES as u32) });
+        compiler_fence(Ordering::Release);
+        stream.cr().write(|w| unsafe { w.bits(DMA_CR | DMA_EN) });
+
+        moder(MODER_ALTERNATE);
+    }
+
+    // Stop capturing, and return the number of edges
+    fn disarm(&mut self) -> usize {
+        moder(MODER_OUTPUT);
+
+        let stream = self.dma.st(0);
+        stream.cr().modify(|r, w| unsafe { w.bits(r.bits() & !DMA_EN) });
+        while stream.cr().read().bits() & DMA_EN != 0 {}
+        compiler_fence(Ordering::Acquire);
+        CAPTURES - stream.ndtr().read().bits() as usize
+    }
+
+    // Decode the edges captured since arm()
+    pub fn decode(&mut self) -> Result<Packet, ReadError> {
+        let count = self.disarm();
+        self.decoder.reset();
+        let decoder = &mut self.decoder;
+        self.buffer[..count]
+            .windows(2)
+            .find_map(|edges| decoder.interval(edges[1].wrapping_sub(edges[0])))
+            .unwrap_or(Result::Err(ReadError::Timeout))
+    }
+
+    pub fn statistics(&self) -> Statistics {
+        self.decoder.statistics
+    }
+}
+
+// The reader owns PA2 as an output, so this only changes its mode.
+fn moder(mode: u32) {
+    // Safety: the other GPIOA pins are only configured during init
+    let gpioa = unsafe { &*GPIOA::ptr() };
+    gpioa.moder().modify(|r, w| unsafe { w.bits(r.bits() & !(0b11 << (PIN * 2)) | mode << (PIN * 2)) });
+}
@@ -0,0 +1,153 @@
+use crate::{
+    onewire::{ OneWire, Rom, CRC },
+    proto::{
+        Probe,
+        ProbeRequest,
+        ProbeRequest_,
+        ProbeResponse,
+        ProbeStatus,
+        Response,
+        Response_::Peripheral as ResponsePeripheral,
+    },
+    shell::ResponseSender,
+    Duration,
+    Mono,
+};
+
+use defmt::{ info, warn };
+use embedded_hal::digital::{ InputPin, OutputPin };
+use futures::{ select_biased, FutureExt };
+use heapless::Vec;
+use rtic_monotonics::Monotonic;
+use rtic_sync::channel::Receiver;
+
+pub const REQUEST_CAPACITY: usize = 2;
+pub const MAX_PROBES: usize = 8;
+const FAMILY: u8 = 0x28;
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xbe;
+// A 12 bit conversion
+const CONVERSION: Duration = Duration::millis(750);
+const INTERVAL: Duration = Duration::secs(2);
+// 85C, in the scratchpad until the first conversion completes
+const POWER_ON: i16 = 0x0550;
+
+#[derive(Clone, Copy)]
+struct Reading {
+    rom: Rom,
+    temperature_celsius: f32,
+    status: ProbeStatus,
+}
+
+// DS18B20 probes on one 1-Wire bus, found by a ROM search at start up or
+// on request. All of them convert at once, then each is read in turn.
+pub struct Probes<'a, PIN> {
+    bus: OneWire<PIN>,
+    requests: Receiver<'a, ProbeRequest, REQUEST_CAPACITY>,
+    responses: ResponseSender,
+    readings: Vec<Reading, MAX_PROBES>,
+}
+
+impl <'a, PIN> Probes<'a, PIN>
+where PIN: InputPin + OutputPin {
+    pub fn new(
+        pin: PIN,
+        requests: Receiver<'a, ProbeRequest, REQUEST_CAPACITY>,
+        responses: ResponseSender,
+    ) -> Self {
+        Probes {
+            bus: OneWire::new(pin),
+            requests,
+            responses,
+            readings: Vec::new(),
+        }
+    }
+
+    pub async fn run(&mut self) {
+        self.search().await;
+        loop {
+            self.convert().await;
+            select_biased! {
+                request = self.requests.recv().fuse() => match request {
+                    Ok(request) => self.process(request).await,
+                    Err(_) => break,
+                },
+                _ = Mono::delay(INTERVAL).fuse() => {},
+            }
+        }
+    }
+
+    async fn search(&mut self) {
+        let roms: Vec<Rom, MAX_PROBES> = self.bus.search().await;
+        self.readings = roms.iter()
+            .filter(|rom| match rom.to_le_bytes()[0] {
+                FAMILY => true,
+                family => {
+                    warn!("1-Wire device {:x} of family {:x} isn't a DS18B20", rom, family);
+                    false
+                },
+            })
+            .map(|rom| Reading { rom: *rom, temperature_celsius: 0.0, status: ProbeStatus::NeverRead })
+            .collect();
+        info!("{} DS18B20 probes", self.readings.len());
+    }
+
+    async fn convert(&mut self) {
+        if self.readings.is_empty() || !self.bus.select(None).await {
+            return;
+        }
+        self.bus.write_byte(CONVERT_T).await;
+        Mono::delay(CONVERSION).await;
+
+        for index in 0..self.readings.len() {
+            let rom = self.readings[index].rom;
+            let (temperature, status) = match self.read(rom).await {
+                Ok(temperature) => (temperature, ProbeStatus::Ok),
+                Err(status) => {
+                    warn!("DS18B20 {:x} read failed: {}", rom, status.0);
+                    (self.readings[index].temperature_celsius, status)
+                },
+            };
+            self.readings[index].temperature_celsius = temperature;
+            self.readings[index].status = status;
+        }
+    }
+
+    async fn read(&mut self, rom: Rom) -> Result<f32, ProbeStatus> {
+        if !self.bus.select(Some(rom)).await {
+            return Err(ProbeStatus::NoResponse);
+        }
+        self.bus.write_byte(READ_SCRATCHPAD).await;
+        let mut scratchpad = [0u8; 9];
+        self.bus.read(&mut scratchpad).await;
+        // A disconnected probe reads all ones, which fails the CRC
+        if CRC.checksum(&scratchpad[..8]) != scratchpad[8] {
+            return Err(ProbeStatus::Crc);
+        }
+        // sixteenths of a degree
+        match i16::from_le_bytes([scratchpad[0], scratchpad[1]]) {
+            // The power-on value: the probe reset, or missed the conversion
+            POWER_ON => Err(ProbeStatus::PowerOn),
+            raw => Ok(raw as f32 / 16.0),
+        }
+    }
+
+    async fn process(&mut self, request: ProbeRequest) {
+        match request {
+            ProbeRequest { command: Some(ProbeRequest_::Command::Search(_)) } => self.search().await,
+            ProbeRequest { command: Some(ProbeRequest_::Command::Get(_)) } => { },
+            ProbeRequest { command: _ } => {
+                warn!("Unknown command for probes");
+            }
+        }
+
+        let probes = self.readings.iter()
+            .map(|reading| Probe {
+                rom: reading.rom,
+                temperature_celsius: reading.temperature_celsius,
+                status: reading.status,
+            })
+            .collect();
+        self.responses.send(Response { peripheral: Some(ResponsePeripheral::Probe(ProbeResponse { probes })) }).await.unwrap();
+    }
+}
@@ -1,25 +1,93 @@
 use crate::{
-    proto::{ 
-        FanRequest, 
-        FanRequest_, 
-        FanResponse, 
-        Response, 
+    proto::{
+        FanCurve,
+        FanCurve_::Point,
+        FanFault,
+        FanMode,
+        FanRequest,
+        FanRequest_,
+        FanResponse,
+        FanStatus,
+        Response,
         Response_::Peripheral as ResponsePeripheral,
+        SettingKey,
+        TempResponse,
     },
+    settings::{ SettingsSender, Value },
     shell::ResponseSender,
 };
 
+use aux_core::{
+    fan_curve::{ self, Control, Curve },
+    frequency::Held,
+};
 use defmt::{ debug, info, warn };
 use embedded_hal::pwm::SetDutyCycle;
+use futures::{ select_biased, FutureExt };
+
+use rtic_monotonics::Monotonic;
+use rtic_sync::{ channel::Receiver, signal::SignalReader };
+
+use crate::{ fugit::Rate, Duration, Instant, Mono };
+
+pub use aux_core::fan_curve::Climate;
+
+pub const REQUEST_CAPACITY: usize = 2;
+const DEFAULT_SPIN_UP: Duration = Duration::secs(3);
+// Matches the upper bound on tachometer intervals, beyond which Frequency
+// stops reporting them.
+const PULSE_TIMEOUT: Duration = Duration::secs(1);
+// Short enough for a smooth ramp after a kick
+const CONTROL_INTERVAL: Duration = Duration::millis(50);
+const KICK_DUTY: f32 = 1.0;
+
+// How the fan is started from rest, and the range of duty it runs at
+#[derive(Clone, Copy)]
+struct Start {
+    // Requested duty below this turns the fan off
+    cutoff: f32,
+    // Lowest duty the fan reliably keeps turning at
+    min_duty: f32,
+    // Longest time at KICK_DUTY when starting, ended early by tachometer pulses
+    kick: Duration,
+    ramp: Duration,
+}
 
-use rtic_sync::signal::SignalReader;
+#[derive(Clone, Copy)]
+enum Drive {
+    Steady,
+    Kick(Instant),
+    Ramp(Instant),
+}
 
-use crate::fugit::Rate;
+// The temperature task signals None when a read fails
+impl From<&TempResponse> for Climate {
+    fn from(response: &TempResponse) -> Self {
+        Climate {
+            temperature_celsius: response.temperature_celsius,
+            humidity_percent: response.humidity_percent,
+        }
+    }
+}
 
 pub struct Fan<'a, PWM: SetDutyCycle> {
     pwm: PWM,
     responses: ResponseSender,
+    requests: Receiver<'a, FanRequest, REQUEST_CAPACITY>,
     freq_reader: SignalReader<'a, crate::Duration>,
+    climate_reader: SignalReader<'a, Option<Climate>>,
+    rescale: SignalReader<'a, ()>,
+    settings: SettingsSender,
+    curve: Option<Control>,
+    spin_up: Duration,
+    started: Option<Instant>,
+    last_pulse: Option<Instant>,
+    // The interval pulses() last read, for rpm()
+    interval: Held<Instant, Duration>,
+    status: FanStatus,
+    start: Start,
+    drive: Drive,
+    applied_duty: f32,
     curent_duty: f32,
 }
 
@@ -27,22 +95,99 @@ impl <'a, PWM: SetDutyCycle> Fan<'a, PWM> {
     pub fn new(
         pwm: PWM,
         responses: ResponseSender,
+        requests: Receiver<'a, FanRequest, REQUEST_CAPACITY>,
         freq_reader: SignalReader<'a, crate::Duration>,
+        climate_reader: SignalReader<'a, Option<Climate>>,
+        rescale: SignalReader<'a, ()>,
+        settings: SettingsSender,
     ) -> Self {
         Fan {
             pwm,
             responses,
+            requests,
             freq_reader,
+            climate_reader,
+            rescale,
+            settings,
+            curve: None,
+            spin_up: DEFAULT_SPIN_UP,
+            started: None,
+            last_pulse: None,
+            interval: Held::new(PULSE_TIMEOUT),
+            status: FanStatus::Off,
+            start: Start {
+                cutoff: 0.0,
+                min_duty: 0.0,
+                kick: Duration::millis(0),
+                ramp: Duration::millis(0),
+            },
+            drive: Drive::Steady,
+            applied_duty: 0.0,
             curent_duty: 0.0,
         }
     }
 
-    pub async fn process(&mut self, request: FanRequest) {
+    // Set the duty saved from before the last reset
+    pub fn restore(&mut self, duty: f32) {
+        info!("fan restore duty {}", duty);
+        self.set_duty(duty);
+    }
+
+    pub async fn run(&mut self) {
+        let mut control = Mono::now() + CONTROL_INTERVAL;
+        loop {
+            select_biased! {
+                request = self.requests.recv().fuse() => match request {
+                    Ok(request) => self.process(request).await,
+                    Err(_) => break,
+                },
+                climate = self.climate_reader.wait_fresh().fuse() => self.climate(climate),
+                _ = self.rescale.wait_fresh().fuse() => self.apply(self.applied_duty),
+                _ = Mono::delay_until(control).fuse() => {
+                    control += CONTROL_INTERVAL;
+                    self.monitor();
+                    self.drive(Mono::now());
+                },
+            }
+        }
+    }
+
+    async fn process(&mut self, request: FanRequest) {
        match request {
             FanRequest { command: Some(FanRequest_::Command::Set(set)) } => {
                 info!("fan set duty {}", set.duty);
-                self.pwm.set_duty_cycle((set.duty*self.pwm.max_duty_cycle() as f32) as u16).unwrap();
-                self.curent_duty = set.duty;
+                self.curve = None;
+                self.set_duty(set.duty);
+                if self.settings.try_send((SettingKey::FanDuty, Value::Float(set.duty))).is_err() {
+                    warn!("fan duty not saved");
+                }
+            },
+            FanRequest { command: Some(FanRequest_::Command::Curve(curve)) } => {
+                match Control::new(core_curve(&curve)) {
+                    Some(control) => {
+                        info!("fan curve with {} temperature and {} humidity points",
+                            curve.temperature_celsius.len(), curve.humidity_percent.len());
+                        self.curve = Some(control);
+                    },
+                    None => warn!("fan curve points must have increasing, finite inputs and duties from 0 to 1"),
+                }
+            },
+            FanRequest { command: Some(FanRequest_::Command::StallDetection(stall)) } => {
+                self.spin_up = match stall.spin_up_millis {
+                    0 => DEFAULT_SPIN_UP,
+                    millis => Duration::millis(millis as u64),
+                };
+                info!("fan spin up time {}", self.spin_up);
+            },
+            FanRequest { command: Some(FanRequest_::Command::Start(start)) } => {
+                self.start = Start {
+                    cutoff: start.cutoff.clamp(0.0, 1.0),
+                    min_duty: start.min_duty.clamp(0.0, 1.0),
+                    kick: Duration::millis(start.kick_millis as u64),
+                    ramp: Duration::millis(start.ramp_millis as u64),
+                };
+                info!("fan cutoff {} min duty {} kick {} ramp {}",
+                    self.start.cutoff, self.start.min_duty, self.start.kick, self.start.ramp);
             },
             FanRequest { command: Some(FanRequest_::Command::Get(_)) } => { },
             FanRequest { command: _ } => {
@@ -54,15 +199,145 @@ impl <'a, PWM: SetDutyCycle> Fan<'a, PWM> {
         self.responses.send(response).await.unwrap();
     }
 
+    fn climate(&mut self, climate: Option<Climate>) {
+        let duty = match &mut self.curve {
+            Some(control) => control.climate(climate, self.curent_duty),
+            None => return,
+        };
+
+        if duty != self.curent_duty {
+            debug!("fan curve duty {}", duty);
+            self.set_duty(duty);
+        }
+    }
+
+    // Track tachometer pulses against the commanded duty, and tell the client
+    // when the fan enters or leaves a fault status. The control loop mustn't
+    // wait for a client that isn't reading responses, so when the response
+    // channel is full the event is dropped.
+    fn monitor(&mut self) {
+        self.pulses();
+        let status = self.status(Mono::now());
+        if status != self.status {
+            let fault = faulty(status) || faulty(self.status);
+            if faulty(status) {
+                warn!("fan fault: {} at duty {}", status.0, self.curent_duty);
+            }
+            self.status = status;
+            if fault {
+                let event = Response { peripheral: Some(ResponsePeripheral::FanFault(FanFault {
+                    status,
+                    duty: self.curent_duty,
+                })) };
+                if self.responses.try_send(event).is_err() {
+                    warn!("fan fault event dropped: {}", status.0);
+                }
+            }
+        }
+    }
+
+    // Frequency clears the interval when the pulses stop, so any value is
+    // evidence the fan turned since the last read. Reading takes the value,
+    // so it's kept for rpm().
+    fn pulses(&mut self) {
+        let interval = self.freq_reader.try_read();
+        let now = Mono::now();
+        if interval.is_some() && self.started.is_some() {
+            self.last_pulse = Some(now);
+        }
+        self.interval.update(now, interval);
+    }
+
+    fn status(&self, now: Instant) -> FanStatus {
+        match (self.started, self.last_pulse) {
+            (None, _) => FanStatus::Off,
+            (Some(_), Some(pulse)) if now - pulse < PULSE_TIMEOUT => FanStatus::Running,
+            (Some(started), _) if now - started < self.spin_up => FanStatus::SpinningUp,
+            (Some(_), Some(_)) => FanStatus::Stalled,
+            (Some(_), None) => FanStatus::TachDisconnected,
+        }
+    }
+
+    fn set_duty(&mut self, duty: f32) {
+        let duty = match duty.clamp(0.0, 1.0) {
+            duty if duty < self.start.cutoff || duty == 0.0 => 0.0,
+            duty => duty.max(self.start.min_duty),
+        };
+        match (self.curent_duty > 0.0, duty > 0.0) {
+            (false, true) => {
+                let now = Mono::now();
+                self.started = Some(now);
+                self.last_pulse = None;
+                if self.start.kick.ticks() > 0 {
+                    debug!("fan kick");
+                    self.drive = Drive::Kick(now);
+                    self.apply(KICK_DUTY);
+                } else {
+                    self.apply(duty);
+                }
+            },
+            (true, false) => {
+                self.started = None;
+                self.drive = Drive::Steady;
+                self.apply(0.0);
+            },
+            // a kick or ramp in progress heads for the new duty
+            _ => if let Drive::Steady = self.drive {
+                self.apply(duty);
+            },
+        }
+        self.curent_duty = duty;
+    }
+
+    // Move a starting fan from the kick, down the ramp, to the requested duty
+    fn drive(&mut self, now: Instant) {
+        match self.drive {
+            Drive::Steady => {},
+            Drive::Kick(kicked) => {
+                if self.last_pulse.is_some() || now - kicked >= self.start.kick {
+                    debug!("fan kick complete, spinning: {}", self.last_pulse.is_some());
+                    self.drive = Drive::Ramp(now);
+                }
+            },
+            Drive::Ramp(ramped) => {
+                let elapsed = now - ramped;
+                if elapsed >= self.start.ramp {
+                    self.drive = Drive::Steady;
+                    self.apply(self.curent_duty);
+                } else {
+                    let progress = elapsed.to_micros() as f32 / self.start.ramp.to_micros() as f32;
+                    self.apply(KICK_DUTY + (self.curent_duty - KICK_DUTY)*progress);
+                }
+            },
+        }
+    }
+
+    // The PWM frequency may change, so scale to max_duty_cycle every time
+    fn apply(&mut self, duty: f32) {
+        self.pwm.set_duty_cycle((duty*self.pwm.max_duty_cycle() as f32) as u16).unwrap();
+        self.applied_duty = duty;
+    }
+
+    fn mode(&self) -> FanMode {
+        match &self.curve {
+            None => FanMode::Manual,
+            Some(control) if control.failsafe() => FanMode::Failsafe,
+            Some(_) => FanMode::Curve,
+        }
+    }
+
     fn response(&mut self) -> Response {
         Response { peripheral: Some(ResponsePeripheral::Fan(FanResponse {
             duty: self.curent_duty,
             rpm: self.rpm(),
+            mode: self.mode(),
+            status: self.status,
         })) }
     }
 
     fn rpm(&mut self) -> i32 {
-        match self.freq_reader.try_read() {
+        self.pulses();
+        match self.interval.get(Mono::now()) {
             Some(duration) => {
                 debug!("fan pulse duration: {}", duration);
                 let rate: Rate<u64, 1, 1> = duration.into_rate();
@@ -78,4 +353,18 @@ impl <'a, PWM: SetDutyCycle> Fan<'a, PWM> {
     }
 }
 
+fn faulty(status: FanStatus) -> bool {
+    status == FanStatus::Stalled || status == FanStatus::TachDisconnected
+}
 
+fn core_curve(curve: &FanCurve) -> Curve {
+    let points = |points: &[Point]| points.iter()
+        .map(|point| fan_curve::Point { input: point.input, duty: point.duty })
+        .collect();
+    Curve {
+        temperature_celsius: points(&curve.temperature_celsius),
+        humidity_percent: points(&curve.humidity_percent),
+        hysteresis_celsius: curve.hysteresis_celsius,
+        failsafe_errors: curve.failsafe_errors,
+    }
+}
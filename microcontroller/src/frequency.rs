@@ -1,47 +1,14 @@
-use core::ops::{ Add, Div, Mul };
 use defmt::{ trace };
 
+use aux_core::frequency::Interval;
+pub use aux_core::frequency::{ Bounds, Proportion, Ratio, Value };
 use rtic_monotonics::Monotonic;
 use rtic_sync::signal::{ SignalWriter };
 use hal::{ gpio::{ Edge, ExtiPin }, pac::EXTI, syscfg::SysCfg};
 
-pub trait Proportion<P> : Add<P, Output = P> + Copy + Clone {}
-pub trait Value<V, P>: 
-    Add<V, Output=V> + 
-    Div<P, Output = V> + 
-    Mul<P, Output = V> + 
-    Div<P, Output = V> +
-    Ord {}
-
-#[derive(Clone, Copy)]
-pub struct Ratio<R: Proportion<R>> (pub R, pub R);
-
-impl <R: Proportion<R>> Ratio<R> {
-    fn apply<V: Value<V, R>>(self, a: V, b: V) -> V {
-        let (ar, br) = (self.0, self.1);
-        (a*ar + b*br)/(ar + br)
-    }
-}
-#[derive(Clone, Copy)]
-pub struct Bounds<V> (pub V, pub V);
-
-impl <V: Ord> Bounds<V> {
-    fn apply(self, x: V) -> Option<V> {
-        if x > self.0 && x < self.1 {
-            Some(x)
-        } else {
-            None
-        }
-    }
-}
-
-
 pub struct Frequency<'a, PIN: ExtiPin, CLOCK: Monotonic, R: Proportion<R>> {
     pin: PIN,
-    filter: Ratio<R>,
-    bounds: Bounds<CLOCK::Duration>,
-    previous: Option<CLOCK::Instant>,
-    interval: Option<CLOCK::Duration>,
+    interval: Interval<CLOCK::Instant, CLOCK::Duration, R>,
     writer: SignalWriter<'a, CLOCK::Duration>,
 }
 
@@ -60,23 +27,13 @@ where
         pin.make_interrupt_source(syscfg);
         pin.trigger_on_edge(exti, Edge::Rising);
         pin.enable_interrupt(exti);
-        Frequency { pin, filter, bounds, writer, previous: None, interval: None }
+        Frequency { pin, interval: Interval::new(filter, bounds), writer }
     }
 
     pub fn edge(&mut self) {
-        let now = CLOCK::now();
-        self.interval = match (self.previous, self.interval) {
-            (Some(instant), Some(duration)) => {
-                let next: CLOCK::Duration = now - instant;
-                self.bounds.apply(self.filter.apply(duration, next))
-            },
-            (None, Some(duration)) => self.bounds.apply(duration),
-            (Some(instant), None) => self.bounds.apply(now - instant),
-            (None,None) => None
-        };
-        self.previous = Some(now);
+        let interval = self.interval.edge(CLOCK::now());
         self.pin.clear_interrupt_pending_bit();
-        match self.interval {
+        match interval {
             Some(duration) => {
                 trace!("edge: writing"); 
                 self.writer.write(duration); 
@@ -0,0 +1,175 @@
+use crate::{
+    proto::{
+        HealthRequest,
+        HealthRequest_,
+        HealthResponse,
+        ResetCause,
+        Response,
+        Response_::Peripheral as ResponsePeripheral,
+        SupplyWarning,
+    },
+    shell::ResponseSender,
+    Duration,
+    Mono,
+};
+
+use defmt::{ info, warn };
+use futures::{ select_biased, FutureExt };
+use hal::{
+    adc::{ config::SampleTime, Adc, Temperature, Vref },
+    pac::{ ADC1, RCC },
+    signature::{ VrefCal, VtempCal110, VtempCal30 },
+};
+use rtic_monotonics::Monotonic;
+use rtic_sync::channel::Receiver;
+
+/*
+Samples the MCU's internal temperature sensor and VREFINT. VREFINT is a
+fixed ~1.21V, and its reading at VDDA = 3.3V is calibrated in the factory, so
+VDDA = 3.3V * VREFINT_CAL / reading. The temperature sensor is calibrated at
+30C and 110C, also at 3.3V, so its reading is scaled to 3.3V first.
+
+VDDA comes from the board's 3.3V regulator, so it sags when the USB supply
+does. The brown-out reset is off by default, so a deep enough sag resets the
+MCU at ~1.7V, and the reset cause tells that apart from other resets.
+ */
+pub const REQUEST_CAPACITY: usize = 2;
+const INTERVAL: Duration = Duration::secs(1);
+const CALIBRATION_VOLTS: f32 = 3.3;
+// Warn below LOW, until VDDA recovers above HIGH
+const SUPPLY_LOW: f32 = 3.1;
+const SUPPLY_HIGH: f32 = 3.2;
+
+// RCC_CSR reset flags, and the bit that clears them
+const LPWRRSTF: u32 = 1 << 31;
+const WWDGRSTF: u32 = 1 << 30;
+const IWDGRSTF: u32 = 1 << 29;
+const SFTRSTF: u32 = 1 << 28;
+const PORRSTF: u32 = 1 << 27;
+const PINRSTF: u32 = 1 << 26;
+const BORRSTF: u32 = 1 << 25;
+const RMVF: u32 = 1 << 24;
+
+// Why the MCU last reset. Call once at boot, as it clears the flags for next time.
+pub fn reset_cause() -> ResetCause {
+    // Safety: only reads and clears the reset flags
+    let rcc = unsafe { &*RCC::ptr() };
+    let flags = rcc.csr().read().bits();
+    rcc.csr().modify(|r, w| unsafe { w.bits(r.bits() | RMVF) });
+
+    // A power on reset sets the brown-out flag too, and every reset pulses the
+    // reset pin, so the order matters
+    let cause = [
+        (LPWRRSTF, ResetCause::LowPower),
+        (WWDGRSTF, ResetCause::Watchdog),
+        (IWDGRSTF, ResetCause::Watchdog),
+        (SFTRSTF, ResetCause::Software),
+        (PORRSTF, ResetCause::PowerOn),
+        (BORRSTF, ResetCause::BrownOut),
+        (PINRSTF, ResetCause::Pin),
+    ].iter()
+        .find(|(flag, _)| flags & flag != 0)
+        .map(|(_, cause)| *cause)
+        .unwrap_or(ResetCause::Unknown);
+    info!("reset cause {}", cause.0);
+    cause
+}
+
+pub struct Health<'a> {
+    adc: Adc<ADC1>,
+    requests: Receiver<'a, HealthRequest, REQUEST_CAPACITY>,
+    responses: ResponseSender,
+    reset_cause: ResetCause,
+    temperature_celsius: f32,
+    vdda_volts: f32,
+    min_vdda_volts: f32,
+    supply_low: bool,
+}
+
+impl <'a> Health<'a> {
+    pub fn new(
+        mut adc: Adc<ADC1>,
+        requests: Receiver<'a, HealthRequest, REQUEST_CAPACITY>,
+        responses: ResponseSender,
+        reset_cause: ResetCause,
+    ) -> Self {
+        adc.enable_temperature_and_vref();
+        Health {
+            adc,
+            requests,
+            responses,
+            reset_cause,
+            temperature_celsius: 0.0,
+            vdda_volts: CALIBRATION_VOLTS,
+            min_vdda_volts: CALIBRATION_VOLTS,
+            supply_low: false,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            self.sample();
+            select_biased! {
+                request = self.requests.recv().fuse() => match request {
+                    Ok(request) => self.process(request).await,
+                    Err(_) => break,
+                },
+                _ = Mono::delay(INTERVAL).fuse() => {},
+            }
+        }
+    }
+
+    // Sampling mustn't wait for a client that isn't reading responses, so when
+    // the response channel is full a supply warning is dropped.
+    fn sample(&mut self) {
+        // The temperature sensor needs a sampling time of at least 10uS
+        let vref = self.adc.convert(&Vref, SampleTime::Cycles_480);
+        let temperature = self.adc.convert(&Temperature, SampleTime::Cycles_480);
+        if vref == 0 {
+            return;
+        }
+
+        self.vdda_volts = CALIBRATION_VOLTS * VrefCal::get().read() as f32 / vref as f32;
+        self.min_vdda_volts = self.min_vdda_volts.min(self.vdda_volts);
+
+        let (cal30, cal110) = (VtempCal30::get().read() as f32, VtempCal110::get().read() as f32);
+        let calibrated = temperature as f32 * self.vdda_volts / CALIBRATION_VOLTS;
+        self.temperature_celsius = 30.0 + (110.0 - 30.0) * (calibrated - cal30) / (cal110 - cal30);
+
+        let supply_low = match self.supply_low {
+            false => self.vdda_volts < SUPPLY_LOW,
+            true => self.vdda_volts < SUPPLY_HIGH,
+        };
+        if supply_low != self.supply_low {
+            self.supply_low = supply_low;
+            match supply_low {
+                true => warn!("supply low: VDDA {}V", self.vdda_volts),
+                false => info!("supply recovered: VDDA {}V", self.vdda_volts),
+            }
+            let event = Response { peripheral: Some(ResponsePeripheral::SupplyWarning(SupplyWarning {
+                vdda_volts: self.vdda_volts,
+                low: supply_low,
+            })) };
+            if self.responses.try_send(event).is_err() {
+                warn!("supply warning dropped: low {}", supply_low);
+            }
+        }
+    }
+
+    async fn process(&mut self, request: HealthRequest) {
+        match request {
+            HealthRequest { command: Some(HealthRequest_::Command::Get(_)) } => { },
+            HealthRequest { command: _ } => {
+                warn!("Unknown command for health");
+            }
+        }
+
+        self.responses.send(Response { peripheral: Some(ResponsePeripheral::Health(HealthResponse {
+            mcu_temperature_celsius: self.temperature_celsius,
+            vdda_volts: self.vdda_volts,
+            min_vdda_volts: self.min_vdda_volts,
+            supply_low: self.supply_low,
+            reset_cause: self.reset_cause,
+        })) }).await.unwrap();
+    }
+}
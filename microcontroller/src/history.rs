@@ -0,0 +1,110 @@
+use crate::{
+    proto::{ TempHistory, TempResponse, TempSample },
+    Duration,
+    Instant,
+};
+
+use aux_core::statistics::StatsAccumulator;
+use heapless::{ Deque, Vec };
+use micromath::F32Ext;
+
+/*
+The temperature and humidity over the last day, one sample a minute. Each
+sample keeps the min, mean and max of the readings during its minute, in
+tenths, so a day of samples fits in ~20K of RAM. A minute without a good
+reading gets a sample with no reads, so the samples stay a minute apart.
+ */
+pub const SAMPLES: usize = 24 * 60;
+// Samples in each response
+pub const PAGE: usize = 30;
+const SAMPLE_SECONDS: u32 = 60;
+const SAMPLE_INTERVAL: Duration = Duration::secs(SAMPLE_SECONDS as u64);
+
+#[derive(Clone, Copy)]
+struct Sample {
+    temperature: [i16; 3],
+    humidity: [i16; 3],
+    reads: u8,
+}
+
+pub struct History {
+    samples: Deque<Sample, SAMPLES>,
+    temperature: StatsAccumulator<u32, f32>,
+    humidity: StatsAccumulator<u32, f32>,
+    // The start of the minute being accumulated
+    start: Option<Instant>,
+}
+
+impl History {
+    pub const fn new() -> Self {
+        History {
+            samples: Deque::new(),
+            temperature: StatsAccumulator::new(),
+            humidity: StatsAccumulator::new(),
+            start: None,
+        }
+    }
+
+    // Call after every read, good or not, to keep the clock going
+    pub fn add(&mut self, now: Instant, reading: Option<&TempResponse>) {
+        let mut start = *self.start.get_or_insert(now);
+        while now >= start + SAMPLE_INTERVAL {
+            self.sample();
+            start += SAMPLE_INTERVAL;
+        }
+        self.start = Some(start);
+
+        if let Some(reading) = reading {
+            self.temperature.add(reading.temperature_celsius);
+            self.humidity.add(reading.humidity_percent);
+        }
+    }
+
+    fn sample(&mut self) {
+        if self.samples.is_full() {
+            self.samples.pop_front();
+        }
+        let sample = Sample {
+            temperature: tenths(&self.temperature),
+            humidity: tenths(&self.humidity),
+            reads: self.temperature.count() as u8,
+        };
+        let _ = self.samples.push_back(sample);
+        self.temperature = StatsAccumulator::new();
+        self.humidity = StatsAccumulator::new();
+    }
+
+    // Up to a page of samples, newest first, starting this many samples back
+    // from the newest
+    pub fn page(&self, now: Instant, start: u32) -> TempHistory {
+        let samples: Vec<TempSample, PAGE> = self.samples.iter()
+            .rev()
+            .skip(start as usize)
+            .take(PAGE)
+            .map(|sample| TempSample {
+                temperature_min: sample.temperature[0] as i32,
+                temperature_mean: sample.temperature[1] as i32,
+                temperature_max: sample.temperature[2] as i32,
+                humidity_min: sample.humidity[0] as i32,
+                humidity_mean: sample.humidity[1] as i32,
+                humidity_max: sample.humidity[2] as i32,
+                reads: sample.reads as u32,
+            })
+            .collect();
+
+        TempHistory {
+            total: self.samples.len() as u32,
+            start,
+            interval_seconds: SAMPLE_SECONDS,
+            age_seconds: self.start.map(|start| (now - start).to_secs() as u32).unwrap_or(0),
+            samples,
+        }
+    }
+}
+
+fn tenths(stats: &StatsAccumulator<u32, f32>) -> [i16; 3] {
+    match stats.count() {
+        0 => [0; 3],
+        _ => [stats.min(), stats.mean(), stats.max()].map(|value| (value * 10.0).round() as i16),
+    }
+}
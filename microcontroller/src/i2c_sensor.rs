@@ -0,0 +1,85 @@
+use crate::{
+    bme280::{ self, Bme280 },
+    dht11::{ ReadError, SensorError },
+    proto::TempResponse,
+    sht3x::{ self, Sht3x },
+    Duration,
+};
+
+use defmt::{ info, warn };
+use embedded_hal::i2c::I2c;
+
+const INTERVAL: Duration = Duration::secs(1);
+
+enum Driver {
+    Bme280(Bme280),
+    Sht3x(Sht3x),
+}
+
+// Whichever supported sensor was found on the I2C bus at boot
+pub struct I2cSensor<I2C> {
+    i2c: I2C,
+    driver: Driver,
+}
+
+impl <I2C: I2c> I2cSensor<I2C> {
+    pub fn detect(mut i2c: I2C) -> Option<Self> {
+        let bme280 = bme280::ADDRESSES.iter()
+            .find_map(|address| Bme280::detect(&mut i2c, *address))
+            .map(Driver::Bme280);
+        let driver = bme280.or_else(|| sht3x::ADDRESSES.iter()
+            .find_map(|address| Sht3x::detect(&mut i2c, *address))
+            .map(Driver::Sht3x));
+        match driver {
+            Some(driver) => {
+                let sensor = I2cSensor { i2c, driver };
+                info!("I2C sensor {}", sensor.name());
+                Some(sensor)
+            },
+            None => {
+                info!("No I2C sensor");
+                None
+            },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.driver {
+            Driver::Bme280(_) => "bme280",
+            Driver::Sht3x(_) => "sht3x",
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        INTERVAL
+    }
+
+    pub async fn read(&mut self) -> Result<TempResponse, SensorError> {
+        match &self.driver {
+            Driver::Bme280(bme280) => match bme280.read(&mut self.i2c).await {
+                Ok(measurement) => Ok(TempResponse {
+                    temperature_celsius: measurement.temperature_celsius,
+                    humidity_percent: measurement.humidity_percent,
+                    pressure_hpa: measurement.pressure_hpa,
+                    ..TempResponse::default()
+                }),
+                Err(_) => {
+                    warn!("BME280 bus error");
+                    Err(SensorError::Bus)
+                },
+            },
+            Driver::Sht3x(sht3x) => match sht3x.read(&mut self.i2c).await {
+                Ok(measurement) => Ok(TempResponse {
+                    temperature_celsius: measurement.temperature_celsius,
+                    humidity_percent: measurement.humidity_percent,
+                    ..TempResponse::default()
+                }),
+                Err(sht3x::Error::Checksum) => Err(SensorError::Read(ReadError::Checksum)),
+                Err(sht3x::Error::I2c(_)) => {
+                    warn!("SHT3x bus error");
+                    Err(SensorError::Bus)
+                },
+            },
+        }
+    }
+}
@@ -1,39 +1,123 @@
 use crate::{
-    proto::{ 
-        LightRequest, 
-        LightRequest_, 
-        LightResponse, 
-        Response, 
+    proto::{
+        LightRequest,
+        LightRequest_,
+        LightResponse,
+        Response,
         Response_::Peripheral as ResponsePeripheral,
+        SettingKey,
     },
+    settings::{ SettingsSender, Value },
     shell::{ ResponseSender },
+    Duration,
+    Instant,
+    Mono,
 };
 
-use defmt::{ info, warn };
+use defmt::{ debug, info, warn };
 use embedded_hal::pwm::SetDutyCycle;
+use futures::{ select_biased, FutureExt };
+use micromath::F32Ext;
+use rtic_monotonics::Monotonic;
+use rtic_sync::{ channel::Receiver, signal::SignalReader };
 
+pub const REQUEST_CAPACITY: usize = 2;
+// Duty in requests is perceived brightness, PWM duty is brightness^GAMMA
+const GAMMA: f32 = 2.2;
+const FADE_STEP: Duration = Duration::millis(20);
 
-pub struct Light<PWM: SetDutyCycle> {
+#[derive(Clone, Copy)]
+struct Fade {
+    from: f32,
+    start: Instant,
+    duration: Duration,
+}
+
+pub struct Light<'a, PWM: SetDutyCycle> {
     pwm: PWM,
     responses: ResponseSender,
+    requests: Receiver<'a, LightRequest, REQUEST_CAPACITY>,
+    rescale: SignalReader<'a, ()>,
+    settings: SettingsSender,
+    fade: Option<Fade>,
+    target_duty: f32,
     curent_duty: f32,
 }
 
-impl <PWM: SetDutyCycle> Light<PWM> {
-    pub fn new(pwm: PWM, responses: ResponseSender) -> Self {
+impl <'a, PWM: SetDutyCycle> Light<'a, PWM> {
+    pub fn new(
+        pwm: PWM,
+        responses: ResponseSender,
+        requests: Receiver<'a, LightRequest, REQUEST_CAPACITY>,
+        rescale: SignalReader<'a, ()>,
+        settings: SettingsSender,
+    ) -> Self {
         Light {
             pwm,
             responses,
+            requests,
+            rescale,
+            settings,
+            fade: None,
+            target_duty: 0.0,
             curent_duty: 0.0,
         }
     }
 
-    pub async fn process(&mut self, request: LightRequest) {
+    // Set the duty saved from before the last reset
+    pub fn restore(&mut self, duty: f32) {
+        info!("Light restore duty {}", duty);
+        self.target_duty = duty.clamp(0.0, 1.0);
+        self.set_duty(self.target_duty);
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            let request = match self.fade {
+                Some(_) => select_biased! {
+                    request = self.requests.recv().fuse() => Some(request),
+                    _ = Mono::delay(FADE_STEP).fuse() => {
+                        self.step(Mono::now());
+                        None
+                    },
+                },
+                None => select_biased! {
+                    request = self.requests.recv().fuse() => Some(request),
+                    _ = self.rescale.wait_fresh().fuse() => {
+                        // the PWM frequency changed, so the duty needs scaling again
+                        self.set_duty(self.curent_duty);
+                        None
+                    },
+                },
+            };
+
+            match request {
+                Some(Ok(request)) => self.process(request).await,
+                Some(Err(_)) => break,
+                None => {},
+            }
+        }
+    }
+
+    async fn process(&mut self, request: LightRequest) {
        match request {
             LightRequest { command: Some(LightRequest_::Command::Set(set)) } => {
-                info!("Light set duty {}", set.duty);
-                self.pwm.set_duty_cycle((set.duty*self.pwm.max_duty_cycle() as f32) as u16).unwrap();
-                self.curent_duty = set.duty;
+                info!("Light set duty {} over {}ms", set.duty, set.transition_millis);
+                self.target_duty = set.duty.clamp(0.0, 1.0);
+                if self.settings.try_send((SettingKey::LightDuty, Value::Float(self.target_duty))).is_err() {
+                    warn!("Light duty not saved");
+                }
+                if set.transition_millis > 0 {
+                    // a fade in progress continues from wherever it has got to
+                    self.fade = Some(Fade {
+                        from: self.curent_duty,
+                        start: Mono::now(),
+                        duration: Duration::millis(set.transition_millis as u64),
+                    });
+                } else {
+                    self.fade = None;
+                    self.set_duty(self.target_duty);
+                }
             },
             LightRequest { command: Some(LightRequest_::Command::Get(_)) } => { },
             LightRequest { command: _ } => {
@@ -45,9 +129,30 @@ impl <PWM: SetDutyCycle> Light<PWM> {
         self.responses.send(response).await.unwrap();
     }
 
+    fn step(&mut self, now: Instant) {
+        if let Some(fade) = self.fade {
+            let elapsed = now - fade.start;
+            if elapsed >= fade.duration {
+                debug!("Light fade complete");
+                self.fade = None;
+                self.set_duty(self.target_duty);
+            } else {
+                let progress = elapsed.to_micros() as f32 / fade.duration.to_micros() as f32;
+                self.set_duty(fade.from + (self.target_duty - fade.from)*progress);
+            }
+        }
+    }
+
+    fn set_duty(&mut self, duty: f32) {
+        let pwm_duty = if duty > 0.0 { duty.powf(GAMMA) } else { 0.0 };
+        self.pwm.set_duty_cycle((pwm_duty*self.pwm.max_duty_cycle() as f32) as u16).unwrap();
+        self.curent_duty = duty;
+    }
+
     fn response(&mut self) -> Response {
         Response { peripheral: Some(ResponsePeripheral::Light(LightResponse {
-            duty: self.curent_duty,
+            duty: self.target_duty,
+            current_duty: self.curent_duty,
         })) }
     }
 }
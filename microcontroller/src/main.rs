@@ -3,15 +3,24 @@
 #![no_std]
 #[allow(non_snake_case)]
 
+mod bme280;
 mod dht11;
+mod ds18b20;
 mod fan;
 mod frequency;
+mod health;
+mod history;
+mod i2c_sensor;
 mod light;
 mod network;
+mod onewire;
+mod peripheral;
+mod pwm;
 mod seed;
 mod serial;
+mod settings;
+mod sht3x;
 mod shell;
-mod statistics;
 
 pub mod proto {
     #![allow(clippy::all)]
@@ -26,13 +35,17 @@ use defmt_rtt as _;
 
 use cortex_m::asm::delay;
 use hal::{
-    adc::{config::AdcConfig, Adc}, 
+    adc::{config::{ AdcConfig, Clock }, Adc}, 
     gpio::{
-        gpioa::{ PA1, PA2 }, 
+        gpioa::PA1, 
         gpioc::{ 
+            PC0, // used with PB0, to clear EXTI0 interrupts
+            #[cfg(not(feature = "dht-capture"))]
             PC2, // used for with PA2, to clear EXTI2 interrupts */ 
+            PC3, // used with PB3, to clear EXTI3 interrupts
             PC13, // led 
         },
+        ErasedPin,
         Input,
         Output, 
         OpenDrain,
@@ -54,38 +67,83 @@ use rtic_monotonics::{
 use smoltcp::{
     iface::SocketStorage, wire::DhcpOption,
 };
+use aux_core::{ hostname, ipv4, mdns };
 
 
 const MONO_RATE: u32 = 1_000_000;
 stm32_tim2_monotonic!(Mono, MONO_RATE);
-impl frequency::Proportion<u32> for u32 {}
 type Duration = fugit::Duration<u64, 1, MONO_RATE>;
 type Instant = fugit::Instant<u64, 1, MONO_RATE>;
-impl frequency::Value<Duration, u32> for Duration {} 
+#[cfg(not(feature = "light-tim4"))]
+type LightPwm = PwmChannel<TIM3, 1>;
+#[cfg(feature = "light-tim4")]
+type LightPwm = PwmChannel<hal::pac::TIM4, 0>;
 
 const CHANNEL_CAPACITY: usize = 2*network::MTU as usize;
 const CHANNELS: usize = 2;
-const SOCKETS: usize = CHANNELS + 1; // +1 for the dhcp socket
+const SOCKETS: usize = CHANNELS + 4; // +4 for the DHCP client or server, mDNS, SLAAC and discovery
+
+// The DHT sensors, numbered in this order in requests. Sensor 0 is on PA2
+// (EXTI2), and drives the fan curve and the history. Sensor 1 is on PB0 (EXTI0)
+// and sensor 2 on PB3 (EXTI3). The DHT11 module reads DHT22/AM2302 sensors too.
+const TEMP_SENSORS: [(&str, dht11::Sensor); TEMP_COUNT] = [
+    ("enclosure", dht11::Sensor::Dht11),
+    ("outside", dht11::Sensor::Dht11),
+    ("electronics", dht11::Sensor::Dht11),
+];
+const TEMP_COUNT: usize = 3;
+// The DHT sensors fitted, bit 0 for sensor 0 and so on, unless the settings
+// say otherwise. Sensors that aren't fitted are never read.
+const DHT_FITTED: u32 = 0b001;
+// The DHT sensors, and an I2C sensor if there is one
+const SENSOR_COUNT: usize = TEMP_COUNT + 1;
+// BME280 or SHT3x, on PB8 (SCL) and PB9 (SDA)
+type I2cBus = hal::i2c::I2c<hal::pac::I2C1>;
+const I2C_FREQUENCY: hal::time::Hertz = hal::time::Hertz::kHz(100);
+type TempPin = ErasedPin<Output<OpenDrain>>;
+// The 1-Wire bus for DS18B20 probes
+type ProbePin = hal::gpio::gpiob::PB5<Output<OpenDrain>>;
+// Read intervals without a read before the temperature is reported stale
+const STALE_READS: u32 = 3;
+
+// How the address is chosen, unless the IPv4 settings say otherwise. For a
+// static address
+// ipv4::Mode::Static(ipv4::Static {
+//     address: Ipv4Cidr::new(Ipv4Address::new(192, 168, 7, 2), 24),
+//     gateway: Some(Ipv4Address::new(192, 168, 7, 1)),
+// })
+// or to serve DHCP on the USB link
+// ipv4::Mode::Server(aux_core::dhcp_server::ADDRESS)
+const IPV4: ipv4::Mode = ipv4::Mode::Dhcp;
 
 const DHCP_HOST_NAME: u8 = 12;
-const DHCP_OPTIONS: &[DhcpOption<'static>] = &[
-    DhcpOption { kind: DHCP_HOST_NAME, data: b"ant-auxiliary" },
-];
+// Unless the settings say otherwise
+const GRBL_PORT: u16 = 1337;
+const SHELL_PORT: u16 = 1338;
 
 #[rtic::app(device = hal::pac, dispatchers = [ EXTI4, EXTI9_5, EXTI15_10 ])]
 mod app {
 
     use micropb::MessageEncode;
     use proto::{ 
+        DeviceAnnouncement,
+        DeviceResponse,
         FanRequest,
+        HealthRequest,
+        ProbeRequest,
+        PwmRequest,
         Request, 
+        SettingKey,
+        SettingsRequest,
         Request_::Peripheral as RequestPeripheral, 
         Response, 
         Response_::Peripheral as ResponsePeripheral, 
+        ServicePort,
     };
     use rtic_sync::{
         make_channel, 
         make_signal,
+        signal::SignalWriter,
     };
 
     use pbstreams::{
@@ -100,10 +158,12 @@ mod app {
         },
     };
 
+    use core::fmt::Write;
+
     use crate::{ 
         frequency::{ Bounds, Frequency, Ratio }, 
         network::SendChannel, 
-        proto::{ LightRequest, TempRequest, TempResponse },
+        proto::{ LightRequest, TempRequest, TempRequest_ },
         shell::TaskResponses,
     };
 
@@ -136,7 +196,10 @@ mod app {
     struct Shared {
         usb: UsbDevice<'static, UsbBusType>,
         network: NetworkStack<'static, Mono>,
-        temp: Option<TempResponse>,
+        temp: heapless::Vec<dht11::Latest, SENSOR_COUNT>,
+        climate_writer: SignalWriter<'static, Option<fan::Climate>>,
+        temp_diagnostics: [dht11::Diagnostics; TEMP_COUNT],
+        temp_history: &'static mut history::History,
     }
 
     #[local]
@@ -145,15 +208,28 @@ mod app {
         grbl_rx: serial::RxTask<'static>,
         network_recv: [RecvChannel<'static, CHANNEL_CAPACITY>; CHANNELS],
         request_decoder: RequestDecoder,
+        registry: peripheral::Registry<'static, Request, { peripheral::PERIPHERALS }>,
+        request_responses: shell::ResponseSender,
+        device: DeviceResponse,
         response_receiver: ChannelStream<'static, Response, { shell::MESSAGE_CAPACITY }>,
         response_encoder: ResponseEncoder,
         network_send: [SendChannel<'static, CHANNEL_CAPACITY>; CHANNELS],
         fan: fan::Fan<'static, PwmChannel<TIM3, 0>>,
         fan_freq: Frequency<'static, PA1<Input>, Mono, u32>,
-        light: light::Light<PwmChannel<TIM3, 1>>,
+        light: light::Light<'static, LightPwm>,
+        health: health::Health<'static>,
+        probes: ds18b20::Probes<'static, ProbePin>,
+        pwm: pwm::Pwm,
+        settings: settings::SettingsTask<'static>,
         led: PC13<Output>,
-        temp_reader: dht11::Dht11Reader<'static, PA2<Output<OpenDrain>>>,
-        temp_writer: dht11::Dht11Writer<'static, PC2>,
+        temp_readers: [dht11::Dht11Reader<'static, TempPin>; TEMP_COUNT],
+        // The fan curve and history follow DHT sensor 0 unless there's an I2C sensor
+        dht_climate: bool,
+        i2c_sensor: Option<i2c_sensor::I2cSensor<I2cBus>>,
+        #[cfg(not(feature = "dht-capture"))]
+        temp_writer_0: dht11::Dht11Writer<'static, PC2>,
+        temp_writer_1: dht11::Dht11Writer<'static, PC0>,
+        temp_writer_2: dht11::Dht11Writer<'static, PC3>,
         temp_responses: TaskResponses<()>,
     }
 
@@ -165,7 +241,32 @@ mod app {
         shell_channel_storage: NetworkChannelStorage<CHANNEL_CAPACITY> = NetworkChannelStorage::new(),
         ethernet_in_buffer: [u8; 2048] = [0; 2048],
         ethernet_out_buffer: [u8; 2048] = [0; 2048],
-        socket_storage: [SocketStorage<'static>; SOCKETS] = [SocketStorage::EMPTY; SOCKETS]])]
+        socket_storage: [SocketStorage<'static>; SOCKETS] = [SocketStorage::EMPTY; SOCKETS],
+        dhcp_server_storage: network::DhcpServerStorage = network::DhcpServerStorage::new(),
+        mdns_storage: network::MdnsStorage = network::MdnsStorage::new(),
+        slaac_storage: network::SlaacStorage = network::SlaacStorage::new(),
+        discovery_storage: network::DiscoveryStorage = network::DiscoveryStorage::new(),
+        // The host name, sent to DHCP servers in the options
+        host_name: hostname::HostName = hostname::HostName::new(),
+        dhcp_options: [DhcpOption<'static>; 1] = [DhcpOption { kind: DHCP_HOST_NAME, data: &[] }],
+        // The TXT record mDNS advertises with each service
+        device_txt: heapless::String<32> = heapless::String::new(),
+        mdns_txt: [&'static str; 1] = [""],
+        // Advertised by mDNS and discovery, for hosts to find without knowing
+        // the address, with the ports from the settings
+        services: [mdns::Service<'static>; 2] = [
+            mdns::Service { kind: "_ant-aux-rpc._tcp", port: SHELL_PORT },
+            mdns::Service { kind: "_grbl._tcp", port: GRBL_PORT },
+        ],
+        temp_history: history::History = history::History::new(),
+        // The peripherals requests are routed to, each registered in init
+        fan_peripheral: Option<peripheral::Channel<FanRequest, { fan::REQUEST_CAPACITY }>> = None,
+        temp_peripheral: Option<peripheral::Spawn<TempRequest>> = None,
+        light_peripheral: Option<peripheral::Channel<LightRequest, { light::REQUEST_CAPACITY }>> = None,
+        pwm_peripheral: Option<peripheral::Spawn<PwmRequest>> = None,
+        settings_peripheral: Option<peripheral::Channel<SettingsRequest, { settings::REQUEST_CAPACITY }>> = None,
+        probe_peripheral: Option<peripheral::Channel<ProbeRequest, { ds18b20::REQUEST_CAPACITY }>> = None,
+        health_peripheral: Option<peripheral::Channel<HealthRequest, { health::REQUEST_CAPACITY }>> = None])]
     fn init(cx: init::Context) -> (Shared, Local) {
 
         let mut peripherals = cx.device;
@@ -181,10 +282,14 @@ mod app {
 
         Mono::start(100_000_000);
 
+        let reset_cause = health::reset_cause();
+        let settings = settings::Settings::new(peripherals.FLASH);
+
         let uid = hal::signature::Uid::get();
         info!("UID: lot: {} wafer: {} x: {}, y: {}", uid.lot_num(), uid.waf_num(), uid.x(), uid.y());
 
         let gpioa = peripherals.GPIOA.split();
+        let gpiob = peripherals.GPIOB.split();
         let gpioc = peripherals.GPIOC.split();
 
         // BluePill board has a pull-up resistor on the D+ line.
@@ -212,28 +317,86 @@ mod app {
             cx.local.ethernet_in_buffer, 
             cx.local.ethernet_out_buffer);
 
+        // Within the ADC's 36MHz limit, for the health task's measurements
+        let mut adc = Adc::adc1(peripherals.ADC1, true, AdcConfig::default().clock(Clock::Pclk2_div_4));
+
+        *cx.local.host_name = network::host_name(&settings);
+        let device = DeviceResponse {
+            hostname: cx.local.host_name.clone(),
+            id: network::device_id(),
+            mac_address: heapless::Vec::from_slice(&network::mac_address("device")).unwrap(),
+            firmware_version: heapless::String::try_from(env!("CARGO_PKG_VERSION")).unwrap(),
+        };
+        let host_name: &'static str = cx.local.host_name;
+        info!("host name: {}", host_name);
+        cx.local.dhcp_options[0].data = host_name.as_bytes();
         let mut network = NetworkStack::new(
-            DHCP_OPTIONS,
+            cx.local.dhcp_options,
+            network::ipv4_mode(&settings, IPV4),
             usb_ethernet, 
             &mut cx.local.socket_storage[..],
+            cx.local.dhcp_server_storage,
+            cx.local.slaac_storage,
             seed::seed(
-                &mut Adc::adc1(peripherals.ADC1, true, AdcConfig::default()), 
+                &mut adc, 
                 &mut gpioa.pa3.into_analog()));
     
+        let (grbl_port, shell_port) = network::ports(&settings, (GRBL_PORT, SHELL_PORT));
+        info!("GRBL on port {}, RPC on port {}", grbl_port, shell_port);
         let grbl = serial::Tasks::new(
             peripherals.USART1, 
             gpioa.pa9.into(),
             gpioa.pa10, 
             clocks,
-            network.channel(1337, cx.local.grbl_channel_storage));
-
-        let shell_channel = network.channel(1338, cx.local.shell_channel_storage);
+            network.channel(grbl_port, cx.local.grbl_channel_storage));
+
+        let shell_channel = network.channel(shell_port, cx.local.shell_channel_storage);
+        let [rpc_service, grbl_service] = &mut *cx.local.services;
+        rpc_service.port = shell_port;
+        grbl_service.port = grbl_port;
+        let services: &'static [mdns::Service<'static>] = cx.local.services;
+
+        let device_txt = cx.local.device_txt;
+        write!(device_txt, "id={}", network::device_id()).unwrap();
+        cx.local.mdns_txt[0] = device_txt;
+        network.advertise(
+            mdns::Responder::new(host_name, services, cx.local.mdns_txt),
+            cx.local.mdns_storage);
+        let announcement = DeviceAnnouncement {
+            hostname: device.hostname.clone(),
+            id: device.id.clone(),
+            mac_address: device.mac_address.clone(),
+            firmware_version: device.firmware_version.clone(),
+            interface_mac_address: heapless::Vec::from_slice(&network::mac_address("interface")).unwrap(),
+            services: services.iter()
+                .map(|service| ServicePort {
+                    name: heapless::String::try_from(service.kind).unwrap(),
+                    port: service.port as u32,
+                })
+                .collect(),
+            ..DeviceAnnouncement::default()
+        };
+        network.discoverable(announcement, cx.local.discovery_storage);
 
         let (fan_freq_writer, fan_freq_reader) = make_signal!(Duration);
-
-        let (_, (fan_pwm, light_pwm, ..)) = peripherals.TIM3.pwm_hz(25.kHz(), &clocks);
+        let (climate_writer, climate_reader) = make_signal!(Option<fan::Climate>);
+        let (fan_requests, fan_request_receiver) = make_channel!(FanRequest, { fan::REQUEST_CAPACITY });
+        let (light_requests, light_request_receiver) = make_channel!(LightRequest, { light::REQUEST_CAPACITY });
+        let (health_requests, health_request_receiver) = make_channel!(HealthRequest, { health::REQUEST_CAPACITY });
+        let (probe_requests, probe_request_receiver) = make_channel!(ProbeRequest, { ds18b20::REQUEST_CAPACITY });
+        let (fan_rescale_writer, fan_rescale_reader) = make_signal!(());
+        let (light_rescale_writer, light_rescale_reader) = make_signal!(());
+        let (settings_updates, settings_update_receiver) = make_channel!(settings::Update, { settings::UPDATE_CAPACITY });
+        let (settings_requests, settings_request_receiver) = make_channel!(SettingsRequest, { settings::REQUEST_CAPACITY });
+
+        let (fan_timer, (fan_pwm, _tim3_ch2, ..)) = peripherals.TIM3.pwm_hz(pwm::DEFAULT_FREQUENCY, &clocks);
         let mut fan_pwm = fan_pwm.with(gpioa.pa6);
-        let mut light_pwm = light_pwm.with(gpioa.pa7);
+        #[cfg(not(feature = "light-tim4"))]
+        let (light_timer, mut light_pwm) = ((), _tim3_ch2.with(gpioa.pa7));
+        #[cfg(feature = "light-tim4")]
+        let (light_timer, (light_pwm, ..)) = peripherals.TIM4.pwm_hz(pwm::DEFAULT_FREQUENCY, &clocks);
+        #[cfg(feature = "light-tim4")]
+        let mut light_pwm = light_pwm.with(gpiob.pb6);
         fan_pwm.enable();
         light_pwm.enable();
 
@@ -253,21 +416,121 @@ mod app {
         responses::spawn().unwrap();
         requests::spawn().unwrap();
         temp::spawn().unwrap();
+        fan_control::spawn().unwrap();
+        light_control::spawn().unwrap();
+        probe_control::spawn().unwrap();
+        health_control::spawn().unwrap();
+        settings_control::spawn().unwrap();
 
         let (response_sender, 
              response_receiver) = make_channel!(
                 Response,
                 { shell::MESSAGE_CAPACITY });
 
-        let (temp_writer, temp_reader) = dht11::make(
-            gpioa.pa2.into_open_drain_output(),
+        let mut registry = peripheral::Registry::new();
+        [
+            cx.local.fan_peripheral.insert(peripheral::Channel::new(aux_core::peripherals::FAN, crate::extract!(Fan), fan_requests)) as &mut dyn peripheral::Peripheral<Request>,
+            cx.local.temp_peripheral.insert(peripheral::Spawn::new(aux_core::peripherals::TEMP, crate::extract!(Temp), temp_request::spawn)),
+            cx.local.light_peripheral.insert(peripheral::Channel::new(aux_core::peripherals::LIGHT, crate::extract!(Light), light_requests)),
+            cx.local.pwm_peripheral.insert(peripheral::Spawn::new(aux_core::peripherals::PWM, crate::extract!(Pwm), pwm_request::spawn)),
+            cx.local.settings_peripheral.insert(peripheral::Channel::new(aux_core::peripherals::SETTINGS, crate::extract!(Settings), settings_requests)),
+            cx.local.probe_peripheral.insert(peripheral::Channel::new(aux_core::peripherals::PROBE, crate::extract!(Probe), probe_requests)),
+            cx.local.health_peripheral.insert(peripheral::Channel::new(aux_core::peripherals::HEALTH, crate::extract!(Health), health_requests)),
+        ].into_iter().for_each(|peripheral| registry.register(peripheral).unwrap());
+
+        let [(name_0, sensor_0), (name_1, sensor_1), (name_2, sensor_2)] = TEMP_SENSORS;
+        let dht_fitted = settings.uint(SettingKey::DhtSensors).unwrap_or(DHT_FITTED);
+        let fitted = |sensor: usize| dht_fitted & (1 << sensor) != 0;
+        #[cfg(not(feature = "dht-capture"))]
+        let (temp_writer_0, temp_reader_0) = dht11::make(
+            gpioa.pa2.into_open_drain_output().erase(),
             gpioc.pc2,
+            sensor_0,
+            name_0,
+            fitted(0),
+            &mut syscfg, &mut peripherals.EXTI);
+        #[cfg(feature = "dht-capture")]
+        let temp_reader_0 = dht11::make_capture(
+            gpioa.pa2.into_open_drain_output().erase(),
+            dht11::Capture::new(
+                peripherals.TIM5,
+                peripherals.DMA1,
+                cortex_m::singleton!(: [u32; dht11::CAPTURES] = [0; dht11::CAPTURES]).unwrap(),
+                &clocks),
+            sensor_0,
+            name_0);
+        let (temp_writer_1, temp_reader_1) = dht11::make(
+            gpiob.pb0.into_open_drain_output().erase(),
+            gpioc.pc0,
+            sensor_1,
+            name_1,
+            fitted(1),
+            &mut syscfg, &mut peripherals.EXTI);
+        let (temp_writer_2, temp_reader_2) = dht11::make(
+            gpiob.pb3.into_open_drain_output().erase(),
+            gpioc.pc3,
+            sensor_2,
+            name_2,
+            fitted(2),
             &mut syscfg, &mut peripherals.EXTI);
+        let temp_readers = [temp_reader_0, temp_reader_1, temp_reader_2];
+
+        let i2c_sensor = i2c_sensor::I2cSensor::detect(hal::i2c::I2c::new(
+            peripherals.I2C1,
+            (gpiob.pb8, gpiob.pb9),
+            I2C_FREQUENCY,
+            &clocks));
+        if i2c_sensor.is_some() {
+            i2c_temp::spawn().unwrap();
+        }
+
+        let mut fan = fan::Fan::new(
+            fan_pwm,
+            response_sender.clone(),
+            fan_request_receiver,
+            fan_freq_reader,
+            climate_reader,
+            fan_rescale_reader,
+            settings_updates.clone());
+        let mut light = light::Light::new(
+            light_pwm,
+            response_sender.clone(),
+            light_request_receiver,
+            light_rescale_reader,
+            settings_updates.clone());
+        let mut pwm = pwm::Pwm::new(
+            fan_timer,
+            light_timer,
+            fan_rescale_writer,
+            light_rescale_writer,
+            settings_updates,
+            response_sender.clone());
+
+        pwm.restore(settings.uint(SettingKey::FanPwmHz), settings.uint(SettingKey::LightPwmHz));
+        fan.restore(settings.float(SettingKey::FanDuty).unwrap_or(0.0));
+        light.restore(settings.float(SettingKey::LightDuty).unwrap_or(0.0));
 
         (Shared {
             usb : usb_device(usb_bus),
             network,
-            temp: Option::None,
+            temp: temp_readers.iter()
+                .enumerate()
+                .map(|(sensor, reader)| {
+                    // The failsafe needs every read of the sensor driving the fan curve
+                    let polling = match (fitted(sensor), sensor == 0 && i2c_sensor.is_none()) {
+                        (false, _) => dht11::Polling::Off,
+                        (true, false) => dht11::Polling::Fitted,
+                        (true, true) => dht11::Polling::Always,
+                    };
+                    (reader.name(), reader.interval(), polling)
+                })
+                .chain(i2c_sensor.as_ref().map(|sensor| (sensor.name(), sensor.interval(), dht11::Polling::Always)))
+                .enumerate()
+                .map(|(sensor, (name, interval, polling))| dht11::Latest::new(sensor as u32, name, interval * STALE_READS, polling))
+                .collect(),
+            climate_writer,
+            temp_diagnostics: core::array::from_fn(|_| dht11::Diagnostics::new()),
+            temp_history: cx.local.temp_history,
          }, 
          Local {
             grbl_tx: grbl.tx,
@@ -276,13 +539,36 @@ mod app {
             network_send : [grbl.net.send, shell_channel.net.send],
             response_receiver: ChannelStream::new(response_receiver),
             request_decoder: codec::Decoder::new(ChannelStream::new(shell_channel.app.recv)),
+            registry,
+            request_responses: response_sender.clone(),
+            device,
             response_encoder: codec::Encoder::new(ChannelSink::new(shell_channel.app.send)),
-            fan: fan::Fan::new(fan_pwm, response_sender.clone(), fan_freq_reader),
-            light: light::Light::new(light_pwm, response_sender.clone()),
+            fan,
+            light,
+            health: health::Health::new(
+                adc,
+                health_request_receiver,
+                response_sender.clone(),
+                reset_cause),
+            probes: ds18b20::Probes::new(
+                gpiob.pb5.into_open_drain_output(),
+                probe_request_receiver,
+                response_sender.clone()),
+            pwm,
+            settings: settings::SettingsTask::new(
+                settings,
+                settings_update_receiver,
+                settings_request_receiver,
+                response_sender.clone()),
             fan_freq,
             led: gpioc.pc13.into_push_pull_output(),
-            temp_reader,
-            temp_writer,
+            temp_readers,
+            dht_climate: i2c_sensor.is_none(),
+            i2c_sensor,
+            #[cfg(not(feature = "dht-capture"))]
+            temp_writer_0,
+            temp_writer_1,
+            temp_writer_2,
             temp_responses: shell::TaskResponses { 
                     task: (), 
                     responses: response_sender.clone()
@@ -340,25 +626,28 @@ mod app {
         }
     }
 
-    #[task(local = [request_decoder])]
+    #[task(local = [request_decoder, registry, request_responses, device])]
     async fn requests(cx: requests::Context) {
         let request_decoder = cx.local.request_decoder;
+        let registry = cx.local.registry;
         loop {
-            match request_decoder.next().await {
-                Some(Request { peripheral: Some(RequestPeripheral::Fan(request)) }) => {
-                    let _ = fan_request::spawn(request);
+            let response = match request_decoder.next().await {
+                Some(Request { peripheral: Some(RequestPeripheral::Peripherals(_)) }) => {
+                    peripheral::peripherals_response(registry)
                 },
-                Some(Request { peripheral: Some(RequestPeripheral::Temp(request)) }) => {
-                    let _  = temp_request::spawn(request);
+                Some(Request { peripheral: Some(RequestPeripheral::Device(_)) }) => {
+                    peripheral::device_response(cx.local.device)
                 },
-                Some(Request { peripheral: Some(RequestPeripheral::Light(request)) }) => {
-                    let _ = light_request::spawn(request);
-                },
-                Some(Request { peripheral: None }) => {
-                    warn!("No peripheral specified")
+                Some(request) => match registry.dispatch(request) {
+                    Ok(_) => continue,
+                    Err(err) => {
+                        warn!("Request not delivered: {}", err);
+                        peripheral::error_response(err)
+                    },
                 },
                 None => break,
             };
+            cx.local.request_responses.send(response).await.unwrap();
         }
     }
 
@@ -370,44 +659,118 @@ mod app {
     }
 
 
-    #[task(local=[temp_reader], shared=[temp])]
+    #[task(local=[temp_readers, dht_climate], shared=[temp, climate_writer, temp_diagnostics, temp_history])]
     async fn temp(mut cx: temp::Context) {
-        let temp = cx.local.temp_reader;
+        let readers = cx.local.temp_readers;
+        // Read one sensor at a time, spread over the longest read interval, so each
+        // is read as often as the slowest allows
+        let interval = readers.iter().map(|reader| reader.interval()).max().unwrap();
+        let step = interval / TEMP_COUNT as u32;
         Mono::delay(Duration::secs(1)).await;
         loop {
-            let start = Mono::now();
-            match temp.read().await {
-                Ok(response) => {
-                    cx.shared.temp.lock(|current| {
-                        current.replace(response)
-                    });
-                },
-                Err(err) => {
-                    warn!("Error reading temperature {}", err);
+            for (sensor, temp) in readers.iter_mut().enumerate() {
+                let start = Mono::now();
+                if !cx.shared.temp.lock(|latest| latest[sensor].due()) {
+                    // A fan curve on a sensor that isn't fitted fails safe
+                    if sensor == 0 && *cx.local.dht_climate {
+                        cx.shared.climate_writer.lock(|climate| climate.write(None));
+                    }
+                    Mono::delay_until(start + step).await;
+                    continue;
+                }
+                let result = temp.read().await;
+                if let Err(err) = &result {
+                    warn!("Error reading temperature {} {}", temp.name(), err);
+                }
+                let now = Mono::now();
+                if sensor == 0 && *cx.local.dht_climate {
+                    cx.shared.climate_writer.lock(|climate| climate.write(result.as_ref().ok().map(fan::Climate::from)));
+                    cx.shared.temp_history.lock(|history| history.add(now, result.as_ref().ok()));
                 }
+                cx.shared.temp.lock(|latest| latest[sensor].update(now, &result));
+                let diagnostics = temp.diagnostics();
+                cx.shared.temp_diagnostics.lock(|current| current[sensor] = diagnostics);
+                Mono::delay_until(start + step).await;
             }
-            Mono::delay_until(start + Duration::secs(1)).await;
         }
     }
 
-    #[task(local = [ temp_responses], shared = [temp])]
-    async fn temp_request(mut cx: temp_request::Context, _: TempRequest) {
+    #[task(local=[i2c_sensor], shared=[temp, climate_writer, temp_history])]
+    async fn i2c_temp(mut cx: i2c_temp::Context) {
+        let Some(sensor) = cx.local.i2c_sensor.as_mut() else { return };
+        loop {
+            let start = Mono::now();
+            let result = sensor.read().await;
+            if let Err(err) = &result {
+                warn!("Error reading temperature {} {}", sensor.name(), err);
+            }
+            let now = Mono::now();
+            cx.shared.climate_writer.lock(|climate| climate.write(result.as_ref().ok().map(fan::Climate::from)));
+            cx.shared.temp_history.lock(|history| history.add(now, result.as_ref().ok()));
+            cx.shared.temp.lock(|latest| latest[TEMP_COUNT].update(now, &result));
+            Mono::delay_until(start + sensor.interval()).await;
+        }
+    }
+
+    #[task(local = [ temp_responses], shared = [temp, temp_diagnostics, temp_history])]
+    async fn temp_request(mut cx: temp_request::Context, request: TempRequest) {
         let temp = cx.local.temp_responses;
-        let current = cx.shared.temp.lock(|current| current.clone());
-        temp.responses.send(Response {
-            peripheral: Some(ResponsePeripheral::Temp(current.unwrap_or(TempResponse::default())))
-        }).await.unwrap();
+        let peripheral = match request.command {
+            Some(TempRequest_::Command::Diagnostics(diagnostics)) => {
+                let sensor = diagnostics.sensor;
+                cx.shared.temp_diagnostics.lock(|diagnostics| diagnostics.get(sensor as usize).map(|diagnostics| diagnostics.response(sensor)))
+                    .map(ResponsePeripheral::TempDiagnostics)
+                    .unwrap_or_else(|| ResponsePeripheral::Temp(dht11::no_sensor(sensor)))
+            },
+            Some(TempRequest_::Command::History(history)) => {
+                let page = cx.shared.temp_history.lock(|samples| samples.page(Mono::now(), history.start));
+                ResponsePeripheral::TempHistory(page)
+            },
+            Some(TempRequest_::Command::Get(get)) if get.all => {
+                for sensor in 0..cx.shared.temp.lock(|latest| latest.len()) {
+                    let current = cx.shared.temp.lock(|latest| latest[sensor].response(Mono::now()));
+                    temp.responses.send(Response { peripheral: Some(ResponsePeripheral::Temp(current)) }).await.unwrap();
+                }
+                return;
+            },
+            Some(TempRequest_::Command::Get(get)) => {
+                let sensor = get.sensor;
+                // Asking for a suspended sensor reads it again, from the next round
+                let current = cx.shared.temp.lock(|latest| latest.get_mut(sensor as usize).map(|latest| {
+                    let response = latest.response(Mono::now());
+                    latest.resume();
+                    response
+                }));
+                ResponsePeripheral::Temp(current.unwrap_or_else(|| dht11::no_sensor(sensor)))
+            },
+            None => {
+                let current = cx.shared.temp.lock(|latest| latest[0].response(Mono::now()));
+                ResponsePeripheral::Temp(current)
+            },
+        };
+        temp.responses.send(Response { peripheral: Some(peripheral) }).await.unwrap();
     }
 
 
-    #[task(binds=EXTI2, local=[temp_writer], priority=4)]
-    fn temp_falling_edge(cx: temp_falling_edge::Context) {
-        cx.local.temp_writer.falling_edge();
+    #[cfg(not(feature = "dht-capture"))]
+    #[task(binds=EXTI2, local=[temp_writer_0], priority=4)]
+    fn temp_falling_edge_0(cx: temp_falling_edge_0::Context) {
+        cx.local.temp_writer_0.falling_edge();
+    }
+
+    #[task(binds=EXTI0, local=[temp_writer_1], priority=4)]
+    fn temp_falling_edge_1(cx: temp_falling_edge_1::Context) {
+        cx.local.temp_writer_1.falling_edge();
+    }
+
+    #[task(binds=EXTI3, local=[temp_writer_2], priority=4)]
+    fn temp_falling_edge_2(cx: temp_falling_edge_2::Context) {
+        cx.local.temp_writer_2.falling_edge();
     }
 
     #[task(local=[fan])]
-    async fn fan_request(cx: fan_request::Context, request: FanRequest) {
-        cx.local.fan.process(request).await;
+    async fn fan_control(cx: fan_control::Context) {
+        cx.local.fan.run().await;
     }
 
     #[task(binds = EXTI1, local = [fan_freq])]
@@ -416,8 +779,28 @@ mod app {
     }
 
     #[task(local = [light])]
-    async fn light_request(cx: light_request::Context, request: LightRequest) {
-        cx.local.light.process(request).await;       
+    async fn light_control(cx: light_control::Context) {
+        cx.local.light.run().await;
+    }
+
+    #[task(local = [health])]
+    async fn health_control(cx: health_control::Context) {
+        cx.local.health.run().await;
+    }
+
+    #[task(local = [probes])]
+    async fn probe_control(cx: probe_control::Context) {
+        cx.local.probes.run().await;
+    }
+
+    #[task(local = [settings])]
+    async fn settings_control(cx: settings_control::Context) {
+        cx.local.settings.run().await;
+    }
+
+    #[task(local = [pwm])]
+    async fn pwm_request(cx: pwm_request::Context, request: PwmRequest) {
+        cx.local.pwm.process(request).await;
     }
 }
 
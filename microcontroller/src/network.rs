@@ -1,8 +1,6 @@
 use rtic_monotonics::Monotonic;
 use sha2::{Digest, Sha256};
-use core::{future::poll_fn, marker::PhantomData};
-
-use futures::task::Poll;
+use core::{ fmt::Write, marker::PhantomData };
 
 use defmt::{ debug, error, info, warn };
 
@@ -15,15 +13,32 @@ use usb_device::UsbError;
 
 use smoltcp::{
     iface::{self, Interface, SocketHandle, SocketSet, SocketStorage }, 
-    socket::{ dhcpv4, tcp },  
-    time::Instant, 
-    wire::{ DhcpOption, EthernetAddress, IpCidr, Ipv4Address, Ipv4Cidr }
+    socket::{ dhcpv4, raw, tcp, udp },  
+    time::{ self, Instant },
+    wire::{ DhcpOption, EthernetAddress, IpCidr, Ipv4Address, Ipv4Cidr, Ipv6Address },
 };
 
 use rtic_sync::channel::{ Channel, ReceiveError, Receiver, Sender, TrySendError};
+use aux_core::{
+    channel::{ self, ByteReceiver, ByteSender, SendError },
+    dhcp_server::{ self, Server },
+    hostname::{ self, HostName },
+    ipv4::{ Fallback, Mode, Static },
+    mdns::Responder,
+    services::{
+        Announcement, Autoconf, DhcpServer, Discovery, Mdns, ANNOUNCEMENT, DHCP_MESSAGE, MDNS_MESSAGE,
+        NDISC_MESSAGE, QUERY,
+    },
+};
+
+use micropb::{ MessageEncode, PbEncoder };
+
+use crate::{ proto::{ DeviceAnnouncement, SettingKey }, settings::Settings };
 
 pub const IP_ADDRESS: Ipv4Address = Ipv4Address::new(0, 0, 0, 0);
 pub const MTU: u16 = 64;
+// How long to wait for a DHCP lease before using a link-local address
+const LINK_LOCAL_TIMEOUT: time::Duration = time::Duration::from_secs(10);
 
 pub fn mac_address(seed: &str) -> [u8; 6] {
     let uid = hal::signature::Uid::get();
@@ -41,6 +56,44 @@ pub fn mac_address(seed: &str) -> [u8; 6] {
     mac
 }
 
+// Identifies the board in mDNS TXT records: the hex of its unique ID
+pub fn device_id() -> heapless::String<24> {
+    let uid = hal::signature::Uid::get();
+    let mut id = heapless::String::new();
+    for byte in uid.lot_num() {
+        write!(id, "{:02x}", byte).unwrap();
+    }
+    write!(id, "{:02x}{:04x}{:04x}", uid.waf_num(), uid.x(), uid.y()).unwrap();
+    id
+}
+
+// The host name in the settings, or the default for the device's MAC address
+pub fn host_name(settings: &Settings) -> HostName {
+    settings.text(SettingKey::Hostname)
+        .filter(|name| hostname::valid(name))
+        .unwrap_or_else(|| hostname::default(mac_address("device")))
+}
+
+// The GRBL and RPC ports in the settings, unless they'd be the same
+pub fn ports(settings: &Settings, default: (u16, u16)) -> (u16, u16) {
+    let port = |key| settings.uint(key).and_then(|port| u16::try_from(port).ok()).filter(|port| *port != 0);
+    let grbl = port(SettingKey::GrblPort).unwrap_or(default.0);
+    let rpc = port(SettingKey::RpcPort).unwrap_or(default.1);
+    if grbl == rpc {
+        warn!("GRBL and RPC both on port {}, using {} and {}", grbl, default.0, default.1);
+        return default;
+    }
+    (grbl, rpc)
+}
+
+// A static address in the settings takes precedence over the compiled-in one
+pub fn ipv4_mode(settings: &Settings, default: Mode) -> Mode {
+    let address = settings.text(SettingKey::Ipv4Address).and_then(|text| text.parse().ok());
+    let gateway = settings.text(SettingKey::Ipv4Gateway).and_then(|text| text.parse().ok());
+    let serve = settings.uint(SettingKey::DhcpServer).map(|serve| serve != 0);
+    Mode::from_settings(address, gateway, serve).unwrap_or(default)
+}
+
 pub fn usb_ethernet<'a>(
     usb_alloc: &'a usb_device::bus::UsbBusAllocator<UsbBus<USB>>,
     in_buffer: &'a mut [u8; 2048],
@@ -57,138 +110,31 @@ pub fn usb_ethernet<'a>(
 }
 
 
-#[derive(Clone, Copy)]
-enum RecvChannelState {
-    Listening,
-    Receiving,
-    Closing,
-}
+// The application's ends of a channel
+pub struct AppSender<'a, const N: usize>(Sender<'a, u8, N>);
+pub struct AppReceiver<'a, const N: usize>(Receiver<'a, u8, N>);
 
-pub struct RecvChannel<'a, const N: usize> {
-    port: u16,
-    handle: SocketHandle,
-    sender: Sender<'a, u8, N>,
-    state: RecvChannelState,
-}
-
-impl <const N: usize> RecvChannel<'_, N> {
-    pub fn try_recv(&mut self,  sockets: &mut SocketSet<'_>) -> bool {
-        let socket: &mut tcp::Socket = sockets.get_mut(self.handle);
-        let mut consumed: usize = 0;
-
-        if self.may_recv(socket) {
-            let mut buf = [0u8; N];
-            // peek at the bytes, because we don't know how many we can forward
-            match socket.peek_slice(&mut buf[..]) {
-                Ok(received) => {
-                    for index in 0..received {
-                        match self.sender.try_send(buf[index]) {
-                            Ok(()) => { consumed += 1; },
-                            Err(TrySendError::Full(_)) => break,
-                            Err(TrySendError::NoReceiver(_)) => { panic!("no receiver"); },
-                        }
-                    }
-
-                    // Read however many bytes we could send to the channel
-                    socket.recv_slice(&mut buf[0..consumed]).unwrap();
-                    if consumed < received {
-                        warn!("sender is full. received {}, consumed {} for {}", received, consumed, self.port);
-                    } else {
-                        debug!("consumed {} bytes on {}", consumed, self.port);
-                    }
-                },
-                Err(e) => { panic!("Error peeking socket input: {}", e); },
-            }
-        }
-
-        consumed > 0
-    }
-
-    fn may_recv(&mut self, socket: &mut tcp::Socket<'_>) -> bool {
-        // If the remote closes the socket, we close the socket too, and return to the 
-        // listenning state. It may not be necessary to track the state of the channel
-        // separately, but it's simpler (the socket state is complicated), and it makes
-        // logging the transitions possible.
-        let (state, may_recv) = match (self.state, socket.may_recv()) {
-            (RecvChannelState::Listening, true) => {
-                info!("accepted connection, state: {} on {}", socket.state(), self.port);
-                (RecvChannelState::Receiving, true)
-            },
-            (RecvChannelState::Receiving, false) => {
-                info!("remote closed socket, state: {}, closing", socket.state());
-                socket.close();
-                (RecvChannelState::Closing, false)
-            },
-            (RecvChannelState::Closing, false) => {
-                match socket.is_active() {
-                    true => (RecvChannelState::Closing, false),
-                    false => {
-                        info!("socket closed, state {}, listenning on {}", socket.state(), self.port);
-                        socket.listen(self.port).ok();
-                        (RecvChannelState::Listening, false)
-                    }
-                }
-            }
-            (state, receive) => (state, receive)
-        };
-        
-        self.state = state;
-        may_recv
+impl <const N: usize> ByteSender for AppSender<'_, N> {
+    fn try_send(&mut self, byte: u8) -> Result<(), SendError> {
+        self.0.try_send(byte).map_err(|err| match err {
+            TrySendError::Full(_) => SendError::Full,
+            TrySendError::NoReceiver(_) => SendError::Closed,
+        })
     }
 }
-pub struct SendChannel<'a, const N: usize> {
-    handle: SocketHandle,
-    receiver: Receiver<'a, u8, N>
-}
-
-impl <const N: usize> SendChannel<'_, N> {
-    pub async fn send(&mut self,  sockets: &mut SocketSet<'_>) -> Result<bool, ReceiveError> {
-        poll_fn(|_cx| {
 
-            match self.try_send(sockets) {
-                Ok(false) => Poll::Pending,
-                Ok(true) => Poll::Ready(Ok(true)),
-                Err(err) => return Poll::Ready(Err(err)),
-            }
-        }).await
+impl <const N: usize> ByteReceiver for AppReceiver<'_, N> {
+    fn try_recv(&mut self) -> Result<u8, channel::ReceiveError> {
+        self.0.try_recv().map_err(|err| match err {
+            ReceiveError::Empty => channel::ReceiveError::Empty,
+            ReceiveError::NoSender => channel::ReceiveError::Closed,
+        })
     }
-
-    pub fn try_send(&mut self, sockets: &mut SocketSet<'_>) -> Result<bool, ReceiveError> {
-        let socket:&mut tcp::Socket = sockets.get_mut(self.handle);
-
-        if socket.may_send() {
-            let mut count: usize = 0;
-            while socket.can_send() {
-                match self.receiver.try_recv() {
-                    Ok(data) => {
-                        socket.send_slice(&[data]).ok();
-                        count += 1;
-                    },
-                    Err(ReceiveError::Empty) => { 
-                        break; 
-                    },
-                    Err(err) => {
-                        return Err(err);
-                    }
-                }
-            }
-            Ok(count != 0)
-        } else {
-            loop {
-                match self.receiver.try_recv() {
-                    Ok(_) => { },
-                    Err(ReceiveError::Empty) => { 
-                        return Ok(false);
-                    },
-                    Err(err) => {
-                        return Err(err);
-                    }
-                }
-            }
-        }
-    }   
 }
 
+pub type RecvChannel<'a, const N: usize> = channel::RecvChannel<AppSender<'a, N>, N>;
+pub type SendChannel<'a, const N: usize> = channel::SendChannel<AppReceiver<'a, N>>;
+
 pub struct NetworkChannelStorage<const N: usize> {
     pub sender: Channel<u8, N>,
     pub receiver: Channel<u8, N>,
@@ -208,6 +154,93 @@ impl  <const N: usize> NetworkChannelStorage<N> {
     }
 }
 
+pub struct DhcpServerStorage {
+    rx_metadata: [udp::PacketMetadata; 2],
+    rx_storage: [u8; 2 * DHCP_MESSAGE],
+    tx_metadata: [udp::PacketMetadata; 1],
+    tx_storage: [u8; DHCP_MESSAGE],
+}
+
+impl DhcpServerStorage {
+    pub const fn new() -> Self {
+        Self {
+            rx_metadata: [udp::PacketMetadata::EMPTY; 2],
+            rx_storage: [0x0; 2 * DHCP_MESSAGE],
+            tx_metadata: [udp::PacketMetadata::EMPTY; 1],
+            tx_storage: [0x0; DHCP_MESSAGE],
+        }
+    }
+}
+
+pub struct MdnsStorage {
+    rx_metadata: [udp::PacketMetadata; 2],
+    rx_storage: [u8; 2 * MDNS_MESSAGE],
+    tx_metadata: [udp::PacketMetadata; 2],
+    tx_storage: [u8; 2 * MDNS_MESSAGE],
+}
+
+impl MdnsStorage {
+    pub const fn new() -> Self {
+        Self {
+            rx_metadata: [udp::PacketMetadata::EMPTY; 2],
+            rx_storage: [0x0; 2 * MDNS_MESSAGE],
+            tx_metadata: [udp::PacketMetadata::EMPTY; 2],
+            tx_storage: [0x0; 2 * MDNS_MESSAGE],
+        }
+    }
+}
+
+pub struct SlaacStorage {
+    rx_metadata: [raw::PacketMetadata; 2],
+    rx_storage: [u8; 2 * NDISC_MESSAGE],
+    tx_metadata: [raw::PacketMetadata; 1],
+    tx_storage: [u8; NDISC_MESSAGE],
+}
+
+impl SlaacStorage {
+    pub const fn new() -> Self {
+        Self {
+            rx_metadata: [raw::PacketMetadata::EMPTY; 2],
+            rx_storage: [0x0; 2 * NDISC_MESSAGE],
+            tx_metadata: [raw::PacketMetadata::EMPTY; 1],
+            tx_storage: [0x0; NDISC_MESSAGE],
+        }
+    }
+}
+
+pub struct DiscoveryStorage {
+    rx_metadata: [udp::PacketMetadata; 2],
+    rx_storage: [u8; 2 * QUERY],
+    tx_metadata: [udp::PacketMetadata; 2],
+    tx_storage: [u8; 2 * ANNOUNCEMENT],
+}
+
+impl DiscoveryStorage {
+    pub const fn new() -> Self {
+        Self {
+            rx_metadata: [udp::PacketMetadata::EMPTY; 2],
+            rx_storage: [0x0; 2 * QUERY],
+            tx_metadata: [udp::PacketMetadata::EMPTY; 2],
+            tx_storage: [0x0; 2 * ANNOUNCEMENT],
+        }
+    }
+}
+
+impl Announcement for DeviceAnnouncement {
+    fn encode(&mut self, ipv4: Option<Ipv4Address>, ipv6: &[Ipv6Address], buffer: &mut [u8]) -> Option<usize> {
+        self.ipv4_address = ipv4.map_or(0, |address| address.to_bits());
+        self.ipv6_addresses.clear();
+        for address in ipv6 {
+            self.ipv6_addresses.push(heapless::Vec::from_slice(&address.octets()).unwrap()).ok();
+        }
+        let mut encoder = PbEncoder::new(heapless::Vec::<u8, ANNOUNCEMENT>::new());
+        MessageEncode::encode(&*self, &mut encoder).ok()?;
+        let encoded = encoder.into_writer();
+        buffer.get_mut(..encoded.len())?.copy_from_slice(&encoded);
+        Some(encoded.len())
+    }
+}
+
 pub struct NetworkEndpoint<'a, const N: usize> {
     pub send: SendChannel<'a, N>,
     pub recv: RecvChannel<'a, N>,
@@ -231,7 +264,12 @@ pub struct NetworkStack<'a, CLOCK: Monotonic> {
     pub ethernet: Ethernet<'a, UsbBus<USB>>,
     pub interface: Interface,
     pub sockets: SocketSet<'a>,
-    pub dhcp: SocketHandle,
+    pub dhcp: Option<SocketHandle>,
+    fallback: Fallback,
+    server: Option<DhcpServer>,
+    autoconf: Autoconf,
+    mdns: Option<Mdns<'a>>,
+    discovery: Option<Discovery<DeviceAnnouncement>>,
     clock: PhantomData<CLOCK>,
 }
 
@@ -240,39 +278,81 @@ where CLOCK::Instant: IntoInstant {
 
     pub fn new(
         options:&'static [DhcpOption<'static>],
+        ipv4: Mode,
         mut ethernet: Ethernet<'a, UsbBus<USB>>,
         storage: &'a mut [SocketStorage<'a>],
+        server_storage: &'a mut DhcpServerStorage,
+        slaac_storage: &'a mut SlaacStorage,
         seed: u64) -> Self {
-        let interface = Self::interface(&mut ethernet, seed);
-        let mut dhcp_socket = dhcpv4::Socket::new();
-        dhcp_socket.set_outgoing_options(options);
+        let mac_address = mac_address("device");
+        let mut interface = Self::interface(&mut ethernet, EthernetAddress(mac_address), seed);
         let mut sockets = SocketSet::new(storage);
-        let dhcp = sockets.add(dhcp_socket);
+        let (dhcp, server) = match ipv4 {
+            Mode::Static(Static { address, gateway }) => {
+                info!("static IP address: {}", address);
+                interface.update_ip_addrs(|addrs| addrs.push(IpCidr::Ipv4(address)).unwrap());
+                if let Some(gateway) = gateway {
+                    interface.routes_mut().add_default_ipv4_route(gateway).unwrap();
+                }
+                (None, None)
+            },
+            Mode::Dhcp => {
+                interface.update_ip_addrs(|addrs| {
+                    addrs.push(Ipv4Cidr::new(IP_ADDRESS, 0).into()).unwrap();
+                });
+                let mut dhcp_socket = dhcpv4::Socket::new();
+                dhcp_socket.set_outgoing_options(options);
+                (Some(sockets.add(dhcp_socket)), None)
+            },
+            Mode::Server(address) => {
+                let server = Server::new(address).unwrap_or_else(|| {
+                    warn!("No address to offer in {}, serving {}", address, dhcp_server::ADDRESS);
+                    Server::new(dhcp_server::ADDRESS).unwrap()
+                });
+                info!("IP address: {}, offering {} by DHCP", server.address(), server.lease());
+                interface.update_ip_addrs(|addrs| addrs.push(IpCidr::Ipv4(server.address())).unwrap());
+                (None, Some(DhcpServer::new(
+                    server,
+                    udp::PacketBuffer::new(&mut server_storage.rx_metadata[..], &mut server_storage.rx_storage[..]),
+                    udp::PacketBuffer::new(&mut server_storage.tx_metadata[..], &mut server_storage.tx_storage[..]),
+                    &mut sockets)))
+            },
+        };
+        // Reachable over IPv6 on the link whatever happens with IPv4
+        let autoconf = Autoconf::new(
+            EthernetAddress(mac_address),
+            raw::PacketBuffer::new(&mut slaac_storage.rx_metadata[..], &mut slaac_storage.rx_storage[..]),
+            raw::PacketBuffer::new(&mut slaac_storage.tx_metadata[..], &mut slaac_storage.tx_storage[..]),
+            &mut interface,
+            &mut sockets);
+
+        // The link-local address comes from the same hash as the MAC
+        let fallback = Fallback::new(
+            u32::from_le_bytes(mac_address[2..6].try_into().unwrap()),
+            LINK_LOCAL_TIMEOUT);
         NetworkStack::<'a,CLOCK> {
             ethernet,
             interface,
             sockets,
             dhcp,
+            fallback,
+            server,
+            autoconf,
+            mdns: None,
+            discovery: None,
             clock: PhantomData
         }
     }
 
-    fn interface(ethernet: &mut Ethernet<'a, UsbBus<USB>>, seed: u64) -> Interface {
-        let mac_address = EthernetAddress(mac_address("device"));
+    fn interface(ethernet: &mut Ethernet<'a, UsbBus<USB>>, mac_address: EthernetAddress, seed: u64) -> Interface {
         let mut interface_config = iface::Config::new(mac_address.into());
         interface_config.random_seed = seed;
 
-        let mut interface = Interface::new(
+        let interface = Interface::new(
             interface_config,
             ethernet,
             Self::now());
 
-        interface.update_ip_addrs(|ip_addrs| {
-            ip_addrs
-                .push(Ipv4Cidr::new(IP_ADDRESS, 0).into())
-                .unwrap();
-        });
-
         info!("device MAC address: {}", mac_address);
         interface
     }
@@ -298,9 +378,23 @@ where CLOCK::Instant: IntoInstant {
         self.ethernet.state() == DeviceState::Connected
     }
     
+    // Runs in the usb_send task rather than the USB interrupt: the services
+    // answered here encode their replies on the stack, and none of them is
+    // urgent enough to hold up the interrupt.
     pub fn try_send<const N: usize>(&mut self, channels: &mut [SendChannel<N>]) {
         if self.connected() {
-            let mut data = false;
+            let now = Self::now();
+            self.dhcp_poll();
+            let mut data = self.autoconf.poll(now, true, &mut self.interface, &mut self.sockets);
+            if let Some(server) = &self.server {
+                data |= server.poll(&mut self.sockets);
+            }
+            if let Some(mdns) = &mut self.mdns {
+                data |= mdns.poll(&self.interface, &mut self.sockets);
+            }
+            if let Some(discovery) = &mut self.discovery {
+                data |= discovery.poll(&self.interface, &mut self.sockets);
+            }
             for channel in channels {
                 data |= match channel.try_send(&mut self.sockets) {
                     Ok(sent) => sent,
@@ -309,45 +403,42 @@ where CLOCK::Instant: IntoInstant {
             }
 
             if data {
-                self.interface.poll_egress(Self::now(), &mut self.ethernet, &mut self.sockets);
+                self.interface.poll_egress(now, &mut self.ethernet, &mut self.sockets);
             }
         } else {
+            // The wait for a DHCP lease only counts while the host can answer
+            self.fallback.disconnected();
             self.connect();
         }
     }
 
+    // Runs in the USB interrupt, so it only moves packets and TCP bytes
     pub fn try_recv<const N: usize>(&mut self, channels: &mut [RecvChannel<N>]) {
-        let data = match self.interface.poll(Self::now(), &mut self.ethernet, &mut self.sockets) {
-            iface::PollResult::SocketStateChanged => true,
-            iface::PollResult::None => false
-        };
+        self.interface.poll(Self::now(), &mut self.ethernet, &mut self.sockets);
+        let mut egress = false;
 
-        self.dhcp_poll();
-
-        let mut ack = false;
-        if data {
-            for channel in channels {
-                ack |= channel.try_recv(&mut self.sockets);
-            }
+        // Even without a network event, there may be bytes left in a socket
+        // that didn't fit in its channel last time
+        for channel in channels {
+            egress |= channel.try_recv(&mut self.sockets);
         }
 
-        if ack {
+        if egress {
             self.interface.poll_egress(Self::now(), &mut self.ethernet, &mut self.sockets);
         }
     }
 
     fn dhcp_poll(&mut self) {
-        let event = self.sockets.get_mut::<dhcpv4::Socket>(self.dhcp).poll();
+        let Some(dhcp) = self.dhcp else { return };
+        let event = self.sockets.get_mut::<dhcpv4::Socket>(dhcp).poll();
         match event {
             None => {}
             Some(dhcpv4::Event::Configured(config)) => {
                 debug!("DHCP config acquired!");
+                self.fallback.leased();
 
                 info!("IP address:      {}", config.address);
-                self.interface.update_ip_addrs(|addrs| {
-                    addrs.clear();
-                    addrs.push(IpCidr::Ipv4(config.address)).unwrap();
-                });
+                self.set_ipv4_address(Some(config.address));
 
                 if let Some(router) = config.router {
                     debug!("Default gateway: {}", router);
@@ -363,11 +454,27 @@ where CLOCK::Instant: IntoInstant {
             }
             Some(dhcpv4::Event::Deconfigured) => {
                 debug!("DHCP lost config!");
-                self.interface.update_ip_addrs(|addrs| addrs.clear());
+                self.fallback.lost();
+                self.set_ipv4_address(None);
                 self.interface.routes_mut().remove_default_ipv4_route();
             }
         }
 
+        // DHCP keeps trying, and a lease replaces the link-local address
+        if let Some(address) = self.fallback.poll(Self::now()) {
+            info!("No DHCP lease, link-local address: {}", address);
+            self.set_ipv4_address(Some(address));
+        }
+    }
+
+    // Replaces the IPv4 address, leaving the IPv6 addresses alone
+    fn set_ipv4_address(&mut self, address: Option<Ipv4Cidr>) {
+        self.interface.update_ip_addrs(|addrs| {
+            addrs.retain(|cidr| !matches!(cidr, IpCidr::Ipv4(_)));
+            if let Some(address) = address {
+                addrs.push(IpCidr::Ipv4(address)).unwrap();
+            }
+        });
     }
 
     pub fn channel<const N:usize>(&mut self, port: u16, storage: &'a mut NetworkChannelStorage<N>) -> NetworkChannel<'a, N> {
@@ -376,22 +483,38 @@ where CLOCK::Instant: IntoInstant {
 
         let socket = tcp::Socket::new(rx_buffer, tx_buffer);
         let handle = self.sockets.add(socket);
-    
-        let socket = self.sockets.get_mut::<tcp::Socket>(handle);
-        socket.listen(port).ok();
-      
+
         let (net_send, app_recv) = storage.receiver.split();
         let (app_send, net_recv) = storage.sender.split();
 
         NetworkChannel {
             net: NetworkEndpoint { 
-                send: SendChannel { handle, receiver: net_recv },
-                recv: RecvChannel { port, handle, sender: net_send, state: RecvChannelState::Listening },
+                send: SendChannel::new(handle, AppReceiver(net_recv)),
+                recv: RecvChannel::new(port, handle, AppSender(net_send), &mut self.sockets),
             },
             app: ApplicationEndpoint { send: app_send, recv: app_recv }
         }
     }
 
+    /// Answers mDNS queries for the responder's host name and services
+    pub fn advertise(&mut self, responder: Responder<'a>, storage: &'a mut MdnsStorage) {
+        self.mdns = Some(Mdns::new(
+            responder,
+            udp::PacketBuffer::new(&mut storage.rx_metadata[..], &mut storage.rx_storage[..]),
+            udp::PacketBuffer::new(&mut storage.tx_metadata[..], &mut storage.tx_storage[..]),
+            &mut self.interface,
+            &mut self.sockets));
+    }
+
+    /// Answers discovery queries with the announcement
+    pub fn discoverable(&mut self, announcement: DeviceAnnouncement, storage: &'a mut DiscoveryStorage) {
+        self.discovery = Some(Discovery::new(
+            announcement,
+            udp::PacketBuffer::new(&mut storage.rx_metadata[..], &mut storage.rx_storage[..]),
+            udp::PacketBuffer::new(&mut storage.tx_metadata[..], &mut storage.tx_storage[..]),
+            &mut self.sockets));
+    }
+
     fn now() -> Instant {
         CLOCK::now().into_instant()
     }
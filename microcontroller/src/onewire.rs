@@ -0,0 +1,183 @@
+use crate::{ Duration, Instant, Mono };
+
+use crc::{ Crc, CRC_8_MAXIM_DOW };
+use embedded_hal::digital::{ InputPin, OutputPin };
+use heapless::Vec;
+use rtic_monotonics::Monotonic;
+
+/*
+A bit-banged 1-Wire bus master on an open drain pin, with an external pull-up
+of ~4.7K. Each bit is a slot of ~70uS. Only its start, where the bus has an
+upper limit on the timing, is busy-waited on Mono: the 60uS low pulse of a 0,
+and with interrupts off, the short low pulse of a 1 or a read and sampling
+~12uS into a read. That's at most ~12uS, which the DHT edge interrupts
+tolerate. The rest of the slot is awaited, so other tasks run between bits,
+and being late back only stretches the gap between slots, which the bus
+allows. A transaction holds up the other tasks at its priority for at most
+60uS at a time, rather than for the whole transaction.
+
+Parasite power isn't supported: each device needs its own supply.
+ */
+const RESET: Duration = Duration::micros(480);
+const PRESENCE: Duration = Duration::micros(70);
+const SLOT: Duration = Duration::micros(70);
+const WRITE_ONE: Duration = Duration::micros(6);
+const WRITE_ZERO: Duration = Duration::micros(60);
+const READ_INIT: Duration = Duration::micros(3);
+const READ_SAMPLE: Duration = Duration::micros(12);
+
+const SEARCH_ROM: u8 = 0xf0;
+const MATCH_ROM: u8 = 0x55;
+const SKIP_ROM: u8 = 0xcc;
+
+pub const CRC: Crc<u8> = Crc::<u8>::new(&CRC_8_MAXIM_DOW);
+
+// The 64 bit ROM id of a device: family code, serial number then CRC,
+// first byte in the lowest bits
+pub type Rom = u64;
+
+pub struct OneWire<PIN> {
+    pin: PIN,
+}
+
+fn wait_until(instant: Instant) {
+    while Mono::now() < instant {}
+}
+
+impl <PIN> OneWire<PIN>
+where PIN: InputPin + OutputPin {
+    pub fn new(mut pin: PIN) -> Self {
+        pin.set_high().unwrap();
+        OneWire { pin }
+    }
+
+    // Returns true if any device answered with a presence pulse
+    pub async fn reset(&mut self) -> bool {
+        self.pin.set_low().unwrap();
+        Mono::delay(RESET).await;
+        // The presence pulse lasts at least 60uS, so this sample has some slack
+        self.pin.set_high().unwrap();
+        wait_until(Mono::now() + PRESENCE);
+        let presence = self.pin.is_low().unwrap();
+        Mono::delay(RESET - PRESENCE).await;
+        presence
+    }
+
+    async fn write_bit(&mut self, bit: bool) {
+        let start = if bit {
+            cortex_m::interrupt::free(|_| {
+                let start = Mono::now();
+                self.pin.set_low().unwrap();
+                wait_until(start + WRITE_ONE);
+                self.pin.set_high().unwrap();
+                start
+            })
+        } else {
+            let start = Mono::now();
+            self.pin.set_low().unwrap();
+            wait_until(start + WRITE_ZERO);
+            self.pin.set_high().unwrap();
+            start
+        };
+        Mono::delay_until(start + SLOT).await;
+    }
+
+    async fn read_bit(&mut self) -> bool {
+        let (start, bit) = cortex_m::interrupt::free(|_| {
+            let start = Mono::now();
+            self.pin.set_low().unwrap();
+            wait_until(start + READ_INIT);
+            self.pin.set_high().unwrap();
+            wait_until(start + READ_SAMPLE);
+            (start, self.pin.is_high().unwrap())
+        });
+        Mono::delay_until(start + SLOT).await;
+        bit
+    }
+
+    pub async fn write_byte(&mut self, byte: u8) {
+        for bit in 0..8 {
+            self.write_bit(byte & (1 << bit) != 0).await;
+        }
+    }
+
+    pub async fn read_byte(&mut self) -> u8 {
+        let mut byte = 0;
+        for bit in 0..8 {
+            byte |= (self.read_bit().await as u8) << bit;
+        }
+        byte
+    }
+
+    pub async fn read(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            *byte = self.read_byte().await;
+        }
+    }
+
+    // Reset, then address one device, or all of them
+    pub async fn select(&mut self, rom: Option<Rom>) -> bool {
+        if !self.reset().await {
+            return false;
+        }
+        match rom {
+            Some(rom) => {
+                self.write_byte(MATCH_ROM).await;
+                for byte in rom.to_le_bytes() {
+                    self.write_byte(byte).await;
+                }
+            },
+            None => self.write_byte(SKIP_ROM).await,
+        }
+        true
+    }
+
+    // The ROM search from Maxim application note 187: each pass walks the tree
+    // of ids, taking the 0 branch at a new discrepancy and the 1 branch at the
+    // last one, until there are no discrepancies left to explore.
+    pub async fn search<const N: usize>(&mut self) -> Vec<Rom, N> {
+        let mut roms = Vec::new();
+        let mut rom: Rom = 0;
+        let mut last_discrepancy = 0;
+        loop {
+            if !self.reset().await {
+                break;
+            }
+            self.write_byte(SEARCH_ROM).await;
+
+            let mut discrepancy = 0;
+            for bit in 1..=64 {
+                let (id, complement) = (self.read_bit().await, self.read_bit().await);
+                let direction = match (id, complement) {
+                    // nothing answered
+                    (true, true) => return roms,
+                    (id, complement) if id != complement => id,
+                    // devices differ at this bit
+                    _ => {
+                        let direction = match bit.cmp(&last_discrepancy) {
+                            core::cmp::Ordering::Equal => true,
+                            core::cmp::Ordering::Greater => false,
+                            core::cmp::Ordering::Less => rom & (1 << (bit - 1)) != 0,
+                        };
+                        if !direction {
+                            discrepancy = bit;
+                        }
+                        direction
+                    },
+                };
+                rom = (rom & !(1 << (bit - 1))) | (direction as Rom) << (bit - 1);
+                self.write_bit(direction).await;
+            }
+
+            let bytes = rom.to_le_bytes();
+            if CRC.checksum(&bytes[..7]) == bytes[7] && roms.push(rom).is_err() {
+                break;
+            }
+            last_discrepancy = discrepancy;
+            if last_discrepancy == 0 {
+                break;
+            }
+        }
+        roms
+    }
+}
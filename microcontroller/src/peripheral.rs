@@ -0,0 +1,130 @@
+use crate::proto::{
+    self,
+    DeviceResponse,
+    PeripheralInfo,
+    PeripheralsResponse,
+    Request,
+    RequestError,
+    Response,
+    Response_::Peripheral as ResponsePeripheral,
+};
+
+use aux_core::registry::{ Description, DispatchError, Route };
+use heapless::{ String, Vec };
+use rtic_sync::channel::{ Sender, TrySendError };
+
+pub use aux_core::registry::{ Peripheral, Registry };
+
+/*
+Adapters that let the request dispatcher route to peripheral tasks through the
+registry. A peripheral registers with its description from aux_core::peripherals
+(its name, the field number of its requests in the Request oneof, and of the
+commands it handles), a function that picks those requests out, and how to
+reach its task: a channel, or spawning the task for each request. The
+registry only routes: each task still handles its requests and sends its own
+responses, so a new peripheral also needs its messages in aux.proto and a task.
+
+The dispatcher never waits for a peripheral, so a slow one can't hold up
+requests for the others. When a request can't be delivered, the client gets a
+RequestError instead of a response. A channel holds REQUEST_CAPACITY requests
+(2) and a spawned task one, so a client pipelining more than that to one
+peripheral gets BUSY for the rest, where it used to wait.
+ */
+pub const PERIPHERALS: usize = 8;
+
+// Takes a peripheral's requests out of a Request, or hands it back
+pub type Extract<T> = fn(Request) -> Result<T, Request>;
+
+// The Extract for the requests in a variant of the Request oneof
+#[macro_export]
+macro_rules! extract {
+    ($variant:ident) => {
+        |request: $crate::proto::Request| match request.peripheral {
+            Some($crate::proto::Request_::Peripheral::$variant(request)) => Ok(request),
+            peripheral => Err($crate::proto::Request { peripheral }),
+        }
+    };
+}
+
+// A peripheral task that receives its requests on a channel
+pub struct Channel<T: 'static, const N: usize> {
+    description: Description,
+    extract: Extract<T>,
+    sender: Sender<'static, T, N>,
+}
+
+impl <T, const N: usize> Channel<T, N> {
+    pub fn new(description: Description, extract: Extract<T>, sender: Sender<'static, T, N>) -> Self {
+        Channel { description, extract, sender }
+    }
+}
+
+impl <T, const N: usize> Peripheral<Request> for Channel<T, N> {
+    fn description(&self) -> Description {
+        self.description
+    }
+
+    fn route(&mut self, request: Request) -> Route<Request> {
+        match (self.extract)(request) {
+            Ok(request) => match self.sender.try_send(request) {
+                Ok(()) => Route::Accepted,
+                Err(TrySendError::Full(_)) => Route::Busy,
+                Err(TrySendError::NoReceiver(_)) => Route::Closed,
+            },
+            Err(request) => Route::Unrouted(request),
+        }
+    }
+}
+
+// A peripheral task spawned for each request, which is busy until it returns
+pub struct Spawn<T> {
+    description: Description,
+    extract: Extract<T>,
+    spawn: fn(T) -> Result<(), T>,
+}
+
+impl <T> Spawn<T> {
+    pub fn new(description: Description, extract: Extract<T>, spawn: fn(T) -> Result<(), T>) -> Self {
+        Spawn { description, extract, spawn }
+    }
+}
+
+impl <T> Peripheral<Request> for Spawn<T> {
+    fn description(&self) -> Description {
+        self.description
+    }
+
+    fn route(&mut self, request: Request) -> Route<Request> {
+        match (self.extract)(request) {
+            Ok(request) => match (self.spawn)(request) {
+                Ok(()) => Route::Accepted,
+                Err(_) => Route::Busy,
+            },
+            Err(request) => Route::Unrouted(request),
+        }
+    }
+}
+
+pub fn error_response(error: DispatchError) -> Response {
+    let (error, peripheral) = match error {
+        DispatchError::Unknown => (proto::DispatchError::Unknown, 0),
+        DispatchError::Busy(description) => (proto::DispatchError::Busy, description.tag),
+        DispatchError::Closed(description) => (proto::DispatchError::Closed, description.tag),
+    };
+    Response { peripheral: Some(ResponsePeripheral::RequestError(RequestError { error, peripheral })) }
+}
+
+pub fn peripherals_response<const N: usize>(registry: &Registry<'_, Request, N>) -> Response {
+    let peripherals = registry.descriptions()
+        .map(|description| PeripheralInfo {
+            name: String::try_from(description.name).unwrap_or_default(),
+            tag: description.tag,
+            commands: Vec::from_slice(description.commands).unwrap_or_default(),
+        })
+        .collect();
+    Response { peripheral: Some(ResponsePeripheral::Peripherals(PeripheralsResponse { peripherals })) }
+}
+
+pub fn device_response(device: &DeviceResponse) -> Response {
+    Response { peripheral: Some(ResponsePeripheral::Device(device.clone())) }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    proto::{
+        PwmOutput,
+        PwmRequest,
+        PwmRequest_,
+        PwmResponse,
+        Response,
+        Response_::Peripheral as ResponsePeripheral,
+        SettingKey,
+    },
+    settings::{ SettingsSender, Value },
+    shell::ResponseSender,
+};
+
+use defmt::{ info, warn };
+use hal::{
+    pac::TIM3,
+    time::Hertz,
+    timer::PwmHzManager,
+};
+#[cfg(feature = "light-tim4")]
+use hal::pac::TIM4;
+use rtic_sync::signal::SignalWriter;
+
+pub const DEFAULT_FREQUENCY: Hertz = Hertz::kHz(25);
+// Above this the duty resolution of a 100MHz timer clock falls below 1000 steps
+const MAX_FREQUENCY: Hertz = Hertz::kHz(100);
+
+// What a PWM request or a stored setting can set the frequency to
+pub fn valid_frequency(hz: u32) -> bool {
+    hz > 0 && Hertz::Hz(hz) <= MAX_FREQUENCY
+}
+
+pub type FanTimer = PwmHzManager<TIM3>;
+#[cfg(feature = "light-tim4")]
+pub type LightTimer = PwmHzManager<TIM4>;
+// The light shares the fan's timer
+#[cfg(not(feature = "light-tim4"))]
+pub type LightTimer = ();
+
+// Owns the PWM timers. Changing the frequency changes the auto-reload value,
+// and so the meaning of every duty cycle on the timer, so the fan and light
+// are signalled to set their duty again.
+pub struct Pwm {
+    fan: FanTimer,
+    #[cfg_attr(not(feature = "light-tim4"), allow(dead_code))]
+    light: LightTimer,
+    fan_rescale: SignalWriter<'static, ()>,
+    light_rescale: SignalWriter<'static, ()>,
+    settings: SettingsSender,
+    responses: ResponseSender,
+}
+
+impl Pwm {
+    pub fn new(
+        fan: FanTimer,
+        light: LightTimer,
+        fan_rescale: SignalWriter<'static, ()>,
+        light_rescale: SignalWriter<'static, ()>,
+        settings: SettingsSender,
+        responses: ResponseSender,
+    ) -> Self {
+        Pwm {
+            fan,
+            light,
+            fan_rescale,
+            light_rescale,
+            settings,
+            responses,
+        }
+    }
+
+    pub async fn process(&mut self, request: PwmRequest) {
+        let output = request.output;
+        match request {
+            PwmRequest { command: Some(PwmRequest_::Command::Set(set)), .. } => {
+                let frequency = Hertz::Hz(set.frequency_hz);
+                if valid_frequency(set.frequency_hz) {
+                    info!("PWM output {} frequency {}", output.0, frequency);
+                    self.set_frequency(output, frequency);
+                    if self.settings.try_send((setting_key(output), Value::Uint(set.frequency_hz))).is_err() {
+                        warn!("PWM frequency not saved");
+                    }
+                } else {
+                    warn!("PWM frequency {} out of range", frequency);
+                }
+            },
+            PwmRequest { command: Some(PwmRequest_::Command::Get(_)), .. } => { },
+            PwmRequest { command: _, .. } => {
+                warn!("Unknown command for PWM");
+            }
+        }
+
+        let response = self.response(output);
+        self.responses.send(response).await.unwrap();
+    }
+
+    pub fn set_frequency(&mut self, output: PwmOutput, frequency: Hertz) {
+        match output {
+            #[cfg(feature = "light-tim4")]
+            PwmOutput::Light => {
+                self.light.set_period(frequency);
+                self.light_rescale.write(());
+            },
+            _ => {
+                self.fan.set_period(frequency);
+                self.fan_rescale.write(());
+                #[cfg(not(feature = "light-tim4"))]
+                self.light_rescale.write(());
+            },
+        }
+    }
+
+    // Set the frequencies saved from before the last reset. One out of range,
+    // saved by an older build, is ignored: the timer can't be set to it.
+    pub fn restore(&mut self, fan: Option<u32>, light: Option<u32>) {
+        match fan {
+            Some(hz) if valid_frequency(hz) => {
+                info!("PWM restore fan frequency {}Hz", hz);
+                self.set_frequency(PwmOutput::Fan, Hertz::Hz(hz));
+            },
+            Some(hz) => warn!("PWM saved fan frequency {}Hz out of range", hz),
+            None => { },
+        }
+        #[cfg(feature = "light-tim4")]
+        match light {
+            Some(hz) if valid_frequency(hz) => {
+                info!("PWM restore light frequency {}Hz", hz);
+                self.set_frequency(PwmOutput::Light, Hertz::Hz(hz));
+            },
+            Some(hz) => warn!("PWM saved light frequency {}Hz out of range", hz),
+            None => { },
+        }
+        #[cfg(not(feature = "light-tim4"))]
+        let _ = light;
+    }
+
+    fn response(&self, output: PwmOutput) -> Response {
+        let (frequency, resolution) = match output {
+            #[cfg(feature = "light-tim4")]
+            PwmOutput::Light => (self.light.get_period(), self.light.get_max_duty()),
+            _ => (self.fan.get_period(), self.fan.get_max_duty()),
+        };
+
+        Response { peripheral: Some(ResponsePeripheral::Pwm(PwmResponse {
+            output,
+            frequency_hz: frequency.raw(),
+            resolution: resolution as u32,
+            shared: cfg!(not(feature = "light-tim4")),
+        })) }
+    }
+}
+
+// The setting for the timer that drives the output
+fn setting_key(output: PwmOutput) -> SettingKey {
+    match output {
+        #[cfg(feature = "light-tim4")]
+        PwmOutput::Light => SettingKey::LightPwmHz,
+        _ => SettingKey::FanPwmHz,
+    }
+}
@@ -0,0 +1,459 @@
+use crate::{
+    proto::{
+        Response,
+        Response_::Peripheral as ResponsePeripheral,
+        Setting,
+        SettingKey,
+        SettingsError,
+        SettingsRequest,
+        SettingsRequest_,
+        SettingsResponse,
+    },
+    pwm,
+    shell::ResponseSender,
+};
+
+use crc::{ Crc, CRC_16_IBM_3740 };
+use defmt::{ debug, info, warn, Format };
+use futures::{ select_biased, FutureExt };
+use hal::{
+    flash::{ FlashExt, LockedFlash },
+    pac::FLASH,
+};
+use heapless::{ LinearMap, String, Vec };
+use rtic_sync::channel::{ Receiver, Sender };
+use smoltcp::wire::{ Ipv4Address, Ipv4Cidr };
+use aux_core::hostname;
+
+/*
+Settings are a log of records in flash sectors 6 and 7, which memory.x keeps
+out of the FLASH region. Only one sector holds the current log. It starts with
+a sector header
+
+  sequence: u32, marker: u32 (little endian)
+
+and then each record is
+
+  key: u8, len: u8, value: [u8; len], crc: u16 (little endian)
+
+with the CRC over the key, length and value. A later record for a key
+replaces an earlier one, and a zero length record removes the key. Erased
+flash reads 0xff, which marks the end of the log.
+
+Records are appended until the sector is full. Then the current values are
+written to the other sector, and its header last, with the next sequence
+number: only once the marker is written is the new log valid, and only then
+is the old sector erased. A reset at any point leaves one complete log, and
+at boot the valid sector with the highest sequence is used. Writes alternate
+between the two sectors, so each is erased once per sector full of writes.
+
+Erasing a 128K sector takes a second or two, during which the CPU stalls
+fetching from flash, so USB may drop out while the log is compacted.
+ */
+// Flash sector numbers and their offsets from the start of flash
+const SECTORS: [(u8, usize); 2] = [(6, 0x4_0000), (7, 0x6_0000)];
+const SIZE: usize = 0x2_0000;
+const ERASED: u8 = 0xff;
+// "SETv", with no erased bytes so a torn write can't complete it
+const MARKER: u32 = 0x7654_4553;
+const SECTOR_HEADER: usize = 8;
+const HEADER: usize = 2;
+const CRC_SIZE: usize = 2;
+pub const MAX_TEXT: usize = 32;
+// A type tag, then the value
+const MAX_VALUE: usize = 1 + MAX_TEXT;
+const MAX_RECORD: usize = HEADER + MAX_VALUE + CRC_SIZE;
+const CAPACITY: usize = 16;
+const CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_3740);
+
+pub const UPDATE_CAPACITY: usize = 4;
+pub const REQUEST_CAPACITY: usize = 2;
+pub type Update = (SettingKey, Value);
+pub type SettingsSender = Sender<'static, Update, UPDATE_CAPACITY>;
+
+const FLOAT: u8 = 0;
+const UINT: u8 = 1;
+const TEXT: u8 = 2;
+
+#[derive(Clone, PartialEq)]
+pub enum Value {
+    Float(f32),
+    Uint(u32),
+    Text(String<MAX_TEXT>),
+}
+
+#[derive(Clone, Copy, PartialEq, Format)]
+pub enum Error {
+    Flash,
+    Full,
+    Invalid,
+}
+
+impl Value {
+    fn encode(&self) -> Vec<u8, MAX_VALUE> {
+        let mut data = Vec::new();
+        match self {
+            Value::Float(value) => {
+                data.push(FLOAT).unwrap();
+                data.extend_from_slice(&value.to_le_bytes()).unwrap();
+            },
+            Value::Uint(value) => {
+                data.push(UINT).unwrap();
+                data.extend_from_slice(&value.to_le_bytes()).unwrap();
+            },
+            Value::Text(value) => {
+                data.push(TEXT).unwrap();
+                data.extend_from_slice(value.as_bytes()).unwrap();
+            },
+        }
+        data
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        match data.split_first()? {
+            (&FLOAT, bytes) => Some(Value::Float(f32::from_le_bytes(bytes.try_into().ok()?))),
+            (&UINT, bytes) => Some(Value::Uint(u32::from_le_bytes(bytes.try_into().ok()?))),
+            (&TEXT, bytes) => Some(Value::Text(String::try_from(core::str::from_utf8(bytes).ok()?).ok()?)),
+            _ => None,
+        }
+    }
+}
+
+enum Kind {
+    Float,
+    Uint,
+    // A PWM frequency the timer can run at
+    Frequency,
+    Port,
+    // Text, parsed when it's set so a bad value can't stop the network coming up
+    Cidr,
+    Address,
+    HostName,
+}
+
+fn kind(key: SettingKey) -> Option<Kind> {
+    match key {
+        SettingKey::FanDuty | SettingKey::LightDuty => Some(Kind::Float),
+        SettingKey::DhcpServer | SettingKey::DhtSensors => Some(Kind::Uint),
+        SettingKey::FanPwmHz | SettingKey::LightPwmHz => Some(Kind::Frequency),
+        SettingKey::GrblPort | SettingKey::RpcPort => Some(Kind::Port),
+        SettingKey::Ipv4Address => Some(Kind::Cidr),
+        SettingKey::Ipv4Gateway => Some(Kind::Address),
+        SettingKey::Hostname => Some(Kind::HostName),
+        _ => None,
+    }
+}
+
+pub struct Settings {
+    flash: LockedFlash,
+    // the index in SECTORS of the sector holding the log, and its sequence
+    // number, or None before the first write
+    active: Option<(usize, u32)>,
+    // offset of the first free byte in the sector
+    end: usize,
+    values: LinearMap<u8, Vec<u8, MAX_VALUE>, CAPACITY>,
+}
+
+impl Settings {
+    pub fn new(flash: FLASH) -> Self {
+        let mut settings = Settings {
+            flash: LockedFlash::new(flash),
+            active: None,
+            end: SIZE,
+            values: LinearMap::new(),
+        };
+        settings.active = settings.newest();
+        if let Some((sector, sequence)) = settings.active {
+            settings.scan(sector);
+            info!("{} settings, {} bytes of log in sector {} ({})",
+                settings.values.len(), settings.end, SECTORS[sector].0, sequence);
+        } else {
+            info!("no settings");
+        }
+        settings
+    }
+
+    // The valid sector with the highest sequence number
+    fn newest(&self) -> Option<(usize, u32)> {
+        let data = self.flash.read();
+        (0..SECTORS.len())
+            .filter_map(|sector| {
+                let offset = SECTORS[sector].1;
+                let header = &data[offset..offset + SECTOR_HEADER];
+                let sequence = u32::from_le_bytes(header[0..4].try_into().unwrap());
+                let marker = u32::from_le_bytes(header[4..8].try_into().unwrap());
+                (marker == MARKER).then_some((sector, sequence))
+            })
+            .max_by_key(|(_, sequence)| *sequence)
+    }
+
+    fn scan(&mut self, sector: usize) {
+        let offset = SECTORS[sector].1;
+        let data = &self.flash.read()[offset..offset + SIZE];
+        let mut offset = SECTOR_HEADER;
+        while offset + HEADER <= SIZE && data[offset] != ERASED {
+            let (key, len) = (data[offset], data[offset + 1] as usize);
+            let size = HEADER + len + CRC_SIZE;
+            if len > MAX_VALUE || offset + size > SIZE {
+                // A torn write: nothing after it can be trusted, so treat the
+                // sector as full and compact it on the next write.
+                warn!("settings record at {} has length {}", offset, len);
+                offset = SIZE;
+                break;
+            }
+
+            let (record, crc) = data[offset..offset + size].split_at(size - CRC_SIZE);
+            if CRC.checksum(record) == u16::from_le_bytes([crc[0], crc[1]]) {
+                let value = &record[HEADER..];
+                if value.is_empty() {
+                    self.values.remove(&key);
+                } else if self.values.insert(key, Vec::from_slice(value).unwrap()).is_err() {
+                    warn!("too many settings, ignoring {}", key);
+                }
+            } else {
+                warn!("settings record at {} failed CRC", offset);
+            }
+            offset += size;
+        }
+        self.end = offset;
+    }
+
+    pub fn get(&self, key: SettingKey) -> Option<Value> {
+        self.values.get(&(key.0 as u8)).and_then(|data| Value::decode(data))
+    }
+
+    pub fn float(&self, key: SettingKey) -> Option<f32> {
+        match self.get(key) {
+            Some(Value::Float(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn uint(&self, key: SettingKey) -> Option<u32> {
+        match self.get(key) {
+            Some(Value::Uint(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, key: SettingKey) -> Option<String<MAX_TEXT>> {
+        match self.get(key) {
+            Some(Value::Text(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, key: SettingKey, value: &Value) -> Result<(), Error> {
+        let data = value.encode();
+        if self.values.get(&(key.0 as u8)) == Some(&data) {
+            return Ok(());
+        }
+        self.write(key.0 as u8, &data)
+    }
+
+    pub fn remove(&mut self, key: SettingKey) -> Result<(), Error> {
+        match self.values.contains_key(&(key.0 as u8)) {
+            true => self.write(key.0 as u8, &[]),
+            false => Ok(()),
+        }
+    }
+
+    pub fn reset(&mut self) -> Result<(), Error> {
+        info!("settings factory reset");
+        self.values.clear();
+        self.compact()
+    }
+
+    fn write(&mut self, key: u8, data: &[u8]) -> Result<(), Error> {
+        if !data.is_empty() && !self.values.contains_key(&key) && self.values.len() == CAPACITY {
+            return Err(Error::Full);
+        }
+
+        if self.active.is_none() || self.end + HEADER + data.len() + CRC_SIZE > SIZE {
+            self.compact()?;
+        }
+
+        self.append(key, data)?;
+        if data.is_empty() {
+            self.values.remove(&key);
+        } else {
+            self.values.insert(key, Vec::from_slice(data).unwrap()).unwrap();
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, key: u8, data: &[u8]) -> Result<(), Error> {
+        let mut record: Vec<u8, MAX_RECORD> = Vec::new();
+        record.push(key).unwrap();
+        record.push(data.len() as u8).unwrap();
+        record.extend_from_slice(data).unwrap();
+        let crc = CRC.checksum(&record);
+        record.extend_from_slice(&crc.to_le_bytes()).unwrap();
+
+        let (sector, _) = self.active.ok_or(Error::Flash)?;
+        debug!("settings record for {} at {}", key, self.end);
+        self.program(sector, self.end, &record)?;
+        self.end += record.len();
+        Ok(())
+    }
+
+    // Writes the current values to the other sector, which then replaces the
+    // active one
+    fn compact(&mut self) -> Result<(), Error> {
+        let (spare, sequence) = match self.active {
+            Some((sector, sequence)) => ((sector + 1) % SECTORS.len(), sequence.wrapping_add(1)),
+            None => (0, 1),
+        };
+        info!("compacting settings into sector {}", SECTORS[spare].0);
+        let previous = self.active.replace((spare, sequence));
+        if let Err(err) = self.write_log(spare, sequence) {
+            // The old log is still the valid one. Compact again on the next
+            // write.
+            self.active = previous;
+            self.end = SIZE;
+            return Err(err);
+        }
+
+        // The new log is complete, so failing to erase the old one only leaves
+        // a stale log with a lower sequence number
+        if let Some((sector, _)) = previous {
+            if self.erase(sector).is_err() {
+                warn!("settings sector {} not erased", SECTORS[sector].0);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_log(&mut self, sector: usize, sequence: u32) -> Result<(), Error> {
+        self.erase(sector)?;
+        self.end = SECTOR_HEADER;
+        let values = self.values.clone();
+        for (key, data) in values.iter() {
+            self.append(*key, data)?;
+        }
+
+        let mut header = [0u8; SECTOR_HEADER];
+        header[0..4].copy_from_slice(&sequence.to_le_bytes());
+        header[4..8].copy_from_slice(&MARKER.to_le_bytes());
+        self.program(sector, 0, &header)
+    }
+
+    fn program(&mut self, sector: usize, offset: usize, data: &[u8]) -> Result<(), Error> {
+        self.flash.unlocked().program(SECTORS[sector].1 + offset, data.iter()).map_err(|_| Error::Flash)
+    }
+
+    fn erase(&mut self, sector: usize) -> Result<(), Error> {
+        self.flash.unlocked().erase(SECTORS[sector].0).map_err(|_| Error::Flash)
+    }
+
+    fn settings(&self) -> Vec<Setting, CAPACITY> {
+        self.values.iter()
+            .filter_map(|(key, data)| Some(setting(SettingKey(*key as i32), &Value::decode(data)?)))
+            .collect()
+    }
+}
+
+fn setting(key: SettingKey, value: &Value) -> Setting {
+    let mut setting = Setting { key, ..Setting::default() };
+    match value {
+        Value::Float(value) => setting.float_value = *value,
+        Value::Uint(value) => setting.uint_value = *value,
+        Value::Text(value) => setting.string_value = value.clone(),
+    }
+    setting
+}
+
+fn value(setting: &Setting) -> Option<Value> {
+    match kind(setting.key)? {
+        Kind::Float => Some(Value::Float(setting.float_value)),
+        Kind::Uint => Some(Value::Uint(setting.uint_value)),
+        Kind::Frequency => pwm::valid_frequency(setting.uint_value)
+            .then_some(Value::Uint(setting.uint_value)),
+        Kind::Port => u16::try_from(setting.uint_value).ok()
+            .filter(|port| *port != 0)
+            .map(|_| Value::Uint(setting.uint_value)),
+        Kind::Cidr => {
+            setting.string_value.parse::<Ipv4Cidr>().ok()?;
+            Some(Value::Text(setting.string_value.clone()))
+        },
+        Kind::Address => {
+            setting.string_value.parse::<Ipv4Address>().ok()?;
+            Some(Value::Text(setting.string_value.clone()))
+        },
+        Kind::HostName => hostname::valid(&setting.string_value)
+            .then(|| Value::Text(setting.string_value.clone())),
+    }
+}
+
+// Owns the settings: tasks send updates to persist, and clients read, write
+// and reset them over RPC. Values written over RPC take effect at the next boot.
+pub struct SettingsTask<'a> {
+    settings: Settings,
+    updates: Receiver<'a, Update, UPDATE_CAPACITY>,
+    requests: Receiver<'a, SettingsRequest, REQUEST_CAPACITY>,
+    responses: ResponseSender,
+}
+
+impl <'a> SettingsTask<'a> {
+    pub fn new(
+        settings: Settings,
+        updates: Receiver<'a, Update, UPDATE_CAPACITY>,
+        requests: Receiver<'a, SettingsRequest, REQUEST_CAPACITY>,
+        responses: ResponseSender,
+    ) -> Self {
+        SettingsTask { settings, updates, requests, responses }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            select_biased! {
+                update = self.updates.recv().fuse() => match update {
+                    Ok((key, value)) => {
+                        if let Err(err) = self.settings.set(key, &value) {
+                            warn!("Error saving setting {}: {}", key.0, err);
+                        }
+                    },
+                    Err(_) => break,
+                },
+                request = self.requests.recv().fuse() => match request {
+                    Ok(request) => self.process(request).await,
+                    Err(_) => break,
+                },
+            }
+        }
+    }
+
+    async fn process(&mut self, request: SettingsRequest) {
+        let result = match request {
+            SettingsRequest { command: Some(SettingsRequest_::Command::Set(set)) } => {
+                set.settings.iter().try_for_each(|setting| match value(setting) {
+                    Some(value) => self.settings.set(setting.key, &value),
+                    None => Err(Error::Invalid),
+                })
+            },
+            SettingsRequest { command: Some(SettingsRequest_::Command::Remove(remove)) } => {
+                remove.keys.iter().try_for_each(|key| self.settings.remove(*key))
+            },
+            SettingsRequest { command: Some(SettingsRequest_::Command::FactoryReset(_)) } => {
+                self.settings.reset()
+            },
+            SettingsRequest { command: Some(SettingsRequest_::Command::Get(_)) } => Ok(()),
+            SettingsRequest { command: _ } => {
+                warn!("Unknown command for settings");
+                Err(Error::Invalid)
+            }
+        };
+
+        let error = match result {
+            Ok(()) => SettingsError::None,
+            Err(Error::Flash) => SettingsError::Flash,
+            Err(Error::Full) => SettingsError::Full,
+            Err(Error::Invalid) => SettingsError::Invalid,
+        };
+
+        self.responses.send(Response { peripheral: Some(ResponsePeripheral::Settings(SettingsResponse {
+            settings: self.settings.settings(),
+            error,
+        })) }).await.unwrap();
+    }
+}
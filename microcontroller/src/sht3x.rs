@@ -0,0 +1,65 @@
+use crate::{ Duration, Mono };
+
+use crc::{ Crc, CRC_8_NRSC_5 };
+use embedded_hal::i2c::I2c;
+use rtic_monotonics::Monotonic;
+
+/*
+Sensirion SHT30/31/35 temperature and humidity sensor, read with single shot,
+high repeatability measurements without clock stretching. Every 16 bit word
+from the sensor is followed by a CRC-8 (polynomial 0x31, initial 0xff).
+ */
+pub const ADDRESSES: [u8; 2] = [0x44, 0x45];
+
+const READ_STATUS: [u8; 2] = [0xf3, 0x2d];
+const MEASURE_HIGH: [u8; 2] = [0x24, 0x00];
+// The longest high repeatability measurement
+const MEASUREMENT: Duration = Duration::millis(16);
+const CRC: Crc<u8> = Crc::<u8>::new(&CRC_8_NRSC_5);
+
+pub struct Measurement {
+    pub temperature_celsius: f32,
+    pub humidity_percent: f32,
+}
+
+#[derive(Debug)]
+pub enum Error<E> {
+    I2c(E),
+    Checksum,
+}
+
+pub struct Sht3x {
+    address: u8,
+}
+
+fn word(data: &[u8]) -> Option<u16> {
+    match CRC.checksum(&data[..2]) == data[2] {
+        true => Some(u16::from_be_bytes([data[0], data[1]])),
+        false => None,
+    }
+}
+
+impl Sht3x {
+    // Returns the sensor if one answers at the address with a valid status word
+    pub fn detect<I2C: I2c>(i2c: &mut I2C, address: u8) -> Option<Self> {
+        let mut status = [0u8; 3];
+        i2c.write(address, &READ_STATUS).ok()?;
+        i2c.read(address, &mut status).ok()?;
+        word(&status)?;
+        Some(Sht3x { address })
+    }
+
+    pub async fn read<I2C: I2c>(&self, i2c: &mut I2C) -> Result<Measurement, Error<I2C::Error>> {
+        i2c.write(self.address, &MEASURE_HIGH).map_err(Error::I2c)?;
+        Mono::delay(MEASUREMENT).await;
+        let mut data = [0u8; 6];
+        i2c.read(self.address, &mut data).map_err(Error::I2c)?;
+
+        let temperature = word(&data[0..3]).ok_or(Error::Checksum)?;
+        let humidity = word(&data[3..6]).ok_or(Error::Checksum)?;
+        Ok(Measurement {
+            temperature_celsius: -45.0 + 175.0 * temperature as f32 / 65535.0,
+            humidity_percent: 100.0 * humidity as f32 / 65535.0,
+        })
+    }
+}
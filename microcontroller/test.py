@@ -1,8 +1,9 @@
 #!.venv/bin/python
-import client, asyncio
+import client, asyncio, sys
 
+# The board's host name or address, or none to find it by discovery
 async def connect():
-    connection = await client.connect()
+    connection = await client.connect(sys.argv[1] if len(sys.argv) > 1 else None)
 
     async def send():
         for i in range(10):